@@ -0,0 +1,857 @@
+use std::any::TypeId;
+use std::path::PathBuf;
+
+use audible_util::cli::{ChapterEncoding, FfmpegLogLevel, LogFileFormat, MergeDirection, OutputType, OverlapStrategy, SplitStructure, TitleCase};
+use audible_util::convert::ConvertOptions;
+use audible_util::models::{ChapterNamingFormat, SchemaName, TitleDisambiguation, METADATA_MAP_FIELDS};
+use clap::{ArgAction, ArgGroup, CommandFactory, Parser, Subcommand};
+
+/// Command line arguments of audible-util
+#[derive(Parser)]
+#[command(
+    name = "audible-util",
+    about,
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    group(ArgGroup::new("splitting").args(["split", "split_at_silence"])),
+    group(ArgGroup::new("chapter_range").multiple(true).requires("splitting")),
+    group(ArgGroup::new("key_source").requires("iv_source").conflicts_with("voucher_path")),
+    group(ArgGroup::new("iv_source").requires("key_source")),
+)]
+pub struct Cli {
+    /// Subcommand to run instead of converting the input file
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path to the input .aaxc file to convert.
+    ///
+    /// Legacy .aax files are accepted too and decrypted with --activation-bytes.
+    /// Use `-` to read the book from stdin; this skips ffprobe and requires --duration and
+    /// either --voucher-path or --key and --iv.
+    /// Example: -a mybook.aaxc
+    #[clap(short = 'a', long = "aaxc_path", value_name = "AAXC_FILE", required_unless_present = "zip_path", help = "Input .aaxc file")]
+    pub aaxc_path: Option<PathBuf>,
+
+    /// Read the book from a ZIP archive instead of --aaxc_path.
+    ///
+    /// The archive must contain exactly one .aaxc and one .voucher file, and may contain one
+    /// -chapters.json file. They are extracted to a temporary directory that is removed after
+    /// the conversion. Other files in the archive are ignored.
+    /// Example: --zip-path mybook.zip
+    #[clap(long, value_name = "ZIP_FILE", conflicts_with = "aaxc_path", help = "Read the .aaxc, voucher and chapters from a ZIP file")]
+    pub zip_path: Option<PathBuf>,
+
+    /// Extract --zip-path into DIR instead of a temporary directory.
+    ///
+    /// The extracted files are kept, which helps with very large archives that shouldn't be
+    /// extracted to the system's temporary directory on every run.
+    #[clap(long, value_name = "DIR", requires = "zip_path", help = "Extract the ZIP file into DIR and keep the files")]
+    pub zip_extract_dir: Option<PathBuf>,
+
+    /// Path to the voucher file required for decryption.
+    ///
+    /// The voucher file is needed to decrypt the .aaxc file. You can obtain it using the Audible app or other tools.
+    /// Example: --voucher_path my.voucher
+    /// TIP: The voucher file must match the account used to download the .aaxc file.
+    #[clap(short = 'v', long, value_name = "VOUCHER_FILE", help = "Voucher file for decryption")]
+    pub voucher_path: Option<PathBuf>,
+
+    /// Decryption key as hex, instead of reading it from the voucher file.
+    #[clap(long, value_name = "HEX", group = "key_source", help = "Decryption key (hex), instead of a voucher")]
+    pub key: Option<String>,
+
+    /// Run COMMAND with `sh -c` and use its output as the decryption key.
+    ///
+    /// Keeps the key out of the shell history and config files by reading it from a password
+    /// manager or keychain. The command has to exit successfully and print the key as hex;
+    /// surrounding whitespace is ignored.
+    /// Example: --key-from-cmd "pass Audible/key"
+    #[clap(long, value_name = "COMMAND", group = "key_source", help = "Read the decryption key from the output of a command")]
+    pub key_from_cmd: Option<String>,
+
+    /// Decryption IV as hex, instead of reading it from the voucher file.
+    #[clap(long, value_name = "HEX", group = "iv_source", help = "Decryption IV (hex), instead of a voucher")]
+    pub iv: Option<String>,
+
+    /// Run COMMAND with `sh -c` and use its output as the decryption IV, like --key-from-cmd.
+    /// Example: --iv-from-cmd "secret-tool lookup service audible iv iv"
+    #[clap(long, value_name = "COMMAND", group = "iv_source", help = "Read the decryption IV from the output of a command")]
+    pub iv_from_cmd: Option<String>,
+
+    /// Activation bytes for legacy .aax files, as 8 hex digits.
+    ///
+    /// .aax files are decrypted with the account's activation bytes instead of a voucher,
+    /// so no voucher is loaded. When reading from stdin without a voucher or key, the input
+    /// is treated as .aax.
+    #[clap(long, value_name = "HEX", env = "AUDIBLE_ACTIVATION_BYTES", hide_env_values = true, value_parser = parse_activation_bytes, help = "Activation bytes (hex) for .aax files")]
+    pub activation_bytes: Option<String>,
+
+    /// Duration of the book in seconds, required when reading from stdin.
+    ///
+    /// ffprobe can't read the duration from stdin, and it's needed for progress reporting.
+    #[clap(long, value_name = "SECONDS", required_if_eq("aaxc_path", "-"), help = "Book duration in seconds (required with --aaxc_path -)")]
+    pub duration: Option<f64>,
+
+        /// Path to the output audio file or directory.
+        ///
+        /// If a file path is provided, it will be used as the output file.
+        /// If a directory is provided, the output file will be created inside that directory using the default naming scheme (e.g., <album>.<ext>).
+        /// If not specified, the output file will be created in the current directory with the same base name as the input.
+        /// Use `-` to write the audio to stdout; all other output then goes to stderr.
+        /// Example: --output_path output.mp3 or --output_path /path/to/output_dir
+        #[clap(
+            short,
+            long,
+            value_name = "OUTPUT_PATH",
+            help = "Output file or directory (default: current dir)"
+        )]
+        pub output_path: Option<PathBuf>,
+
+    /// Per-book output directory, expanded from the book metadata.
+    ///
+    /// The directory is created under --output-path (or the current directory) before conversion.
+    /// Placeholders: {title}, {artist}, {album_artist}, {album}, {genre}, {date}, {year}, {asin}, {narrator}.
+    /// Values overridden with --metadata-map are used in place of the file's tags.
+    /// Ignored when --output-path names an output file rather than a directory.
+    /// Example: --output-dir-template "{artist}/{album}"
+    #[clap(long, value_name = "TEMPLATE", help = "Per-book output directory, e.g. \"{artist}/{album}\"")]
+    pub output_dir_template: Option<String>,
+
+    /// Write the output into a per-book directory named after the ASIN.
+    ///
+    /// The output goes to <output_path>/<asin>/. With --split the ASIN comes from the
+    /// chapters JSON, otherwise from the voucher (or the file tags when no voucher is used).
+    /// Applied after --output-dir-template, and ignored when --output-path names an output file.
+    #[clap(long, help = "Write output into an <output_path>/<asin>/ directory")]
+    pub asin_dirs: bool,
+
+    /// Put the chapter files in a subdirectory named after the album.
+    ///
+    /// Chapters are written to <output_path>/<album_name>/, where album_name is the album tag
+    /// in snake_case. With --split-structure hierarchical the album directory is the root of the
+    /// hierarchy. A --playlist given as a bare file name is written into the album directory too.
+    #[clap(long, requires = "splitting", help = "Put chapter files in an <output_path>/<album_name>/ directory")]
+    pub album_dir: bool,
+
+    /// Split the output audio file by chapters.
+    ///
+    /// If set, the output will be split into separate files for each chapter (if chapter information is available).
+    /// Requires a chapters.json file in the same directory as the .aaxc file.
+    #[clap(short, long, help = "Split output by chapters")]
+    pub split: bool,
+
+    /// Split the output at silences instead of by chapters.
+    ///
+    /// Runs ffmpeg's silencedetect over the whole book and splits at the middle of every silence
+    /// of 2 seconds or more, for books without chapter data. The parts are titled "Part N".
+    /// Takes the place of --split and accepts the same options.
+    #[clap(long, help = "Split output at silences instead of by chapters")]
+    pub split_at_silence: bool,
+
+    /// Title --split-at-silence parts by their start time (HH:MM:SS) instead of "Part N".
+    #[clap(long, requires = "split_at_silence", help = "Title silence-split parts by their start time")]
+    pub title_from_position: bool,
+
+    /// Embed chapter markers in a single m4a file.
+    ///
+    /// Reads the chapters file the same way --split does and writes its chapters into the output,
+    /// for chapter navigation in Books.app and most podcast players. Requires `--output-type m4a`.
+    #[clap(long, conflicts_with = "splitting", help = "Embed chapter markers in a single m4a file")]
+    pub embed_chapters: bool,
+
+    /// Only convert when the output is missing or older than the .aaxc file.
+    ///
+    /// Compares modification times of the .aaxc file and the output file, or the first chapter
+    /// file when splitting, and skips the conversion when the output is newer.
+    #[clap(long, visible_alias = "if-newer", help = "Skip conversion when the output is newer than the input")]
+    pub conditional: bool,
+
+    /// Fall back to chapter markers embedded in the .aaxc file.
+    ///
+    /// When splitting and no chapters.json file is found, the chapter markers are read
+    /// with `ffprobe -show_chapters` instead of failing. Embedded markers have no hierarchy.
+    #[clap(long, help = "Use embedded chapter markers when no chapters.json is found")]
+    pub chapters_from_ffprobe: bool,
+
+    /// Also look for the chapters file in up to N parent directories of the input file.
+    ///
+    /// For libraries where the chapters files are kept at the root, e.g.
+    /// `~/audiobooks/book-chapters.json` for `~/audiobooks/author/book.aaxc`. The same file name
+    /// is looked for in each directory and the closest one is used. Stops at the filesystem root.
+    /// Example: --chapter-search-parents 2
+    #[clap(long, value_name = "N", default_value = "0", help = "Also look for the chapters file in up to N parent directories")]
+    pub chapter_search_parents: usize,
+
+    /// Read the chapters from this file or http(s) URL instead of looking next to the input.
+    ///
+    /// A URL is downloaded to a temporary file, which is removed after the conversion unless
+    /// --keep-temp-files is set. Takes precedence over a chapters file in --zip-path.
+    /// Example: --chapter-path https://books.example.com/mybook-chapters.json
+    #[clap(long, value_name = "PATH_OR_URL", help = "Chapters file or http(s) URL to read the chapters from")]
+    pub chapter_path: Option<String>,
+
+    /// HTTP header sent when downloading --chapter-path, repeatable.
+    /// Example: --chapter-path-header "Authorization: Bearer TOKEN"
+    #[clap(long, value_name = "KEY:VALUE", requires = "chapter_path", value_parser = parse_http_header, help = "HTTP header for downloading --chapter-path")]
+    pub chapter_path_header: Vec<(String, String)>,
+
+    /// Timeout in seconds for downloading --chapter-path.
+    #[clap(long, value_name = "SECONDS", default_value = "30", requires = "chapter_path", help = "Timeout in seconds for downloading --chapter-path")]
+    pub chapter_download_timeout: u64,
+
+    /// Compute chapter output paths without extracting any audio.
+    ///
+    /// Runs the chapter filtering, merging, and naming logic of a normal split run
+    /// but skips all ffmpeg invocations, printing the output path of each chapter instead.
+    /// Useful to check where files would go, or to re-check a previous run with --verify.
+    #[clap(long, requires = "splitting", help = "Compute chapter outputs without running ffmpeg")]
+    pub metadata_only: bool,
+
+    /// Verify already-converted chapter files.
+    ///
+    /// With --metadata-only, each computed output file is probed with ffprobe and its
+    /// duration compared to the chapter data. Missing or mismatched files are reported.
+    #[clap(long, requires = "metadata_only", help = "Verify existing chapter files against chapter data")]
+    pub verify: bool,
+
+    /// Show each chapter's start and end time in the --metadata-only listing.
+    ///
+    /// Lines read like `[00:15:30 - 00:23:45] (08:15) chapters/Chapter05_Title.mp3`, the same
+    /// timestamps a CUE sheet would list. Machine-readable output is unchanged.
+    #[clap(long, requires = "metadata_only", help = "Show chapter start and end times in the --metadata-only listing")]
+    pub timestamps: bool,
+
+    /// Reject chapter files containing fields this tool doesn't know about.
+    ///
+    /// Unknown fields in chapters.json are ignored by default. With this flag, parsing fails
+    /// and names the unexpected field, which helps spot audible-cli schema changes.
+    #[clap(long, help = "Fail on unknown fields in the chapter file")]
+    pub strict_json: bool,
+
+    /// Character encoding of the chapter file.
+    ///
+    /// Chapter files are expected to be UTF-8, but files passed through older Windows tools
+    /// may be UTF-16 or Latin-1. `auto` detects UTF-8/UTF-16 from the byte order mark and
+    /// falls back to UTF-8.
+    /// Example: --chapter-encoding utf-16-le
+    #[clap(long, value_enum, value_name = "ENCODING", default_value = "utf-8", help = "Chapter file encoding")]
+    pub chapter_encoding: ChapterEncoding,
+
+    /// Case transformation applied to chapter titles.
+    ///
+    /// Chapter titles come in inconsistent case, ALL CAPS in some books and lowercase in others.
+    /// - preserve: Keep titles as they are (default)
+    /// - title: Title Case, keeping small words like "of" and "the" lowercase
+    /// - upper / lower: All uppercase / all lowercase
+    /// - sentence: First letter uppercase, the rest lowercase
+    #[clap(long, value_enum, value_name = "MODE", default_value = "preserve", help = "Chapter title case transformation")]
+    pub title_case: TitleCase,
+
+    /// Remove the part of each chapter title matched by REGEX.
+    ///
+    /// For titles repeating the numbering that's already in the file name. Applied before
+    /// --title-case, so it affects file names, tags, logs, and progress output. A title that
+    /// would be left empty is kept as it is, with a warning.
+    /// Example: --strip-title-prefix "^Chapter \d+:?\s*" turns "Chapter 1: Introduction" into "Introduction"
+    #[clap(long, value_name = "REGEX", value_parser = parse_regex, help = "Remove the part of each chapter title matching REGEX")]
+    pub strip_title_prefix: Option<regex::Regex>,
+
+    /// Apply --title-case to output file names only.
+    ///
+    /// Chapter titles in logs, progress output, and tags keep their original case.
+    #[clap(long, requires = "title_case", help = "Apply --title-case to file names only")]
+    pub title_case_output_only: bool,
+
+    /// Tag each chapter file with a disc number.
+    ///
+    /// For books divided into parts, each top-level part becomes a disc, so players that
+    /// support disc numbers group chapters by part. The tag is written as `disc=N/TOTAL`
+    /// (the TPOS frame in MP3 files). Books without parts are tagged as disc 1/1.
+    #[clap(long, requires = "splitting", help = "Tag chapter files with their part as disc number")]
+    pub disc_tags: bool,
+
+    /// Extend each chapter to the start of the next one.
+    ///
+    /// Chapter data can leave small gaps between chapters, which would be missing from the split files.
+    /// With this flag each chapter is extended to the start of the next, and the last chapter to the
+    /// end of the audio (before the brand outro). Chapters are never shortened.
+    #[clap(long, requires = "splitting", help = "Extend chapters to close gaps between them")]
+    pub chapter_gap_fill: bool,
+
+    /// Move every chapter by MS milliseconds, positive for later, negative for earlier.
+    ///
+    /// For chapter data made from a different encode than the .aaxc file, where every chapter
+    /// is off by the same amount. Applied right after the chapters are read, before merging.
+    /// A chapter moved before the start of the audio starts at 0 instead and keeps its end.
+    /// Example: --chapter-time-offset 3000 or --chapter-time-offset -1500
+    #[clap(long, value_name = "MS", default_value = "0", allow_negative_numbers = true, help = "Move every chapter by MS milliseconds")]
+    pub chapter_time_offset: i64,
+
+    /// What to do when a chapter overlaps the next one.
+    ///
+    /// Some chapter data has chapters that start before the previous one ends, so the
+    /// overlapping audio would end up in both files. Checked after merging short chapters.
+    ///
+    /// - warn: Log the overlap and convert the chapters as they are
+    /// - truncate: Shorten the chapter to end where the next one starts
+    /// - error: Abort before converting anything
+    #[clap(long, value_enum, value_name = "STRATEGY", default_value = "warn", requires = "splitting", help = "How to handle overlapping chapters")]
+    pub overlap_strategy: OverlapStrategy,
+
+    /// First chapter number to convert.
+    ///
+    /// Chapters numbered below N are skipped. Numbers are the ones in the chapter file names,
+    /// assigned before filtering and merging. Combine with --max-chapter for a range.
+    /// Example: --min-chapter 50 --max-chapter 100
+    #[clap(long, value_name = "N", group = "chapter_range", value_parser = clap::value_parser!(u64).range(1..), help = "Skip chapters numbered below N")]
+    pub min_chapter: Option<u64>,
+
+    /// Last chapter number to convert.
+    ///
+    /// Chapters numbered above N are skipped. See --min-chapter.
+    #[clap(long, value_name = "N", group = "chapter_range", value_parser = clap::value_parser!(u64).range(1..), help = "Skip chapters numbered above N")]
+    pub max_chapter: Option<u64>,
+
+    /// Convert only the chapters listed in a text file.
+    ///
+    /// Each line is a chapter number (7) or an inclusive range (10-15). Empty lines and lines
+    /// starting with # are ignored. Combined with --min-chapter/--max-chapter, only chapters
+    /// selected by both are converted.
+    /// Example: --chapter-list-file reviewed.txt
+    #[clap(long, value_name = "PATH", group = "chapter_range", help = "Convert only the chapter numbers and ranges listed in a file")]
+    pub chapter_list_file: Option<PathBuf>,
+
+    /// Convert a single chapter as a test run.
+    ///
+    /// Only chapter N (1-based, counted after filtering and merging) is converted, e.g. to
+    /// check output settings quickly. A banner marks the run as a test with incomplete output.
+    /// Example: --test-chapter 3
+    #[clap(long, value_name = "N", requires = "splitting", value_parser = clap::value_parser!(u64).range(1..), help = "Convert only chapter N as a test run")]
+    pub test_chapter: Option<u64>,
+
+    /// Minimum chapter duration in seconds.
+    ///
+    /// Chapters shorter than this duration will be skipped when splitting.
+    /// Fractional values are accepted, e.g. 0.5 skips chapters shorter than 500 ms.
+    /// Default: 0 (no minimum duration).
+    #[clap(short = 'd', long, value_name = "SECONDS", help = "Minimum chapter duration in seconds (fractions allowed)")]
+    pub min_chapter_duration: Option<f64>,
+
+    /// Chapter naming format.
+    ///
+    /// Controls how chapter files are named when splitting.
+    /// Available formats: chapter-number-title, number-title, track-number-title, title-only, custom
+    #[clap(short = 'f', long, value_enum, value_name = "FORMAT", default_value = "chapter-number-title", help = "Chapter naming format")]
+    pub chapter_naming_format: ChapterNamingFormat,
+
+    /// Prefix used instead of "Chapter" by the chapter-number-title naming format.
+    ///
+    /// Example: --chapter-prefix Kapitel gives Kapitel01_Title.mp3
+    #[clap(long, value_name = "STRING", requires = "splitting", help = "Prefix for chapter-number-title file names (default: Chapter)")]
+    pub chapter_prefix: Option<String>,
+
+    /// Restart chapter numbering at 1 in each part of a multi-part book.
+    ///
+    /// For books split into parts (top-level chapters with children), e.g. "Part 1" with
+    /// chapters 1-15 and "Part 2" with chapters 1-12. Books without parts are unaffected.
+    /// Combine with --split-structure hierarchical to keep same-numbered chapters apart.
+    #[clap(long, requires = "splitting", help = "Restart chapter numbering in each part of the book")]
+    pub reset_chapter_numbers_per_part: bool,
+
+    /// Append the start time to chapters whose titles aren't unique.
+    ///
+    /// Chapters sharing a title (e.g. several "Interlude" chapters) would otherwise get the same
+    /// file name in title-only mode. Their file names get the start time as HH-MM-SS appended,
+    /// e.g. Interlude_00-45-30.mp3 and Interlude_02-13-07.mp3.
+    /// Shorthand for --title-disambiguation time.
+    #[clap(long, requires = "splitting", conflicts_with = "title_disambiguation", help = "Append the start time to file names of chapters with duplicate titles")]
+    pub disambiguate_duplicates: bool,
+
+    /// How to tell apart chapters whose titles aren't unique.
+    ///
+    /// - none: Leave the file names alone, the conversion stops if two chapters get the same name
+    /// - number: Append the chapter number, e.g. Interlude_03.mp3 and Interlude_17.mp3
+    /// - time: Append the start time as HH-MM-SS, e.g. Interlude_00-45-30.mp3
+    #[clap(long, value_enum, value_name = "STRATEGY", default_value = "none", requires = "splitting", help = "Suffix for file names of chapters with duplicate titles")]
+    pub title_disambiguation: TitleDisambiguation,
+
+    /// Convert even if several chapters would be written to the same file.
+    ///
+    /// Before splitting, the output paths of all chapters are compared and the conversion stops
+    /// when two chapters would overwrite each other, e.g. with a {title}-only naming pattern and
+    /// duplicate titles. With this flag the conflicts are only logged as a warning.
+    #[clap(long, requires = "splitting", help = "Only warn when chapters would be written to the same file")]
+    pub allow_duplicate_filenames: bool,
+
+    /// Output structure for split chapters.
+    ///
+    /// Controls how chapter files are organized when splitting.
+    /// - flat: All chapters in a single directory
+    /// - hierarchical: Create folders based on chapter hierarchy
+    #[clap(short = 't', long, value_enum, value_name = "STRUCTURE", default_value = "flat", help = "Output structure for split chapters")]
+    pub split_structure: SplitStructure,
+
+    /// Merge short chapters with the next chapter instead of filtering them out.
+    ///
+    /// When enabled, chapters shorter than --min-chapter-duration will be merged
+    /// with the next chapter instead of being filtered out. This prevents gaps
+    /// in the audio timeline while still allowing filtering of very short content.
+    #[clap(short = 'm', long, help = "Merge short chapters with next chapter instead of filtering them out")]
+    pub merge_short_chapters: bool,
+
+    /// Merge chapters whose boundary falls in the middle of speech.
+    ///
+    /// ffmpeg's silencedetect filter is run on a few seconds of audio around each chapter
+    /// boundary. A boundary without silence there is dropped and the chapters on both sides
+    /// are merged. Takes the place of --min-chapter-duration filtering.
+    #[clap(long, requires = "splitting", conflicts_with = "merge_short_chapters", help = "Merge chapters whose boundary has no silence")]
+    pub merge_on_silence: bool,
+
+    /// Where a run of consecutive short chapters is merged to.
+    ///
+    /// - next: Into the first long chapter after the run
+    /// - previous: Into the last long chapter before the run
+    ///
+    /// Falls back to the other direction when there is no chapter on that side.
+    #[clap(long, value_enum, value_name = "DIRECTION", default_value = "next", requires = "merge_short_chapters", help = "Merge short chapters into the next or previous chapter")]
+    pub merge_direction: MergeDirection,
+
+    /// Output file type/format.
+    ///
+    /// Supported values: mp3, wav, flac, ogg, m4a, aiff
+    /// Example: --output_type mp3
+    #[clap(short = 'T', long, value_enum, value_name = "TYPE", default_value = "mp3", help = "Output format")]
+    pub output_type: OutputType,
+
+    /// Encode MP3 with variable bitrate at this LAME quality (0 = best, 9 = smallest).
+    ///
+    /// Passed to ffmpeg as -q:a instead of the fixed 128 kbps -b:a. Requires `--output-type mp3`.
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u8).range(0..=9), help = "Encode MP3 with variable bitrate at quality N (0-9)")]
+    pub mp3_quality: Option<u8>,
+
+    /// Refuse to convert to an output type, repeatable.
+    ///
+    /// A safety guard for shared setups, e.g. to never produce huge WAV files by accident.
+    /// The run fails before any file is touched when --output-type is excluded.
+    /// Example: --exclude-output-type wav --exclude-output-type aiff
+    #[clap(long, value_enum, value_name = "TYPE", help = "Fail if --output-type is this type")]
+    pub exclude_output_type: Vec<OutputType>,
+
+    /// Enable verbose progress reporting.
+    ///
+    /// When enabled, shows detailed progress information including bitrate, file size, and conversion speed.
+    /// This provides more detailed feedback during long conversions.
+    #[clap(short = 'P', long, help = "Enable verbose progress reporting")]
+    pub verbose_progress: bool,
+
+    /// Enable machine-readable output mode.
+    ///
+    /// When enabled, outputs structured JSON progress information to stdout, making it easy to parse
+    /// from other programs. Progress bars and human-readable output are suppressed in this mode.
+    /// Perfect for integration with Python, shell scripts, or other automation tools.
+    #[clap(short = 'M', long, help = "Enable machine-readable JSON output mode")]
+    pub machine_readable: bool,
+
+    /// Don't draw progress bars.
+    ///
+    /// Progress bars are redrawn with terminal escape sequences, which garble logs when the output
+    /// is redirected. This turns them off while keeping the normal log messages, unlike
+    /// --machine-readable which replaces the output entirely. Progress bars are also turned off
+    /// automatically when stderr is not a terminal.
+    #[clap(long, conflicts_with = "force_progress", help = "Don't draw progress bars")]
+    pub no_progress: bool,
+
+    /// Draw progress bars even when stderr is not a terminal.
+    #[clap(long, help = "Draw progress bars even when stderr is not a terminal")]
+    pub force_progress: bool,
+
+    /// Don't copy the input file's metadata into the output.
+    ///
+    /// By default ffmpeg is run with `-map_metadata 0`. With this flag the input's tags are dropped
+    /// instead, so only the tags this tool writes itself (e.g. --disc-tags) end up in the output.
+    /// Not suitable for workflows that rely on the book's tags being present in the output files.
+    #[clap(long, help = "Don't copy the input file's metadata into the output")]
+    pub no_ffmpeg_metadata: bool,
+
+    /// Override a metadata field of the output, repeatable.
+    ///
+    /// FIELD is one of: title, artist, album, genre, date, comment, copyright, narrator.
+    /// Overrides are written as tags into every output file and used for --output-dir-template
+    /// placeholders, the default output file name, --album-dir and playlists.
+    /// Example: --metadata-map genre=Fantasy --metadata-map "artist=Jane Doe"
+    #[clap(long, value_name = "FIELD=VALUE", value_parser = parse_metadata_mapping, help = "Override an output metadata field, e.g. genre=Fantasy")]
+    pub metadata_map: Vec<(String, String)>,
+
+    /// Extra ffmpeg audio filter chain, passed to ffmpeg with -af.
+    ///
+    /// Appended to the filters the tool builds from other options, separated by a comma.
+    /// Example: --audio-filter "loudnorm,atempo=1.25"
+    #[clap(long, value_name = "FILTER_STRING", help = "Extra ffmpeg audio filter chain (-af)")]
+    pub audio_filter: Option<String>,
+
+    /// Keep ffmpeg's temporary output file when a conversion fails.
+    ///
+    /// ffmpeg writes to a `.tmp` file next to the output (e.g. Chapter01_Intro.tmp.mp3), which is
+    /// moved into place once the conversion succeeds and removed when it fails. With this flag a
+    /// failed conversion leaves the file behind and logs its path for inspection.
+    #[clap(long, help = "Keep the temporary output file when a conversion fails")]
+    pub keep_temp_files: bool,
+
+    /// Write an M3U playlist of the chapter files.
+    ///
+    /// Paths in the playlist are relative to the playlist's directory. With --album-dir, a bare
+    /// file name is placed in the album directory.
+    /// Example: --playlist book.m3u8
+    #[clap(long, value_name = "PATH", requires = "splitting", help = "Write an M3U playlist of the chapter files")]
+    pub playlist: Option<PathBuf>,
+
+    /// Write an extended M3U playlist with duration, artist, album, genre, and chapter number.
+    ///
+    /// Defaults to true for .m3u8 playlists and false otherwise.
+    #[clap(long, value_name = "BOOL", num_args = 0..=1, default_missing_value = "true", requires = "playlist", help = "Write an extended M3U playlist (default: true for .m3u8)")]
+    pub m3u_extended: Option<bool>,
+
+    /// Retry a failed ffmpeg invocation up to N times before giving up on a chapter.
+    ///
+    /// Helps with transient failures such as filesystem hiccups. Retries back off exponentially,
+    /// starting at 1 second, and the partial output file is removed before each retry.
+    /// Example: --retry 3
+    #[clap(long, value_name = "N", default_value = "0", value_parser = clap::value_parser!(u32).range(0..=5), requires = "splitting", help = "Retry failed chapter conversions up to N times (max 5)")]
+    pub retry: u32,
+
+    /// Ask for confirmation before a split writes more than N chapter files.
+    ///
+    /// The prompt shows the number of files and the estimated disk usage. It is skipped with --yes,
+    /// in --machine-readable mode and when the terminal isn't interactive.
+    #[clap(long, value_name = "N", default_value = "100", requires = "splitting", help = "Ask before writing more than N chapter files")]
+    pub warn_chapter_count: usize,
+
+    /// Don't ask for confirmation before large splits (see --warn-chapter-count).
+    #[clap(short = 'y', long, help = "Don't ask for confirmation before large splits")]
+    pub yes: bool,
+
+    /// Give up on probing the input file after SECONDS.
+    ///
+    /// ffprobe can hang on corrupted or very large input files. When it runs longer than this,
+    /// the process is killed and the conversion fails with a timeout error.
+    /// Example: --ffprobe-timeout 300
+    #[clap(long, value_name = "SECONDS", default_value = "60", value_parser = clap::value_parser!(u64).range(1..), help = "Maximum time ffprobe may spend probing the input file")]
+    pub ffprobe_timeout: u64,
+
+    /// Convert at most N times faster than realtime.
+    ///
+    /// Keeps a background conversion from saturating disk I/O. While ffmpeg is ahead of the limit,
+    /// its progress output isn't read, which makes it wait, so short bursts above the limit are
+    /// possible. The trailing `x` is optional.
+    /// Example: --max-speed 1.5x
+    #[clap(long, value_name = "Nx", value_parser = parse_max_speed, help = "Limit conversion to N times realtime, e.g. 1.5x")]
+    pub max_speed: Option<f64>,
+
+    /// Measure the conversion speed without writing any output.
+    ///
+    /// ffmpeg decrypts and encodes as usual but writes to the null device, so the speed reflects
+    /// CPU and decryption overhead only. Chapters are still converted one by one with --split.
+    /// A summary with the audio length, average and peak speed and wall time is printed at the
+    /// end, as a `benchmark_result` event with --machine-readable.
+    #[clap(long, conflicts_with = "playlist", help = "Measure conversion speed without writing output")]
+    pub benchmark: bool,
+
+    /// Report paths in machine-readable events relative to the output directory.
+    ///
+    /// `output_path` in conversion_started and `output_file` in chapter_completed are made
+    /// relative to --output-path (the current directory when not set), so events stay valid
+    /// when the output is accessed from another machine or mount point.
+    #[clap(long, help = "Use paths relative to the output directory in machine-readable events")]
+    pub relative_paths: bool,
+
+    /// Emit a chapter_progress event only every N ffmpeg progress lines.
+    ///
+    /// In machine-readable mode one event is emitted per ffmpeg progress line by default,
+    /// which can flood slower consumers such as mobile apps or web UIs. With a batch size,
+    /// the latest progress values are emitted every N lines, and always when a chapter starts or completes.
+    /// Example: --progress-batch 10
+    #[clap(long, value_name = "N", default_value = "1", value_parser = clap::value_parser!(u64).range(1..), help = "Emit machine-readable progress every N lines")]
+    pub progress_batch: u64,
+
+    /// Number of threads for FFmpeg processing.
+    ///
+    /// Controls how many CPU cores FFmpeg will use for encoding/decoding.
+    /// - auto: The number of available cores, at most 4 since audio encoders rarely get faster
+    ///   with more (default, recommended for most users)
+    /// - 0: Let FFmpeg pick the thread count itself
+    /// - N: Use exactly N threads (e.g., 4 for 4 cores)
+    ///
+    /// Example: --threads 4 or --threads auto
+    #[clap(long, value_name = "THREADS", default_value = "auto", help = "Number of threads for FFmpeg processing (auto, 0 for FFmpeg's choice, or N)")]
+    pub threads: String,
+
+    /// Append log output to a file as well as stderr.
+    ///
+    /// The file receives the same events as stderr, at the level set with RUST_LOG.
+    /// It is opened in append mode, so multiple runs accumulate.
+    /// Example: --log-file audible-util.log
+    #[clap(long, value_name = "PATH", help = "Also write log output to a file")]
+    pub log_file: Option<PathBuf>,
+
+    /// Format of the --log-file output.
+    ///
+    /// - text: Human readable lines, like stderr
+    /// - json: One JSON object per line with level, timestamp, message, and module
+    #[clap(long, value_enum, value_name = "FORMAT", default_value = "text", requires = "log_file", help = "Log file format")]
+    pub log_format: LogFileFormat,
+
+    /// Log level passed to ffmpeg via -loglevel.
+    ///
+    /// ffmpeg's own log output is hidden by default. At `info` or more verbose levels,
+    /// ffmpeg's stderr is forwarded to this tool's logger (see RUST_LOG) at the matching level.
+    /// Example: --ffmpeg-log-level info
+    #[clap(long, value_enum, value_name = "LEVEL", default_value = "fatal", help = "ffmpeg log level")]
+    pub ffmpeg_log_level: FfmpegLogLevel,
+}
+
+/// Subcommands besides converting a book
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print a commented config file with every option and its default.
+    ///
+    /// Options with a default are written as TOML values, the others are commented out.
+    GenerateConfig {
+        /// Write the config to this file instead of stdout
+        #[clap(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+    /// Write a chapters.json in audible-cli format from the chapter markers embedded in a book.
+    ///
+    /// Useful when the chapters.json that came with an .aaxc file is lost. Embedded markers are
+    /// flat, so the hierarchy of parts and chapters can't be recovered.
+    ExportChapters {
+        /// Input .aaxc file
+        #[clap(value_name = "AAXC_FILE")]
+        aaxc_path: PathBuf,
+        /// Voucher to take the content reference (ASIN, SKU, ...) from, inferred if not provided
+        #[clap(short = 'v', long, value_name = "VOUCHER_FILE")]
+        voucher_path: Option<PathBuf>,
+        /// Output file (default: <stem>-chapters.json next to the input)
+        #[clap(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+        /// Overwrite an existing chapters file
+        #[clap(long)]
+        force: bool,
+    },
+    /// Check a chapters.json file without converting anything.
+    ///
+    /// Reports schema problems, invalid chapter data, gaps and overlaps between chapters, and
+    /// chapters that don't fit the book's runtime. No .aaxc file or voucher is needed. Exits
+    /// with 0 when the file is valid (warnings allowed), 1 when it has errors and 2 when it
+    /// can't be read or parsed.
+    /// Example: audible-util validate-chapters book-chapters.json && echo OK
+    ValidateChapters {
+        /// Chapters file to check
+        #[clap(value_name = "CHAPTERS_FILE")]
+        chapters_path: PathBuf,
+        /// Character encoding of the chapters file
+        #[clap(long, value_enum, value_name = "ENCODING", default_value = "utf-8")]
+        chapter_encoding: ChapterEncoding,
+        /// Print the report as a JSON validation_report event
+        #[clap(short = 'M', long)]
+        machine_readable: bool,
+    },
+    /// Print the JSON Schema of an input file format.
+    ///
+    /// For tools producing chapters or voucher files to validate their output against.
+    GenerateSchema {
+        /// Format to describe: chapters or voucher
+        #[clap(value_enum, value_name = "SCHEMA_NAME")]
+        name: SchemaName,
+        /// Write the schema to this file instead of stdout
+        #[clap(long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Render every conversion option as a commented TOML config, generated from the CLI definition
+pub fn default_config_toml() -> String {
+    let command = Cli::command();
+    let mut config = String::from("# audible-util configuration\n# Generated from the command line options, keys match the option names.\n");
+
+    for arg in command.get_arguments() {
+        let key = arg.get_id().as_str();
+        if key == "help" || key == "version" {
+            continue;
+        }
+
+        config.push('\n');
+        if let Some(help) = arg.get_help() {
+            config.push_str(&format!("# {}\n", help));
+        }
+        let possible_values: Vec<_> = arg
+            .get_possible_values()
+            .iter()
+            .filter(|value| !value.is_hide_set())
+            .map(|value| value.get_name().to_string())
+            .collect();
+        let is_flag = matches!(arg.get_action(), ArgAction::SetTrue);
+        if !possible_values.is_empty() && !is_flag {
+            config.push_str(&format!("# Supported: {}\n", possible_values.join(", ")));
+        }
+
+        let value_type = arg.get_value_parser().type_id();
+        let is_number = [TypeId::of::<u32>(), TypeId::of::<u64>(), TypeId::of::<usize>(), TypeId::of::<f64>()]
+            .iter()
+            .any(|type_id| value_type == *type_id);
+        let default = arg.get_default_values().first().map(|value| value.to_string_lossy().to_string());
+        match (is_flag, default) {
+            (true, _) => config.push_str(&format!("{} = false\n", key)),
+            (false, Some(default)) if is_number => config.push_str(&format!("{} = {}\n", key, default)),
+            (false, Some(default)) => config.push_str(&format!("{} = {}\n", key, toml_string(&default))),
+            (false, None) => {
+                let value_name = arg.get_value_names().and_then(|names| names.first()).map_or("VALUE", |name| name.as_str());
+                config.push_str(&format!("# {} = <{}>  (no default)\n", key, value_name))
+            }
+        }
+    }
+    config
+}
+
+/// Parse a `FIELD=VALUE` --metadata-map entry
+fn parse_metadata_mapping(value: &str) -> Result<(String, String), String> {
+    let (field, value) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected FIELD=VALUE, got '{}'", value))?;
+    let field = field.trim().to_ascii_lowercase();
+    if !METADATA_MAP_FIELDS.contains(&field.as_str()) {
+        return Err(format!("unknown field '{}', expected one of: {}", field, METADATA_MAP_FIELDS.join(", ")));
+    }
+    Ok((field, value.to_string()))
+}
+
+/// Compile a --strip-title-prefix pattern, once for all chapters
+fn parse_regex(value: &str) -> Result<regex::Regex, String> {
+    regex::Regex::new(value).map_err(|e| e.to_string())
+}
+
+/// Parse a `KEY:VALUE` --chapter-path-header entry, whitespace around both is ignored
+fn parse_http_header(value: &str) -> Result<(String, String), String> {
+    match value.split_once(':') {
+        Some((name, header_value)) if !name.trim().is_empty() => Ok((name.trim().to_string(), header_value.trim().to_string())),
+        _ => Err(format!("expected KEY:VALUE, got '{}'", value)),
+    }
+}
+
+/// Activation bytes are 4 bytes written as 8 hex digits, e.g. 1a2b3c4d
+fn parse_activation_bytes(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.len() != 8 || !value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("expected 8 hex digits, got '{}'", value));
+    }
+    Ok(value.to_ascii_lowercase())
+}
+
+/// A positive speed factor such as `1.5` or `1.5x`
+fn parse_max_speed(value: &str) -> Result<f64, String> {
+    let number = value.trim();
+    let number = number.strip_suffix(['x', 'X']).unwrap_or(number);
+    match number.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err(format!("expected a positive speed such as 1.5x, got '{}'", value)),
+    }
+}
+
+/// Quote a TOML basic string, JSON string escapes are valid TOML
+fn toml_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| format!("\"{}\"", value))
+}
+
+impl From<Cli> for ConvertOptions {
+    fn from(cli: Cli) -> Self {
+        Self {
+            aaxc_path: cli.aaxc_path,
+            zip_path: cli.zip_path,
+            zip_extract_dir: cli.zip_extract_dir,
+            voucher_path: cli.voucher_path,
+            key: cli.key,
+            key_from_cmd: cli.key_from_cmd,
+            iv: cli.iv,
+            iv_from_cmd: cli.iv_from_cmd,
+            activation_bytes: cli.activation_bytes,
+            duration: cli.duration,
+            output_path: cli.output_path,
+            output_dir_template: cli.output_dir_template,
+            asin_dirs: cli.asin_dirs,
+            album_dir: cli.album_dir,
+            split: cli.split,
+            split_at_silence: cli.split_at_silence,
+            title_from_position: cli.title_from_position,
+            embed_chapters: cli.embed_chapters,
+            conditional: cli.conditional,
+            chapters_from_ffprobe: cli.chapters_from_ffprobe,
+            chapter_search_parents: cli.chapter_search_parents,
+            chapter_path: cli.chapter_path,
+            chapter_path_header: cli.chapter_path_header,
+            chapter_download_timeout: cli.chapter_download_timeout,
+            metadata_only: cli.metadata_only,
+            verify: cli.verify,
+            timestamps: cli.timestamps,
+            strict_json: cli.strict_json,
+            chapter_encoding: cli.chapter_encoding,
+            title_case: cli.title_case,
+            strip_title_prefix: cli.strip_title_prefix,
+            title_case_output_only: cli.title_case_output_only,
+            disc_tags: cli.disc_tags,
+            chapter_gap_fill: cli.chapter_gap_fill,
+            chapter_time_offset: cli.chapter_time_offset,
+            overlap_strategy: cli.overlap_strategy,
+            min_chapter: cli.min_chapter,
+            max_chapter: cli.max_chapter,
+            chapter_list_file: cli.chapter_list_file,
+            test_chapter: cli.test_chapter,
+            min_chapter_duration: cli.min_chapter_duration,
+            chapter_naming_format: cli.chapter_naming_format,
+            chapter_prefix: cli.chapter_prefix,
+            reset_chapter_numbers_per_part: cli.reset_chapter_numbers_per_part,
+            disambiguate_duplicates: cli.disambiguate_duplicates,
+            title_disambiguation: cli.title_disambiguation,
+            allow_duplicate_filenames: cli.allow_duplicate_filenames,
+            split_structure: cli.split_structure,
+            merge_short_chapters: cli.merge_short_chapters,
+            merge_on_silence: cli.merge_on_silence,
+            merge_direction: cli.merge_direction,
+            output_type: cli.output_type,
+            mp3_quality: cli.mp3_quality,
+            exclude_output_type: cli.exclude_output_type,
+            verbose_progress: cli.verbose_progress,
+            machine_readable: cli.machine_readable,
+            no_progress: cli.no_progress,
+            force_progress: cli.force_progress,
+            no_ffmpeg_metadata: cli.no_ffmpeg_metadata,
+            metadata_map: cli.metadata_map,
+            audio_filter: cli.audio_filter,
+            keep_temp_files: cli.keep_temp_files,
+            playlist: cli.playlist,
+            m3u_extended: cli.m3u_extended,
+            retry: cli.retry,
+            warn_chapter_count: cli.warn_chapter_count,
+            yes: cli.yes,
+            ffprobe_timeout: cli.ffprobe_timeout,
+            max_speed: cli.max_speed,
+            benchmark: cli.benchmark,
+            relative_paths: cli.relative_paths,
+            progress_batch: cli.progress_batch,
+            threads: cli.threads,
+            ffmpeg_log_level: cli.ffmpeg_log_level,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_options_default_matches_cli_defaults() {
+        let cli = Cli::parse_from(["audible-util", "--aaxc_path", "book.aaxc"]);
+        let expected = ConvertOptions { aaxc_path: Some(PathBuf::from("book.aaxc")), ..ConvertOptions::default() };
+        assert_eq!(format!("{:?}", ConvertOptions::from(cli)), format!("{:?}", expected));
+    }
+}
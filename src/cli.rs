@@ -1,132 +1,82 @@
-use std::path::PathBuf;
-
-use clap::{Parser, ValueEnum};
-use crate::models::ChapterNamingFormat;
-
-#[derive(Parser)]
-#[command(
-    name = "audible-util",
-    about,
-    version,
-)]
-pub struct Cli {
-    /// Path to the input .aaxc file to convert.
-    ///
-    /// Example: -a mybook.aaxc
-    #[clap(short = 'a', long = "aaxc_path", value_name = "AAXC_FILE", help = "Input .aaxc file")]
-    pub aaxc_path: PathBuf,
-
-    /// Path to the voucher file required for decryption.
-    ///
-    /// The voucher file is needed to decrypt the .aaxc file. You can obtain it using the Audible app or other tools.
-    /// Example: --voucher_path my.voucher
-    /// TIP: The voucher file must match the account used to download the .aaxc file.
-    #[clap(short = 'v', long, value_name = "VOUCHER_FILE", help = "Voucher file for decryption")]
-    pub voucher_path: Option<PathBuf>,
-
-        /// Path to the output audio file or directory.
-        ///
-        /// If a file path is provided, it will be used as the output file.
-        /// If a directory is provided, the output file will be created inside that directory using the default naming scheme (e.g., <album>.<ext>).
-        /// If not specified, the output file will be created in the current directory with the same base name as the input.
-        /// Example: --output_path output.mp3 or --output_path /path/to/output_dir
-        #[clap(
-            short,
-            long,
-            value_name = "OUTPUT_PATH",
-            help = "Output file or directory (default: current dir)"
-        )]
-        pub output_path: Option<PathBuf>,
-
-    /// Split the output audio file by chapters.
-    ///
-    /// If set, the output will be split into separate files for each chapter (if chapter information is available).
-    /// Requires a chapters.json file in the same directory as the .aaxc file.
-    #[clap(short, long, help = "Split output by chapters")]
-    pub split: bool,
-
-    /// Minimum chapter duration in seconds.
-    ///
-    /// Chapters shorter than this duration will be skipped when splitting.
-    /// Default: 0 (no minimum duration).
-    #[clap(short = 'd', long, value_name = "SECONDS", help = "Minimum chapter duration in seconds")]
-    pub min_chapter_duration: Option<u64>,
-
-    /// Chapter naming format.
-    ///
-    /// Controls how chapter files are named when splitting.
-    /// Available formats: chapter-number-title, number-title, title-only, custom
-    #[clap(short = 'f', long, value_enum, value_name = "FORMAT", default_value = "chapter-number-title", help = "Chapter naming format")]
-    pub chapter_naming_format: ChapterNamingFormat,
-
-    /// Output structure for split chapters.
-    ///
-    /// Controls how chapter files are organized when splitting.
-    /// - flat: All chapters in a single directory
-    /// - hierarchical: Create folders based on chapter hierarchy
-    #[clap(short = 't', long, value_enum, value_name = "STRUCTURE", default_value = "flat", help = "Output structure for split chapters")]
-    pub split_structure: SplitStructure,
-
-    /// Merge short chapters with the next chapter instead of filtering them out.
-    ///
-    /// When enabled, chapters shorter than --min-chapter-duration will be merged
-    /// with the next chapter instead of being filtered out. This prevents gaps
-    /// in the audio timeline while still allowing filtering of very short content.
-    #[clap(short = 'm', long, help = "Merge short chapters with next chapter instead of filtering them out")]
-    pub merge_short_chapters: bool,
-
-    /// Output file type/format.
-    ///
-    /// Supported values: mp3, wav, flac, ogg, m4a
-    /// Example: --output_type mp3
-    #[clap(short = 'T', long, value_enum, value_name = "TYPE", default_value = "mp3", help = "Output format")]
-    pub output_type: OutputType,
-
-    /// Enable verbose progress reporting.
-    ///
-    /// When enabled, shows detailed progress information including bitrate, file size, and conversion speed.
-    /// This provides more detailed feedback during long conversions.
-    #[clap(short = 'P', long, help = "Enable verbose progress reporting")]
-    pub verbose_progress: bool,
-
-    /// Enable machine-readable output mode.
-    ///
-    /// When enabled, outputs structured JSON progress information to stdout, making it easy to parse
-    /// from other programs. Progress bars and human-readable output are suppressed in this mode.
-    /// Perfect for integration with Python, shell scripts, or other automation tools.
-    #[clap(short = 'M', long, help = "Enable machine-readable JSON output mode")]
-    pub machine_readable: bool,
-
-    /// Number of threads for FFmpeg processing.
-    ///
-    /// Controls how many CPU cores FFmpeg will use for encoding/decoding.
-    /// - 0 or "auto": Let FFmpeg automatically detect and use all available cores (default)
-    /// - N: Use exactly N threads (e.g., 4 for 4 cores)
-    /// - "auto": Same as 0, auto-detect optimal thread count
-    /// 
-    /// Example: --threads 4 or --threads auto
-    #[clap(long, value_name = "THREADS", default_value = "0", help = "Number of threads for FFmpeg processing (0=auto)")]
-    pub threads: String,
-}
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use crate::models::{ChapterNamingFormat, SchemaName, TitleDisambiguation, DEFAULT_CHAPTER_PREFIX};
 
+/// ffmpeg settings for an output type, see [`OutputType::get_format`]
 pub trait OutputFormat {
+    /// ffmpeg audio encoder, passed as `-codec:a`
     fn codec(&self) -> &'static str;
+    /// File extension of the output, without the dot
     fn extension(&self) -> &'static str;
+    /// ffmpeg muxer (-f) for output without a file extension, such as stdout
+    fn muxer(&self) -> &'static str { self.extension() }
+    /// Extra ffmpeg arguments for non-seekable output
+    fn streaming_args(&self) -> &'static [&'static str] { &[] }
+    /// Audio bitrate passed to ffmpeg as -b:a, so quality doesn't depend on the codec's default.
+    /// None for lossless and uncompressed formats.
+    fn default_bitrate_bps(&self) -> Option<u64> { None }
+    /// ffmpeg arguments setting the output quality, they have to come after `-codec:a`
+    fn quality_args(&self) -> Vec<String> {
+        bitrate_args(self.default_bitrate_bps())
+    }
+    /// Average output bitrate for estimating file sizes, given the source's sample rate and
+    /// channel count. None when it isn't known in advance, as for VBR or lossless compression.
+    fn estimated_bitrate_bps(&self, _sample_rate_hz: Option<u32>, _channels: Option<u32>) -> Option<u64> {
+        self.default_bitrate_bps()
+    }
+}
+
+/// Bitrate of 16-bit PCM at the source's sample rate and channel count
+fn pcm_bitrate_bps(sample_rate_hz: Option<u32>, channels: Option<u32>) -> Option<u64> {
+    Some(u64::from(sample_rate_hz?) * u64::from(channels?) * 16)
+}
+
+/// `-b:a` for a fixed bitrate, nothing when there is none
+fn bitrate_args(bitrate_bps: Option<u64>) -> Vec<String> {
+    bitrate_bps
+        .map(|bitrate| vec!["-b:a".to_string(), bitrate.to_string()])
+        .unwrap_or_default()
 }
 
-pub struct Mp3Format;
+/// MP3 encoded with LAME
+#[derive(Default)]
+pub struct Mp3Format {
+    /// VBR quality from --mp3-quality, constant bitrate when None
+    pub quality: Option<u8>,
+}
+/// Uncompressed 16-bit little-endian PCM
 pub struct WavFormat;
+/// Lossless FLAC
 pub struct FlacFormat;
+/// AAC in an MP4 container (.m4a)
 pub struct AacFormat;
+/// Vorbis in an Ogg container
 pub struct OggFormat;
+/// Uncompressed 16-bit big-endian PCM
+pub struct AiffFormat;
 
 impl OutputFormat for Mp3Format {
     fn codec(&self) -> &'static str { "mp3" }
     fn extension(&self) -> &'static str { "mp3" }
+    fn default_bitrate_bps(&self) -> Option<u64> { Some(128_000) }
+    fn quality_args(&self) -> Vec<String> {
+        match self.quality {
+            Some(quality) => vec!["-q:a".to_string(), quality.to_string()],
+            None => bitrate_args(self.default_bitrate_bps()),
+        }
+    }
+    fn estimated_bitrate_bps(&self, _sample_rate_hz: Option<u32>, _channels: Option<u32>) -> Option<u64> {
+        // The VBR bitrate depends on the audio
+        self.quality.map_or(self.default_bitrate_bps(), |_| None)
+    }
 }
 impl OutputFormat for WavFormat {
     fn codec(&self) -> &'static str { "pcm_s16le" }
     fn extension(&self) -> &'static str { "wav" }
+    fn estimated_bitrate_bps(&self, sample_rate_hz: Option<u32>, channels: Option<u32>) -> Option<u64> {
+        pcm_bitrate_bps(sample_rate_hz, channels)
+    }
 }
 impl OutputFormat for FlacFormat {
     fn codec(&self) -> &'static str { "flac" }
@@ -135,13 +85,27 @@ impl OutputFormat for FlacFormat {
 impl OutputFormat for AacFormat {
     fn codec(&self) -> &'static str { "aac" }
     fn extension(&self) -> &'static str { "m4a" }
+    fn muxer(&self) -> &'static str { "ipod" }
+    fn default_bitrate_bps(&self) -> Option<u64> { Some(128_000) }
+    // The moov atom normally gets written at the end, which needs a seekable output
+    fn streaming_args(&self) -> &'static [&'static str] { &["-movflags", "+frag_keyframe+empty_moov"] }
 }
 impl OutputFormat for OggFormat {
     fn codec(&self) -> &'static str { "vorbis" }
     fn extension(&self) -> &'static str { "ogg" }
+    fn default_bitrate_bps(&self) -> Option<u64> { Some(96_000) }
+}
+impl OutputFormat for AiffFormat {
+    // AIFF stores big-endian PCM, unlike WAV
+    fn codec(&self) -> &'static str { "pcm_s16be" }
+    fn extension(&self) -> &'static str { "aiff" }
+    fn estimated_bitrate_bps(&self, sample_rate_hz: Option<u32>, channels: Option<u32>) -> Option<u64> {
+        pcm_bitrate_bps(sample_rate_hz, channels)
+    }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+/// Output audio format (--output-type)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum OutputType {
     /// MPEG Layer 3 Audio (.mp3)
     Mp3,
@@ -152,42 +116,213 @@ pub enum OutputType {
     /// Advanced Audio Coding (.m4a)
     M4a,
     /// Ogg Vorbis Audio (.ogg)
-    Ogg
+    Ogg,
+    /// Audio Interchange File Format (.aiff)
+    Aiff,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+/// Directory layout of split chapter files (--split-structure)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum SplitStructure {
     /// All chapters in a single directory
     Flat,
     /// Create folders based on chapter hierarchy
     Hierarchical,
+    /// One folder per disc (top-level part), with the chapters flat inside
+    ByDisc,
+}
+
+/// Text encoding of the chapters file (--chapter-encoding)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum ChapterEncoding {
+    /// UTF-8, with or without a byte order mark
+    #[value(name = "utf-8")]
+    Utf8,
+    /// UTF-16 little endian
+    #[value(name = "utf-16-le")]
+    Utf16Le,
+    /// UTF-16 big endian
+    #[value(name = "utf-16-be")]
+    Utf16Be,
+    /// ISO-8859-1 (decoded as its Windows-1252 superset)
+    #[value(name = "latin-1")]
+    Latin1,
+    /// Detect UTF-8/UTF-16 from the byte order mark, UTF-8 otherwise
+    Auto,
+}
+
+/// Case applied to chapter titles (--title-case)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum TitleCase {
+    /// Keep titles as they are
+    Preserve,
+    /// Title Case, with small words kept lowercase
+    Title,
+    /// ALL UPPERCASE
+    Upper,
+    /// all lowercase
+    Lower,
+    /// First letter uppercase, the rest lowercase
+    Sentence,
+}
+
+impl TitleCase {
+    /// Transform a chapter title to this case
+    pub fn apply(&self, title: &str) -> String {
+        match self {
+            TitleCase::Preserve => title.to_string(),
+            TitleCase::Title => titlecase::titlecase(title),
+            TitleCase::Upper => title.to_uppercase(),
+            TitleCase::Lower => title.to_lowercase(),
+            TitleCase::Sentence => {
+                let lower = title.to_lowercase();
+                let mut chars = lower.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => lower,
+                }
+            }
+        }
+    }
+}
+
+/// Which neighbour a short chapter is merged into (--merge-direction)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum MergeDirection {
+    /// Merge short chapters into the following chapter
+    Next,
+    /// Merge short chapters into the preceding chapter
+    Previous,
+}
+
+/// What to do with chapters that overlap the next one (--overlap-strategy)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum OverlapStrategy {
+    /// Log overlapping chapters and leave them as they are
+    Warn,
+    /// Shorten a chapter to end where the next one starts
+    Truncate,
+    /// Abort on overlapping chapters
+    Error,
+}
+
+/// Format of the --log-file output
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum LogFileFormat {
+    /// Human readable lines
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// ffmpeg's -loglevel (--ffmpeg-log-level)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum FfmpegLogLevel {
+    /// Show nothing at all
+    Quiet,
+    /// Only show fatal errors which could lead the process to crash
+    Panic,
+    /// Only show fatal errors
+    Fatal,
+    /// Show all errors
+    Error,
+    /// Show all warnings and errors
+    Warning,
+    /// Show informative messages during processing
+    Info,
+    /// Same as info, except more verbose
+    Verbose,
+    /// Show everything, including debugging information
+    Debug,
+    /// Show everything, including tracing information
+    Trace,
+}
+
+impl FfmpegLogLevel {
+    /// The value passed to ffmpeg's -loglevel option
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FfmpegLogLevel::Quiet => "quiet",
+            FfmpegLogLevel::Panic => "panic",
+            FfmpegLogLevel::Fatal => "fatal",
+            FfmpegLogLevel::Error => "error",
+            FfmpegLogLevel::Warning => "warning",
+            FfmpegLogLevel::Info => "info",
+            FfmpegLogLevel::Verbose => "verbose",
+            FfmpegLogLevel::Debug => "debug",
+            FfmpegLogLevel::Trace => "trace",
+        }
+    }
+
+    /// The level ffmpeg's stderr is forwarded at, `None` when it isn't forwarded
+    pub fn forward_level(&self) -> Option<log::Level> {
+        match self {
+            FfmpegLogLevel::Info => Some(log::Level::Info),
+            FfmpegLogLevel::Verbose | FfmpegLogLevel::Debug => Some(log::Level::Debug),
+            FfmpegLogLevel::Trace => Some(log::Level::Trace),
+            _ => None,
+        }
+    }
 }
 
 impl OutputType {
+    /// Encoder settings for this output type
     pub fn get_format(&self) -> Box<dyn OutputFormat> {
         match self {
-            OutputType::Mp3 => Box::new(Mp3Format),
+            OutputType::Mp3 => Box::new(Mp3Format::default()),
             OutputType::Wav => Box::new(WavFormat),
             OutputType::Flac => Box::new(FlacFormat),
             OutputType::M4a => Box::new(AacFormat),
             OutputType::Ogg => Box::new(OggFormat),
+            OutputType::Aiff => Box::new(AiffFormat),
         }
     }
 }
 
+impl ValueEnum for SchemaName {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[SchemaName::Chapters, SchemaName::Voucher]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(clap::builder::PossibleValue::new(match self {
+            SchemaName::Chapters => "chapters",
+            SchemaName::Voucher => "voucher",
+        }))
+    }
+}
+
+impl ValueEnum for TitleDisambiguation {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[TitleDisambiguation::None, TitleDisambiguation::Number, TitleDisambiguation::Time]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(clap::builder::PossibleValue::new(match self {
+            TitleDisambiguation::None => "none",
+            TitleDisambiguation::Number => "number",
+            TitleDisambiguation::Time => "time",
+        }))
+    }
+}
+
 impl ValueEnum for ChapterNamingFormat {
     fn value_variants<'a>() -> &'a [Self] {
-        &[
-            ChapterNamingFormat::ChapterNumberTitle,
+        // The prefix is a String, so the variants can't be a constant
+        static VARIANTS: OnceLock<[ChapterNamingFormat; 4]> = OnceLock::new();
+        VARIANTS.get_or_init(|| [
+            ChapterNamingFormat::ChapterNumberTitle { prefix: DEFAULT_CHAPTER_PREFIX.to_string() },
             ChapterNamingFormat::NumberTitle,
+            ChapterNamingFormat::TrackNumberTitle,
             ChapterNamingFormat::TitleOnly,
-        ]
+        ])
     }
 
     fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
         match self {
-            ChapterNamingFormat::ChapterNumberTitle => Some(clap::builder::PossibleValue::new("chapter-number-title")),
+            ChapterNamingFormat::ChapterNumberTitle { .. } => Some(clap::builder::PossibleValue::new("chapter-number-title")),
             ChapterNamingFormat::NumberTitle => Some(clap::builder::PossibleValue::new("number-title")),
+            ChapterNamingFormat::TrackNumberTitle => Some(clap::builder::PossibleValue::new("track-number-title")),
             ChapterNamingFormat::TitleOnly => Some(clap::builder::PossibleValue::new("title-only")),
             ChapterNamingFormat::Custom(_) => None, // Custom formats are handled separately
         }
@@ -195,14 +330,17 @@ impl ValueEnum for ChapterNamingFormat {
 
     fn from_str(input: &str, _ignore_case: bool) -> Result<Self, String> {
         match input {
-            "chapter-number-title" => Ok(ChapterNamingFormat::ChapterNumberTitle),
+            "chapter-number-title" => Ok(ChapterNamingFormat::ChapterNumberTitle {
+                prefix: DEFAULT_CHAPTER_PREFIX.to_string(),
+            }),
             "number-title" => Ok(ChapterNamingFormat::NumberTitle),
+            "track-number-title" => Ok(ChapterNamingFormat::TrackNumberTitle),
             "title-only" => Ok(ChapterNamingFormat::TitleOnly),
             custom if custom.starts_with("custom:") => {
                 let pattern = custom.strip_prefix("custom:").unwrap().to_string();
                 Ok(ChapterNamingFormat::Custom(pattern))
             },
-            _ => Err(format!("Invalid chapter naming format: {}. Valid options: chapter-number-title, number-title, title-only, custom:pattern", input)),
+            _ => Err(format!("Invalid chapter naming format: {}. Valid options: chapter-number-title, number-title, track-number-title, title-only, custom:pattern", input)),
         }
     }
 }
@@ -0,0 +1,3622 @@
+use crate::models::{ChapterStats, DuplicateTitles, validate_chapter_json_schema, EXPECTED_AUDIO_CODECS, MappedTags, FFProbeFormat, FFProbeChapters, AudibleChapters, StrictAudibleChapters, FlattenedChapter, MergedChapter, ChapterNamingFormat, TitleDisambiguation, DEFAULT_CHAPTER_PREFIX};
+use crate::models;
+use crate::util::filename::{expand_dir_template, sanitize_title, SanitizeConfig};
+use crate::util::archive::extract_book;
+use crate::util::download::{download_file, http_url};
+use crate::util::ffmpeg_args::FFmpegArgs;
+use crate::util::grouping::{group_chapters_by_part, merge_short_chapters};
+use crate::util::probe::ffprobe;
+use crate::util::temp_file::{temp_output_path, TempFileGuard};
+use crate::error::AudibleUtilError;
+use crate::cli::{self, ChapterEncoding, FfmpegLogLevel, MergeDirection, Mp3Format, OutputFormat, OutputType, OverlapStrategy, SplitStructure, TitleCase};
+use crate::progress::{progress_draw_target, ConversionProgress, ProgressEvent, ProgressManager};
+use indicatif::{ProgressBar, ProgressStyle};
+use inflector::Inflector;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::{
+    io::{BufRead, Write},
+    process::{Command, Stdio},
+};
+use anyhow::{Context, Result};
+use log::{info, error, warn};
+use is_terminal::IsTerminal;
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+/// Everything a conversion can be configured with, one field per command line option.
+///
+/// `Default` matches the defaults of the command line.
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    /// Input .aaxc or .aax file (--aaxc_path), `-` for stdin. Required unless `zip_path` is set
+    pub aaxc_path: Option<PathBuf>,
+    /// ZIP file with the .aaxc, voucher and chapters (--zip-path)
+    pub zip_path: Option<PathBuf>,
+    /// Extract the ZIP file here and keep the files, instead of a temporary directory (--zip-extract-dir)
+    pub zip_extract_dir: Option<PathBuf>,
+    /// Voucher file with the decryption key and IV (--voucher-path), found next to the input if not set
+    pub voucher_path: Option<PathBuf>,
+    /// Decryption key in hex, instead of a voucher (--key)
+    pub key: Option<String>,
+    /// Command printing the decryption key (--key-from-cmd)
+    pub key_from_cmd: Option<String>,
+    /// Decryption IV in hex, instead of a voucher (--iv)
+    pub iv: Option<String>,
+    /// Command printing the decryption IV (--iv-from-cmd)
+    pub iv_from_cmd: Option<String>,
+    /// Activation bytes as 8 lowercase hex digits, for .aax files (--activation-bytes)
+    pub activation_bytes: Option<String>,
+    /// Book duration in seconds, needed when reading the book from stdin (--duration)
+    pub duration: Option<f64>,
+    /// Output file or directory, the current directory if not set (--output-path); `-` for stdout
+    pub output_path: Option<PathBuf>,
+    /// Per-book output directory with tag placeholders, e.g. `{artist}/{album}` (--output-dir-template)
+    pub output_dir_template: Option<String>,
+    /// Write into a directory named after the ASIN (--asin-dirs)
+    pub asin_dirs: bool,
+    /// Write chapter files into a directory named after the album (--album-dir)
+    pub album_dir: bool,
+    /// Split the output by chapters (--split)
+    pub split: bool,
+    /// Split the output at silences instead of by chapters (--split-at-silence)
+    pub split_at_silence: bool,
+    /// Title silence-split parts by their start time (--title-from-position)
+    pub title_from_position: bool,
+    /// Embed chapter markers in a single m4a file (--embed-chapters)
+    pub embed_chapters: bool,
+    /// Skip the conversion when the output is newer than the input (--conditional)
+    pub conditional: bool,
+    /// Use the embedded chapter markers when no chapters file is found (--chapters-from-ffprobe)
+    pub chapters_from_ffprobe: bool,
+    /// Parent directories also searched for the chapters file (--chapter-search-parents)
+    pub chapter_search_parents: usize,
+    /// Chapters file or http(s) URL (--chapter-path)
+    pub chapter_path: Option<String>,
+    /// HTTP headers for downloading `chapter_path` (--chapter-path-header)
+    pub chapter_path_header: Vec<(String, String)>,
+    /// Timeout in seconds for downloading `chapter_path` (--chapter-download-timeout)
+    pub chapter_download_timeout: u64,
+    /// List the chapter outputs without running ffmpeg (--metadata-only)
+    pub metadata_only: bool,
+    /// With `metadata_only`, check existing chapter files against the chapter data (--verify)
+    pub verify: bool,
+    /// With `metadata_only`, list chapter start and end times (--timestamps)
+    pub timestamps: bool,
+    /// Fail on unknown fields in the chapters file (--strict-json)
+    pub strict_json: bool,
+    /// Character encoding of the chapters file (--chapter-encoding)
+    pub chapter_encoding: ChapterEncoding,
+    /// Case applied to chapter titles (--title-case)
+    pub title_case: TitleCase,
+    /// Pattern removed from each chapter title (--strip-title-prefix)
+    pub strip_title_prefix: Option<regex::Regex>,
+    /// Apply `title_case` to file names only (--title-case-output-only)
+    pub title_case_output_only: bool,
+    /// Tag chapter files with their part as disc number (--disc-tags)
+    pub disc_tags: bool,
+    /// Extend chapters to close the gaps between them (--chapter-gap-fill)
+    pub chapter_gap_fill: bool,
+    /// Milliseconds every chapter is moved by, may be negative (--chapter-time-offset)
+    pub chapter_time_offset: i64,
+    /// How overlapping chapters are handled (--overlap-strategy)
+    pub overlap_strategy: OverlapStrategy,
+    /// Skip chapters numbered below this (--min-chapter)
+    pub min_chapter: Option<u64>,
+    /// Skip chapters numbered above this (--max-chapter)
+    pub max_chapter: Option<u64>,
+    /// File listing the chapter numbers and ranges to convert (--chapter-list-file)
+    pub chapter_list_file: Option<PathBuf>,
+    /// Convert only this chapter as a test run (--test-chapter)
+    pub test_chapter: Option<u64>,
+    /// Minimum chapter duration in seconds (--min-chapter-duration)
+    pub min_chapter_duration: Option<f64>,
+    /// File names of chapter files (--chapter-naming-format)
+    pub chapter_naming_format: ChapterNamingFormat,
+    /// Prefix replacing "Chapter" in chapter-number-title file names (--chapter-prefix)
+    pub chapter_prefix: Option<String>,
+    /// Restart chapter numbering in each part of the book (--reset-chapter-numbers-per-part)
+    pub reset_chapter_numbers_per_part: bool,
+    /// Shorthand for a `time` `title_disambiguation` (--disambiguate-duplicates)
+    pub disambiguate_duplicates: bool,
+    /// Suffix for file names of chapters with duplicate titles (--title-disambiguation)
+    pub title_disambiguation: TitleDisambiguation,
+    /// Only warn when chapters would be written to the same file (--allow-duplicate-filenames)
+    pub allow_duplicate_filenames: bool,
+    /// Directory layout of the chapter files (--split-structure)
+    pub split_structure: SplitStructure,
+    /// Merge short chapters into a neighbour instead of dropping them (--merge-short-chapters)
+    pub merge_short_chapters: bool,
+    /// Merge chapters whose boundary has no silence (--merge-on-silence)
+    pub merge_on_silence: bool,
+    /// Neighbour short chapters are merged into (--merge-direction)
+    pub merge_direction: MergeDirection,
+    /// Output audio format (--output-type)
+    pub output_type: OutputType,
+    /// VBR quality 0-9 for MP3 output, constant bitrate if not set (--mp3-quality)
+    pub mp3_quality: Option<u8>,
+    /// Output types that are refused (--exclude-output-type)
+    pub exclude_output_type: Vec<OutputType>,
+    /// Report progress in more detail (--verbose-progress)
+    pub verbose_progress: bool,
+    /// Print JSON progress events to stdout (--machine-readable)
+    pub machine_readable: bool,
+    /// Don't draw progress bars (--no-progress)
+    pub no_progress: bool,
+    /// Draw progress bars even when stderr is not a terminal (--force-progress)
+    pub force_progress: bool,
+    /// Don't copy the input's metadata into the output (--no-ffmpeg-metadata)
+    pub no_ffmpeg_metadata: bool,
+    /// Output metadata overrides as field and value (--metadata-map)
+    pub metadata_map: Vec<(String, String)>,
+    /// Extra ffmpeg audio filter chain (--audio-filter)
+    pub audio_filter: Option<String>,
+    /// Keep temporary files when a conversion fails (--keep-temp-files)
+    pub keep_temp_files: bool,
+    /// M3U playlist of the chapter files to write (--playlist)
+    pub playlist: Option<PathBuf>,
+    /// Write an extended M3U playlist, by default only for .m3u8 (--m3u-extended)
+    pub m3u_extended: Option<bool>,
+    /// Retries of a failed chapter conversion (--retry)
+    pub retry: u32,
+    /// Ask before writing more chapter files than this (--warn-chapter-count)
+    pub warn_chapter_count: usize,
+    /// Don't ask before large splits (--yes)
+    pub yes: bool,
+    /// Seconds ffprobe may spend probing the input (--ffprobe-timeout)
+    pub ffprobe_timeout: u64,
+    /// Upper limit on the conversion speed in times realtime (--max-speed)
+    pub max_speed: Option<f64>,
+    /// Measure the conversion speed without writing output (--benchmark)
+    pub benchmark: bool,
+    /// Report paths relative to the output directory in JSON events (--relative-paths)
+    pub relative_paths: bool,
+    /// Progress lines folded into one JSON progress event (--progress-batch)
+    pub progress_batch: u64,
+    /// ffmpeg threads: `auto`, `0` for ffmpeg's choice, or a number (--threads)
+    pub threads: String,
+    /// ffmpeg's log level (--ffmpeg-log-level)
+    pub ffmpeg_log_level: FfmpegLogLevel,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            aaxc_path: None,
+            zip_path: None,
+            zip_extract_dir: None,
+            voucher_path: None,
+            key: None,
+            key_from_cmd: None,
+            iv: None,
+            iv_from_cmd: None,
+            activation_bytes: None,
+            duration: None,
+            output_path: None,
+            output_dir_template: None,
+            asin_dirs: false,
+            album_dir: false,
+            split: false,
+            split_at_silence: false,
+            title_from_position: false,
+            embed_chapters: false,
+            conditional: false,
+            chapters_from_ffprobe: false,
+            chapter_search_parents: 0,
+            chapter_path: None,
+            chapter_path_header: Vec::new(),
+            chapter_download_timeout: 30,
+            metadata_only: false,
+            verify: false,
+            timestamps: false,
+            strict_json: false,
+            chapter_encoding: ChapterEncoding::Utf8,
+            title_case: TitleCase::Preserve,
+            strip_title_prefix: None,
+            title_case_output_only: false,
+            disc_tags: false,
+            chapter_gap_fill: false,
+            chapter_time_offset: 0,
+            overlap_strategy: OverlapStrategy::Warn,
+            min_chapter: None,
+            max_chapter: None,
+            chapter_list_file: None,
+            test_chapter: None,
+            min_chapter_duration: None,
+            chapter_naming_format: ChapterNamingFormat::ChapterNumberTitle { prefix: DEFAULT_CHAPTER_PREFIX.to_string() },
+            chapter_prefix: None,
+            reset_chapter_numbers_per_part: false,
+            disambiguate_duplicates: false,
+            title_disambiguation: TitleDisambiguation::None,
+            allow_duplicate_filenames: false,
+            split_structure: SplitStructure::Flat,
+            merge_short_chapters: false,
+            merge_on_silence: false,
+            merge_direction: MergeDirection::Next,
+            output_type: OutputType::Mp3,
+            mp3_quality: None,
+            exclude_output_type: Vec::new(),
+            verbose_progress: false,
+            machine_readable: false,
+            no_progress: false,
+            force_progress: false,
+            no_ffmpeg_metadata: false,
+            metadata_map: Vec::new(),
+            audio_filter: None,
+            keep_temp_files: false,
+            playlist: None,
+            m3u_extended: None,
+            retry: 0,
+            warn_chapter_count: 100,
+            yes: false,
+            ffprobe_timeout: 60,
+            max_speed: None,
+            benchmark: false,
+            relative_paths: false,
+            progress_batch: 1,
+            threads: "auto".to_string(),
+            ffmpeg_log_level: FfmpegLogLevel::Fatal,
+        }
+    }
+}
+
+impl ConvertOptions {
+    /// The output format for `output_type`, with the MP3 quality from `mp3_quality`
+    pub fn output_format(&self) -> Box<dyn OutputFormat> {
+        match self.output_type {
+            OutputType::Mp3 => Box::new(Mp3Format { quality: self.mp3_quality }),
+            output_type => output_type.get_format(),
+        }
+    }
+
+    /// Whether the output is split, by chapters (`split`) or at silences (`split_at_silence`)
+    pub fn splits(&self) -> bool {
+        self.split || self.split_at_silence
+    }
+
+    /// `title_disambiguation`, or `time` for `disambiguate_duplicates`
+    pub fn title_disambiguation(&self) -> TitleDisambiguation {
+        if self.disambiguate_duplicates {
+            TitleDisambiguation::Time
+        } else {
+            self.title_disambiguation
+        }
+    }
+}
+
+/// Summary of a --benchmark run
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct BenchmarkResult {
+    total_chapters: usize,
+    total_audio_hours: f64,
+    average_speed: f64,
+    peak_speed: f64,
+    elapsed_seconds: f64,
+}
+
+impl BenchmarkResult {
+    pub(crate) fn new(total_chapters: usize, total_audio_ms: i64, speed: &ConversionProgress, elapsed: Duration) -> Self {
+        Self {
+            total_chapters,
+            total_audio_hours: total_audio_ms.max(0) as f64 / 3_600_000.0,
+            average_speed: speed.average_speed,
+            peak_speed: speed.peak_speed,
+            elapsed_seconds: elapsed.as_secs_f64(),
+        }
+    }
+
+    /// Print as a `benchmark_result` event, or as a table for people
+    fn print(&self, machine_readable: bool) {
+        if machine_readable {
+            println!("{}", ProgressEvent::BenchmarkResult(self.clone()).to_json());
+        } else {
+            print!("{}", self);
+        }
+    }
+}
+
+impl std::fmt::Display for BenchmarkResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Benchmark (no output written)")?;
+        writeln!(f, "  Chapters:       {}", self.total_chapters)?;
+        writeln!(f, "  Audio:          {:.2} h", self.total_audio_hours)?;
+        writeln!(f, "  Average speed:  {:.1}x", self.average_speed)?;
+        writeln!(f, "  Peak speed:     {:.1}x", self.peak_speed)?;
+        writeln!(f, "  Wall time:      {}", ConversionProgress::format_time(self.elapsed_seconds))
+    }
+}
+
+/// Result of `validate-chapters` for one chapters file
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub(crate) struct ValidationReport {
+    path: String,
+    /// False when the file couldn't be read, decoded or parsed, the errors say why
+    parsed: bool,
+    valid: bool,
+    chapter_count: usize,
+    /// Sum of the chapter lengths
+    total_duration_ms: i64,
+    runtime_length_ms: i64,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl ValidationReport {
+    /// Print as a `validation_report` event, or as a list of issues for people
+    fn print(&self, machine_readable: bool) {
+        if machine_readable {
+            println!("{}", ProgressEvent::ValidationReport(self.clone()).to_json());
+        } else {
+            print!("{}", self);
+        }
+    }
+}
+
+impl std::fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Chapters file: {}", self.path)?;
+        if self.parsed {
+            writeln!(
+                f,
+                "  {} chapters, {} total, runtime {}",
+                self.chapter_count,
+                format_time_from_ms(self.total_duration_ms),
+                format_time_from_ms(self.runtime_length_ms)
+            )?;
+        }
+        for error in &self.errors {
+            writeln!(f, "  Error: {}", error)?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "  Warning: {}", warning)?;
+        }
+        if self.valid {
+            writeln!(f, "Valid ({} warnings)", self.warnings.len())
+        } else {
+            writeln!(f, "Invalid: {} errors, {} warnings", self.errors.len(), self.warnings.len())
+        }
+    }
+}
+
+/// Identity of the book being converted, reported in `conversion_started`
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BookInfo {
+    pub(crate) asin: String,
+    pub(crate) title: String,
+    pub(crate) artist: String,
+    pub(crate) album: String,
+    pub(crate) genre: Option<String>,
+    pub(crate) date: Option<String>,
+    pub(crate) audio_codec: Option<String>,
+    pub(crate) sample_rate_hz: Option<u32>,
+    pub(crate) channels: Option<u32>,
+    pub(crate) source_bitrate_bps: Option<u64>,
+    pub(crate) chapter_stats: ChapterStats,
+}
+
+impl BookInfo {
+    /// Take the audio details from the probed audio stream. The stream's bit rate is
+    /// preferred, the container's covers files where the stream doesn't report one.
+    fn set_source_audio(&mut self, ffprobe: &FFProbeFormat) {
+        let stream = ffprobe.audio_stream();
+        self.audio_codec = stream.and_then(|stream| stream.codec_name.clone());
+        self.sample_rate_hz = stream.and_then(|stream| stream.sample_rate_hz());
+        self.channels = stream.and_then(|stream| stream.channels).and_then(|channels| u32::try_from(channels).ok());
+        self.source_bitrate_bps = stream
+            .and_then(|stream| stream.bit_rate_bps())
+            .or_else(|| ffprobe.format.bit_rate.trim().parse().ok());
+    }
+
+    /// e.g. `aac, 22050 Hz, mono, 64 kbps`, with `?` for unknown values
+    fn source_quality(&self) -> String {
+        let channels = match self.channels {
+            Some(1) => "mono".to_string(),
+            Some(2) => "stereo".to_string(),
+            Some(channels) => format!("{} channels", channels),
+            None => "? channels".to_string(),
+        };
+        format!(
+            "{}, {} Hz, {}, {} kbps",
+            self.audio_codec.as_deref().unwrap_or("?"),
+            self.sample_rate_hz.map_or("?".to_string(), |rate| rate.to_string()),
+            channels,
+            self.source_bitrate_bps.map_or("?".to_string(), |rate| (rate / 1000).to_string()),
+        )
+    }
+}
+
+/// Options shaping the audio ffmpeg produces, shared by every conversion mode
+#[derive(Debug, Clone, Default)]
+struct ConversionFlags {
+    /// Filter chain from --audio-filter, appended after the filters built from other options
+    audio_filter: Option<String>,
+    /// Tag overrides from --metadata-map, written on top of the tags copied from the input
+    metadata_map: HashMap<String, String>,
+    /// ffmpeg arguments setting the output bitrate or VBR quality, empty to leave it to the codec
+    quality_args: Vec<String>,
+    /// Draw progress bars, off with --no-progress or when stderr is not a terminal
+    progress_bars: bool,
+    /// Upper limit on the conversion speed from --max-speed, in times realtime
+    max_speed: Option<f64>,
+    /// With --benchmark, the muxer for writing the output to the null device
+    benchmark_muxer: Option<&'static str>,
+}
+
+/// Input container, which decides how ffmpeg decrypts it
+#[derive(Debug, Clone, PartialEq)]
+enum InputFormat {
+    /// .aaxc, decrypted with the key and IV from the voucher or --key/--iv
+    Aaxc { key: String, iv: String },
+    /// Legacy .aax, decrypted with the account's activation bytes
+    Aax { activation_bytes: String },
+}
+
+impl InputFormat {
+    /// Add the ffmpeg options decrypting the input
+    fn apply_decryption(&self, args: FFmpegArgs) -> FFmpegArgs {
+        match self {
+            InputFormat::Aaxc { key, iv } => args.decryption_key(key, iv),
+            InputFormat::Aax { activation_bytes } => args.activation_bytes(activation_bytes),
+        }
+    }
+}
+
+impl ConversionFlags {
+    fn from_options(options: &ConvertOptions) -> Self {
+        Self {
+            audio_filter: options.audio_filter.clone(),
+            metadata_map: options.metadata_map.iter().cloned().collect(),
+            quality_args: options.output_format().quality_args(),
+            progress_bars: !options.no_progress && (options.force_progress || std::io::stderr().is_terminal()),
+            max_speed: options.max_speed,
+            benchmark_muxer: options.benchmark.then(|| options.output_type.get_format().muxer()),
+        }
+    }
+}
+
+/// Size of `audio_ms` of audio encoded at `bitrate_bps`
+fn estimated_output_bytes(bitrate_bps: u64, audio_ms: i64) -> u64 {
+    bitrate_bps * audio_ms.max(0) as u64 / 8 / 1000
+}
+
+/// The question asked before a split into `file_count` files. No conversion speed is known
+/// yet, so the time is only bounded by --max-speed.
+fn large_split_prompt(file_count: usize, audio_ms: i64, estimated_size: Option<u64>, max_speed: Option<f64>) -> String {
+    let size = estimated_size.map_or_else(|| "unknown".to_string(), ConversionProgress::format_size);
+    let time = max_speed.map_or_else(
+        || "unknown".to_string(),
+        |speed| format!("at least {}", ConversionProgress::format_time(audio_ms as f64 / 1000.0 / speed)),
+    );
+    format!(
+        "This will create {} files from {} of audio (estimated disk usage: {}, estimated time: {}). Continue? [y/N] ",
+        file_count,
+        ConversionProgress::format_time(audio_ms as f64 / 1000.0),
+        size,
+        time
+    )
+}
+
+/// Ask `prompt` on the terminal, true when the answer is yes
+fn confirm_large_split(prompt: &str) -> Result<bool> {
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).context("Failed to read the answer from stdin")?;
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Convert a book, what the `audible-util` binary does when no subcommand is given
+pub fn convert(options: ConvertOptions) -> Result<(), AudibleUtilError> {
+    // Resolved once, so every ffmpeg run of the book gets the same thread count
+    let options = ConvertOptions { threads: ffmpeg_threads(&options.threads), ..options };
+
+    // --- Early input validation ---
+
+    if options.exclude_output_type.contains(&options.output_type) {
+        return Err(anyhow::anyhow!(
+            "Output type {} is excluded by --exclude-output-type. Choose another --output-type.",
+            options.output_type.get_format().extension()
+        ).into());
+    }
+
+    if options.mp3_quality.is_some() && options.output_type != cli::OutputType::Mp3 {
+        return Err(anyhow::anyhow!(
+            "--mp3-quality requires --output-type mp3, got {}.",
+            options.output_type.get_format().extension()
+        ).into());
+    }
+
+    if options.embed_chapters && options.output_type != cli::OutputType::M4a {
+        return Err(anyhow::anyhow!(
+            "--embed-chapters requires --output-type m4a, got {}.",
+            options.output_type.get_format().extension()
+        ).into());
+    }
+
+    let chapter_list = options.chapter_list_file.as_deref().map(read_chapter_list).transpose()?;
+
+    // Kept until the end of the run, a temporary extraction directory is removed when it's dropped
+    let extracted_book = options
+        .zip_path
+        .as_deref()
+        .map(|zip_path| extract_book(zip_path, options.zip_extract_dir.as_deref()))
+        .transpose()?;
+    // Required by clap unless a subcommand or --zip-path is given
+    let aaxc_file_path = match &extracted_book {
+        Some(extracted) => extracted.aaxc_path.clone(),
+        None => options.aaxc_path.clone().context("Missing required argument --aaxc_path")?,
+    };
+    // --voucher-path still takes precedence over the voucher in the archive
+    let voucher_path = options.voucher_path.clone().or_else(|| extracted_book.as_ref().map(|extracted| extracted.voucher_path.clone()));
+    // Kept until the end of the run like the extracted book, an http(s) --chapter-path is downloaded first
+    let downloaded_chapters = options
+        .chapter_path
+        .as_deref()
+        .and_then(http_url)
+        .map(|url| download_file(&url, &options.chapter_path_header, Duration::from_secs(options.chapter_download_timeout), options.keep_temp_files))
+        .transpose()?;
+    // --chapter-path takes precedence over the chapters file in the archive
+    let chapters_path = match (&downloaded_chapters, &options.chapter_path) {
+        (Some(downloaded), _) => Some(downloaded.path.as_path()),
+        (None, Some(chapter_path)) => Some(Path::new(chapter_path.as_str())),
+        (None, None) => extracted_book.as_ref().and_then(|extracted| extracted.chapters_path.as_deref()),
+    };
+    // `-` reads the book from stdin and writes `-` to stdout, for use in pipelines
+    let pipeline_input = aaxc_file_path == Path::new("-");
+    let pipeline_output = options.output_path.as_deref() == Some(Path::new("-"));
+    let input_extension = aaxc_file_path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+    // Check input .aaxc file exists, is readable, and has correct extension
+    if pipeline_input {
+        info!("Pipeline mode: reading the book from stdin");
+    } else {
+        if !aaxc_file_path.exists() {
+            return Err(AudibleUtilError::InputFileNotFound(aaxc_file_path));
+        }
+        if !aaxc_file_path.is_file() {
+            return Err(anyhow::anyhow!(
+                "Input path is not a file: {}. Please provide a valid .aaxc file.",
+                aaxc_file_path.display()
+            ).into());
+        }
+        if !matches!(input_extension.as_deref(), Some("aaxc") | Some("aax")) {
+            return Err(anyhow::anyhow!(
+                "Input file does not have a .aaxc extension: {}. Please provide a valid Audible .aaxc file, or a .aax file with --activation-bytes.",
+                aaxc_file_path.display()
+            ).into());
+        }
+        if std::fs::File::open(&aaxc_file_path).is_err() {
+            return Err(AudibleUtilError::InputFileNotReadable(aaxc_file_path));
+        }
+    }
+
+    let command_line_key = command_line_key(&options)?;
+
+    // .aax files are decrypted with activation bytes, and so is stdin when no voucher or key is given
+    let use_activation_bytes = if pipeline_input {
+        options.activation_bytes.is_some() && options.voucher_path.is_none() && command_line_key.is_none()
+    } else {
+        input_extension.as_deref() == Some("aax")
+    };
+    if use_activation_bytes && options.activation_bytes.is_none() {
+        return Err(anyhow::anyhow!(
+            "Decrypting .aax files requires --activation-bytes or the AUDIBLE_ACTIVATION_BYTES environment variable: {}",
+            aaxc_file_path.display()
+        ).into());
+    }
+
+    // Determine voucher file path: use CLI override if provided, none when the key and IV
+    // or activation bytes are used instead
+    let voucher_file_path = if use_activation_bytes {
+        info!("Using activation bytes, no voucher needed");
+        None
+    } else if command_line_key.is_some() {
+        info!("Using the key and IV from the command line");
+        None
+    } else if let Some(voucher_path) = voucher_path {
+        info!("Using voucher file from CLI: {}", voucher_path.display());
+        // Check voucher file exists and is readable
+        if !voucher_path.exists() {
+            return Err(AudibleUtilError::VoucherFileNotFound(voucher_path));
+        }
+        if !voucher_path.is_file() {
+            return Err(anyhow::anyhow!(
+                "Voucher path is not a file: {}. Please provide a valid voucher file.",
+                voucher_path.display()
+            ).into());
+        }
+        if std::fs::File::open(&voucher_path).is_err() {
+            return Err(AudibleUtilError::VoucherFileNotReadable(voucher_path));
+        }
+        Some(voucher_path)
+    } else if pipeline_input {
+        return Err(anyhow::anyhow!("Reading from stdin requires --voucher-path or --key and --iv, the voucher can't be inferred.").into());
+    } else {
+        let aaxc_file_path_stem = aaxc_file_path
+            .file_stem()
+            .context("Could not get file stem from the input file path. Please provide a valid .aaxc file.")?;
+        let path = aaxc_file_path.with_file_name(
+            format!(
+                "{}.voucher",
+                aaxc_file_path_stem
+                    .to_str()
+                    .context("Failed to convert file stem to string. Please check your input file name.")?
+            ),
+        );
+        info!("Using inferred voucher file: {}", path.display());
+        if !path.exists() {
+            return Err(AudibleUtilError::VoucherFileNotFound(path));
+        }
+        if !path.is_file() {
+            return Err(anyhow::anyhow!(
+                "Inferred voucher path is not a file: {}. Please provide a valid voucher file or use --voucher-path.",
+                path.display()
+            ).into());
+        }
+        if std::fs::File::open(&path).is_err() {
+            return Err(AudibleUtilError::VoucherFileNotReadable(path));
+        }
+        Some(path)
+    };
+
+    // If output path is provided, check parent directory exists and is writable
+    if let Some(ref output_path) = options.output_path.as_ref().filter(|_| !pipeline_output) {
+    
+        if output_path.exists() && output_path.is_dir() {
+            // If output_path is a directory, check if it's writable
+            if std::fs::metadata(output_path)
+                .map(|m| m.permissions().readonly())
+                .unwrap_or(true)
+            {
+                return Err(anyhow::anyhow!(
+                    "Output directory is not writable: {}. Please check permissions or specify a different output path.",
+                    output_path.display()
+                ).into());
+            }
+        } else if !output_path.exists() {
+            // If output_path does not exist, try to create it as a directory
+            if let Err(e) = std::fs::create_dir_all(output_path) {
+                return Err(anyhow::anyhow!(
+                    "Failed to create output directory '{}': {}. Please check permissions or specify a different output path.",
+                    output_path.display(),
+                    e
+                ).into());
+            }
+            if std::fs::metadata(output_path)
+                .map(|m| m.permissions().readonly())
+                .unwrap_or(true)
+            {
+                return Err(anyhow::anyhow!(
+                    "Output directory is not writable: {}. Please check permissions or specify a different output path.",
+                    output_path.display()
+                ).into());
+            }
+        } else if let Some(parent) = output_path.parent() {
+            // If output_path is a file path, ensure its parent directory exists or create it
+            if !parent.exists() {
+                if let Err(e) = std::fs::create_dir_all(parent) {
+                    return Err(anyhow::anyhow!(
+                        "Failed to create output directory '{}': {}. Please check permissions or specify a different output path.",
+                        parent.display(),
+                        e
+                    ).into());
+                }
+            }
+            if std::fs::metadata(parent)
+                .map(|m| m.permissions().readonly())
+                .unwrap_or(true)
+            {
+                return Err(anyhow::anyhow!(
+                    "Output directory is not writable: {}. Please check permissions or specify a different output path.",
+                    parent.display()
+                ).into());
+            }
+        }
+    }
+
+    // --- Pre-flight checks for ffmpeg and ffprobe ---
+    if !options.metadata_only {
+        check_external_tool("ffmpeg")?;
+    }
+    if !pipeline_input {
+        check_external_tool("ffprobe")?;
+    }
+
+    let voucher = voucher_file_path.as_deref().map(load_voucher).transpose()?;
+    let voucher_reference = voucher.as_ref().map(|voucher| &voucher.content_license.content_metadata.content_reference);
+    if let Some(reference) = voucher_reference {
+        info!("Book: {}", reference.to_display_string());
+    }
+    let mut book = BookInfo {
+        asin: voucher_reference.map(|reference| reference.asin.trim().to_string()).unwrap_or_default(),
+        ..Default::default()
+    };
+    let input_format = match &voucher {
+        Some(voucher) => InputFormat::Aaxc {
+            key: voucher.content_license.license_response.key.clone(),
+            iv: voucher.content_license.license_response.iv.clone(),
+        },
+        None if use_activation_bytes => InputFormat::Aax {
+            activation_bytes: options.activation_bytes.clone().unwrap_or_default(),
+        },
+        None => {
+            let (key, iv) = command_line_key.unwrap_or_default();
+            InputFormat::Aaxc { key, iv }
+        },
+    };
+
+    // ffprobe can't seek in stdin, so pipelines skip probing and run ffmpeg directly
+    if pipeline_input || pipeline_output {
+        return Ok(run_pipeline(&options, &aaxc_file_path, &input_format, &book)?);
+    }
+
+    info!("Running ffprobe on input file: {}", aaxc_file_path.display());
+    let ffprobe_json = ffprobe(&aaxc_file_path, Duration::from_secs(options.ffprobe_timeout))
+        .map_err(|e| match e.downcast_ref::<std::io::Error>() {
+            // The timeout message already names the file problem and the fix
+            Some(io_error) if io_error.kind() == std::io::ErrorKind::TimedOut => e,
+            _ => e.context(format!(
+                "Failed to probe input file: {}. Please ensure ffprobe is installed and the file is a valid Audible AAXC file.",
+                aaxc_file_path.display()
+            )),
+        })?;
+    ffprobe_json.validate().map_err(|e| anyhow::anyhow!("Invalid ffprobe data: {e}"))?;
+    if let Some(codec) = ffprobe_json.audio_stream().and_then(|stream| stream.codec_name.as_deref()) {
+        if !EXPECTED_AUDIO_CODECS.contains(&codec) {
+            warn!(
+                "Unexpected audio codec '{}' (expected one of: {}). The input may not be an Audible AAXC file.",
+                codec,
+                EXPECTED_AUDIO_CODECS.join(", ")
+            );
+        }
+    }
+    info!("ffprobe completed and validated");
+    info!("Book: {}", ffprobe_json.format.tags.to_display_string());
+    let metadata_map: HashMap<String, String> = options.metadata_map.iter().cloned().collect();
+    let tags = MappedTags::new(&ffprobe_json.format.tags, &metadata_map);
+    book.title = tags.title();
+    book.artist = tags.artist();
+    book.album = tags.album();
+    book.genre = tags.genre().filter(|genre| !genre.trim().is_empty());
+    book.date = tags.date().filter(|date| !date.trim().is_empty());
+    book.set_source_audio(&ffprobe_json);
+    info!("Source quality: {}", book.source_quality());
+    if book.asin.is_empty() {
+        book.asin = ffprobe_json.format.tags.asin().unwrap_or_default();
+    }
+
+    // Cross-check the ASIN when the file exposes one; this is best-effort so only warn
+    if let (Some(file_asin), Some(voucher)) = (ffprobe_json.format.tags.asin(), &voucher) {
+        let voucher_asin = voucher.content_license.content_metadata.content_reference.asin.trim();
+        if !file_asin.eq_ignore_ascii_case(voucher_asin) {
+            warn!(
+                "ASIN mismatch: input file reports {} but the voucher is for {}. You may have paired the wrong voucher with this .aaxc file.",
+                file_asin, voucher_asin
+            );
+        }
+    }
+
+    // Expand --output-dir-template into a per-book directory under the output base
+    let output_path = match &options.output_dir_template {
+        // An explicit output file takes precedence over the template
+        Some(_) if !options.splits() && options.output_path.as_ref().is_some_and(|path| !path.is_dir()) => {
+            info!("--output-path names an output file, ignoring --output-dir-template");
+            options.output_path.clone()
+        }
+        Some(template) => {
+            let book_dir = expand_dir_template(template, |name| tags.template_value(name))
+                .map_err(|e| anyhow::anyhow!("Invalid --output-dir-template '{}': {}", template, e))?;
+            let output_dir = options.output_path.clone().unwrap_or_else(|| PathBuf::from(".")).join(book_dir);
+            std::fs::create_dir_all(&output_dir)
+                .with_context(|| format!("Failed to create output directory: {}", output_dir.display()))?;
+            info!("Writing output to {}", output_dir.display());
+            Some(output_dir)
+        }
+        None => options.output_path.clone(),
+    };
+
+    // With --split the ASIN directory is resolved once the chapters JSON is parsed
+    let output_path = match output_path {
+        Some(path) if options.asin_dirs && !options.splits() && !path.is_dir() => {
+            info!("--output-path names an output file, ignoring --asin-dirs");
+            Some(path)
+        }
+        output_path if options.asin_dirs && !options.splits() => Some(asin_dir(output_path.as_deref(), &book.asin)?),
+        output_path => output_path,
+    };
+
+    let title = tags.title();
+    let album = tags.album();
+    let duration = ffprobe_json.format.duration;
+
+    // Determine output file extension and codec based on output_type (trait-based, extensible)
+    use crate::cli::OutputFormat;
+    let output_format: Box<dyn OutputFormat> = options.output_format();
+    let codec = output_format.codec();
+    let ext = output_format.extension();
+
+    // Determine output file name: use CLI override if provided
+    let file_name = if let Some(ref output_path) = output_path {
+        let default_name = format!("{}.{}", album.to_snake_case(), ext);
+        // If the path exists and is a directory, or if it was just created as a directory, use default filename inside it
+        if output_path.exists() && output_path.is_dir() {
+            output_path.join(&default_name).to_string_lossy().to_string()
+        } else if !output_path.exists() {
+            // If the path does not exist, check if it was intended as a directory (created above)
+            if std::fs::metadata(output_path).map(|m| m.is_dir()).unwrap_or(false) {
+                output_path.join(&default_name).to_string_lossy().to_string()
+            } else {
+                output_path.to_string_lossy().to_string()
+            }
+        } else {
+            // If output_path is a file path, use as-is
+            output_path.to_string_lossy().to_string()
+        }
+    } else {
+        format!("{}.{}", album.to_snake_case(), ext)
+    };
+
+    // Handle chapter splitting
+    if options.splits() {
+        info!("Chapter splitting requested");
+        
+        let (flattened_chapters, brand_outro_ms, chapters_asin) = if options.split_at_silence {
+            let duration_ms = (parse_duration_to_seconds(&duration) * 1000.0).round() as i64;
+            (detect_silence_chapters(&aaxc_file_path, &input_format, duration_ms, options.title_from_position)?, 0, None)
+        } else {
+            load_chapters(&options, &aaxc_file_path, chapters_path)?
+        };
+        
+        info!("Found {} total chapters", flattened_chapters.len());
+        book.chapter_stats = ChapterStats::from_chapters(&flattened_chapters);
+        info!("Chapter summary: {}", book.chapter_stats);
+        let parts = group_chapters_by_part(&flattened_chapters);
+        if parts.len() > 1 {
+            for (index, (part_title, chapters)) in parts.iter().enumerate() {
+                info!("Part {}: {} ({} chapters)", index + 1, part_title, chapters.len());
+            }
+        }
+
+        let mut flattened_chapters = flattened_chapters;
+        let range = chapter_number_range(options.min_chapter, options.max_chapter, flattened_chapters.len())?;
+        if range.is_some() || chapter_list.is_some() {
+            let total = flattened_chapters.len();
+            flattened_chapters.retain(|chapter| {
+                range.as_ref().is_none_or(|range| range.contains(&chapter.chapter_number))
+                    && chapter_list.as_ref().is_none_or(|list| list.iter().any(|listed| listed.contains(&chapter.chapter_number)))
+            });
+            info!("After --min-chapter/--max-chapter/--chapter-list-file: {} chapters", flattened_chapters.len());
+            if flattened_chapters.is_empty() {
+                return Err(anyhow::anyhow!("No chapters selected by --min-chapter/--max-chapter/--chapter-list-file, the book has {} chapters.", total).into());
+            }
+        }
+        
+        // Process chapters based on merging preference
+        let min_duration_ms = (options.min_chapter_duration.unwrap_or(0.0) * 1000.0) as i64; // Convert seconds to milliseconds
+        let mut processed_chapters = if options.merge_on_silence {
+            // Silence at the boundaries decides instead of the chapter durations
+            let silent_boundaries = detect_silent_boundaries(&aaxc_file_path, &input_format, &flattened_chapters)?;
+            let merged_chapters = merge_on_silence(&flattened_chapters, &silent_boundaries);
+            info!("After merging chapters on silence: {} chapters", merged_chapters.len());
+            merged_chapters
+        } else if options.merge_short_chapters {
+            // Merge runs of short chapters into a neighbouring chapter
+            let merged_chapters = merge_short_chapters(&flattened_chapters, min_duration_ms, options.merge_direction);
+            info!("After merging short chapters (min duration: {}s): {} chapters", 
+                  min_duration_ms as f64 / 1000.0, merged_chapters.len());
+            merged_chapters
+        } else {
+            // Filter chapters based on minimum duration
+            let filtered_chapters: Vec<&FlattenedChapter> = flattened_chapters
+                .iter()
+                .filter(|chapter| chapter.should_include(min_duration_ms))
+                .collect();
+            
+            info!("After filtering (min duration: {}s): {} chapters", 
+                  min_duration_ms as f64 / 1000.0, filtered_chapters.len());
+            
+            if filtered_chapters.is_empty() {
+                return Err(anyhow::anyhow!("No chapters found after filtering. Try reducing --min-chapter-duration or check your chapter data.").into());
+            }
+            
+            // Warn about filtered chapters and potential time gaps
+            let filtered_count = flattened_chapters.len() - filtered_chapters.len();
+            if filtered_count > 0 {
+                warn!("{} chapters were filtered out due to minimum duration requirement. This may create gaps in the audio timeline.", filtered_count);
+                warn!("Consider using --merge-short-chapters to merge them with the next chapter instead.");
+            }
+            
+            // Convert to MergedChapter for consistency
+            filtered_chapters.into_iter().map(MergedChapter::from_flattened).collect()
+        };
+        
+        if processed_chapters.is_empty() {
+            return Err(anyhow::anyhow!("No chapters found after processing. Try reducing --min-chapter-duration or check your chapter data.").into());
+        }
+
+        resolve_overlaps(&mut processed_chapters, options.overlap_strategy)?;
+
+        // Titles are transformed up front so names, logs, and progress agree,
+        // unless the transformation is meant for file names only
+        let (title_case, filename_title_case) = if options.title_case_output_only {
+            (TitleCase::Preserve, options.title_case)
+        } else {
+            (options.title_case, TitleCase::Preserve)
+        };
+        for chapter in &mut processed_chapters {
+            if let Some(pattern) = &options.strip_title_prefix {
+                chapter.title = strip_title_prefix(&chapter.title, pattern);
+            }
+            chapter.title = title_case.apply(&chapter.title);
+        }
+        
+        if options.chapter_gap_fill {
+            let audio_end_ms = (parse_duration_to_seconds(&duration) * 1000.0).round() as i64 - brand_outro_ms;
+            fill_chapter_gaps(&mut processed_chapters, audio_end_ms);
+        }
+
+        // Found before --test-chapter narrows the list, on the titles used in file names
+        let filename_chapters: Vec<MergedChapter> = processed_chapters
+            .iter()
+            .map(|chapter| MergedChapter { title: filename_title_case.apply(&chapter.title), ..chapter.clone() })
+            .collect();
+        let duplicate_titles = DuplicateTitles::new(&filename_chapters, options.title_disambiguation());
+
+        if let Some(test_chapter) = options.test_chapter {
+            let total_chapters = processed_chapters.len();
+            let index = test_chapter as usize - 1;
+            if index >= total_chapters {
+                return Err(anyhow::anyhow!(
+                    "--test-chapter {} is out of range. The book has {} chapters after processing.",
+                    test_chapter,
+                    total_chapters
+                ).into());
+            }
+            processed_chapters = vec![processed_chapters.swap_remove(index)];
+            print_test_run_banner(&processed_chapters[0], test_chapter as usize, total_chapters);
+        }
+
+        let naming_format = match (&options.chapter_naming_format, &options.chapter_prefix) {
+            (ChapterNamingFormat::ChapterNumberTitle { .. }, Some(prefix)) => ChapterNamingFormat::ChapterNumberTitle {
+                prefix: sanitize_title(prefix, &SanitizeConfig::default()),
+            },
+            (format, Some(_)) => {
+                warn!("--chapter-prefix only applies to the chapter-number-title naming format, ignoring it");
+                format.clone()
+            }
+            (format, None) => format.clone(),
+        };
+
+        let output_base_path = if options.asin_dirs {
+            // Embedded chapter markers carry no ASIN, fall back to the voucher's
+            let asin = chapters_asin.filter(|asin| !asin.is_empty()).unwrap_or_else(|| book.asin.clone());
+            asin_dir(output_path.as_deref(), &asin)?
+        } else if let Some(output_path) = &output_path {
+            output_path.clone()
+        } else {
+            PathBuf::from(".")
+        };
+        let output_base_path = if options.album_dir {
+            album_dir(&output_base_path, &album)?
+        } else {
+            output_base_path
+        };
+
+        let unique_filenames = check_unique_filenames(&processed_chapters, |chapter| chapter_output_path(
+            chapter,
+            &naming_format,
+            filename_title_case,
+            &options.split_structure,
+            &output_base_path,
+            ext,
+            &duplicate_titles,
+        ));
+        if let Err(conflicts) = unique_filenames {
+            if !options.allow_duplicate_filenames {
+                return Err(anyhow::anyhow!(
+                    "Several chapters would be written to the same file:\n  - {}\nUse --title-disambiguation or a naming format with the chapter number to tell them apart, or --allow-duplicate-filenames to convert anyway.",
+                    conflicts.join("\n  - ")
+                ).into());
+            }
+            for conflict in &conflicts {
+                warn!("Chapters would overwrite each other: {}", conflict);
+            }
+        }
+
+        // Written after the conversion, or on its own with --metadata-only
+        let write_playlist = || -> Result<()> {
+            let Some(playlist_path) = &options.playlist else {
+                return Ok(());
+            };
+            // Extended by default for .m3u8 playlists
+            let extended = options.m3u_extended.unwrap_or_else(|| {
+                playlist_path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("m3u8"))
+            });
+            let chapter_paths: Vec<PathBuf> = processed_chapters
+                .iter()
+                .map(|chapter| chapter_output_path(
+                    chapter,
+                    &naming_format,
+                    filename_title_case,
+                    &options.split_structure,
+                    &output_base_path,
+                    ext,
+                    &duplicate_titles,
+                ))
+                .collect();
+            // A bare file name goes next to the chapters in the album directory
+            let playlist_path = if options.album_dir && playlist_path.parent().is_some_and(|parent| parent.as_os_str().is_empty()) {
+                &output_base_path.join(playlist_path)
+            } else {
+                playlist_path
+            };
+            write_m3u_playlist(playlist_path, &processed_chapters, &chapter_paths, &tags, extended)?;
+            info!("Playlist written to {}", playlist_path.display());
+            Ok(())
+        };
+
+        if options.metadata_only {
+            info!("Metadata-only mode: skipping audio extraction");
+            plan_chapters(
+                &processed_chapters,
+                &naming_format,
+                filename_title_case,
+                &options.split_structure,
+                &output_base_path,
+                ext,
+                &duplicate_titles,
+                options.verify,
+                options.timestamps,
+                options.machine_readable,
+            )?;
+            write_playlist()?;
+            if !options.machine_readable {
+                println!("{}", book.chapter_stats);
+            }
+            return Ok(());
+        }
+
+        if options.conditional && !options.benchmark {
+            let first_chapter_path = chapter_output_path(
+                &processed_chapters[0],
+                &naming_format,
+                filename_title_case,
+                &options.split_structure,
+                &output_base_path,
+                ext,
+                &duplicate_titles,
+            );
+            if output_is_up_to_date(&aaxc_file_path, &first_chapter_path) {
+                info!(
+                    "Skipping conversion: {} is newer than {}",
+                    first_chapter_path.display(),
+                    aaxc_file_path.display()
+                );
+                return Ok(());
+            }
+        }
+
+        // --benchmark writes to the null device, so there are no files to warn about
+        if processed_chapters.len() > options.warn_chapter_count && !options.benchmark {
+            let total_audio_ms: i64 = processed_chapters.iter().map(|chapter| chapter.length_ms).sum();
+            let estimated_size = output_format
+                .estimated_bitrate_bps(book.sample_rate_hz, book.channels)
+                .map(|bitrate| estimated_output_bytes(bitrate, total_audio_ms));
+            let interactive = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+            if options.yes || options.machine_readable || !interactive {
+                info!("Writing {} chapter files, more than --warn-chapter-count {}", processed_chapters.len(), options.warn_chapter_count);
+            } else if !confirm_large_split(&large_split_prompt(processed_chapters.len(), total_audio_ms, estimated_size, options.max_speed))? {
+                return Err(anyhow::anyhow!("Split cancelled, no chapter files were written.").into());
+            }
+        }
+
+        // Convert chapters to individual files
+        info!("Starting chapter splitting conversion");
+        convert_chapters(
+            &aaxc_file_path,
+            &input_format,
+            &processed_chapters,
+            &naming_format,
+            filename_title_case,
+            &options.split_structure,
+            &output_base_path,
+            ext,
+            &duplicate_titles,
+            codec,
+            options.verbose_progress,
+            options.machine_readable,
+            options.progress_batch as usize,
+            &options.threads,
+            options.ffmpeg_log_level,
+            options.disc_tags,
+            options.relative_paths,
+            options.retry,
+            !options.no_ffmpeg_metadata,
+            &ConversionFlags::from_options(&options),
+            options.keep_temp_files,
+            &book,
+        )?;
+
+        write_playlist()?;
+        
+        info!("Chapter splitting completed successfully");
+        return Ok(());
+    }
+
+    info!("Title: {}", title);
+    info!("Output file name: {}", file_name);
+
+    if options.conditional && !options.benchmark && output_is_up_to_date(&aaxc_file_path, Path::new(&file_name)) {
+        info!("Skipping conversion: {} is newer than {}", file_name, aaxc_file_path.display());
+        return Ok(());
+    }
+
+    // Handle machine-readable mode for single file conversion
+    if options.machine_readable {
+        let output_path = if options.relative_paths {
+            // Relative to the --output-path directory, or the directory of the output file
+            let base = match &output_path {
+                Some(path) if path.is_dir() => path.clone(),
+                Some(path) => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+                None => PathBuf::from("."),
+            };
+            relative_path(Path::new(&file_name), &base).to_string_lossy().to_string()
+        } else {
+            file_name.clone()
+        };
+        let event = ProgressEvent::ConversionStarted {
+            total_chapters: 1,
+            output_format: ext.to_string(),
+            output_path,
+            asin: book.asin.clone(),
+            book_title: book.title.clone(),
+            artist: book.artist.clone(),
+            audio_codec: book.audio_codec.clone(),
+            sample_rate_hz: book.sample_rate_hz,
+            channels: book.channels,
+            source_bitrate_bps: book.source_bitrate_bps,
+            chapter_count: book.chapter_stats.chapter_count,
+            total_audio_duration_ms: book.chapter_stats.total_duration_ms,
+            max_chapter_depth: book.chapter_stats.max_depth,
+            shortest_chapter_ms: book.chapter_stats.shortest_ms(),
+            longest_chapter_ms: book.chapter_stats.longest_ms(),
+        };
+        println!("{}", event.to_json());
+    }
+
+    // Chapter markers are handed to ffmpeg as a second, metadata-only input
+    let chapters_metadata = if options.embed_chapters {
+        let (chapters, _, _) = load_chapters(&options, &aaxc_file_path, chapters_path)?;
+        info!("Embedding {} chapter markers", chapters.len());
+        let metadata_file = TempFileGuard::new(chapters_metadata_path(Path::new(&file_name)), options.keep_temp_files);
+        std::fs::write(metadata_file.path(), ffmetadata_chapters(&chapters))
+            .with_context(|| format!("Failed to write chapter metadata file: {}", metadata_file.path().display()))?;
+        Some(metadata_file)
+    } else {
+        None
+    };
+
+    info!("Starting ffmpeg conversion");
+    let started = Instant::now();
+    let total_audio_ms = (parse_duration_to_seconds(&duration) * 1000.0).round() as i64;
+    let temp_file = TempFileGuard::new(temp_output_path(Path::new(&file_name)), options.keep_temp_files);
+    let (mut cmd, progress) = ffmpeg(
+        aaxc_file_path,
+        &input_format,
+        duration,
+        temp_file.path().to_string_lossy().to_string(),
+        chapters_metadata.as_ref().map(TempFileGuard::path),
+        codec,
+        options.verbose_progress,
+        options.machine_readable,
+        options.progress_batch as usize,
+        &options.threads,
+        options.ffmpeg_log_level,
+        !options.no_ffmpeg_metadata,
+        &ConversionFlags::from_options(&options),
+    )
+    .with_context(|| {
+        "Failed to start ffmpeg. Please ensure ffmpeg is installed and available in your PATH."
+    })?;
+
+    let status = cmd.wait()
+        .with_context(|| "ffmpeg process failed to complete. Please check your input files and try again.")?;
+
+    if status.success() {
+        if !options.benchmark {
+            temp_file.persist(Path::new(&file_name))
+                .with_context(|| format!("Failed to move the converted file into place: {}", file_name))?;
+        }
+        info!("Conversion speed: average {:.1}x, peak {:.1}x", progress.average_speed, progress.peak_speed);
+        if options.machine_readable {
+            let event = ProgressEvent::ConversionCompleted {
+                total_chapters: 1,
+                total_duration_seconds: 0.0, // Will be calculated if needed
+                success: true,
+                peak_speed: progress.peak_speed,
+                average_speed: progress.average_speed,
+                total_output_bytes: 0,
+            };
+            println!("{}", event.to_json());
+        }
+        if options.benchmark {
+            BenchmarkResult::new(1, total_audio_ms, &progress, started.elapsed()).print(options.machine_readable);
+        }
+        info!("ffmpeg conversion completed successfully");
+    } else {
+        if options.machine_readable {
+            let event = ProgressEvent::Error {
+                message: "ffmpeg conversion failed".to_string(),
+                chapter_number: Some(1),
+            };
+            println!("{}", event.to_json());
+        }
+        error!("ffmpeg conversion failed with status: {:?}", status);
+        return Err(AudibleUtilError::FfmpegFailed { exit_code: status.code().unwrap_or(-1) });
+    }
+
+    Ok(())
+}
+
+/// Parse `chapters_path`, or the chapters file found next to the input, or read the embedded
+/// chapter markers with --chapters-from-ffprobe. Returns the flattened chapters, the brand outro
+/// length and the ASIN from the chapters file.
+fn load_chapters(
+    options: &ConvertOptions,
+    aaxc_file_path: &Path,
+    chapters_path: Option<&Path>,
+) -> Result<(Vec<FlattenedChapter>, i64, Option<String>), AudibleUtilError> {
+    // Determine chapter file path (similar to voucher file inference)
+    let chapter_file_path = if let Some(chapters_path) = chapters_path {
+        chapters_path.to_path_buf()
+    } else {
+        let aaxc_file_path_stem = aaxc_file_path
+            .file_stem()
+            .context("Could not get file stem from the input file path for chapter file inference.")?;
+        
+        // Try multiple naming patterns for chapter files
+        let base_name = aaxc_file_path_stem
+            .to_str()
+            .context("Failed to convert file stem to string for chapter file inference.")?;
+        
+        // Remove AAX suffix if present (e.g., "Book-AAX_44_128" -> "Book")
+        let clean_name = if base_name.contains("-AAX_") {
+            base_name.split("-AAX_").next().unwrap_or(base_name)
+        } else {
+            base_name
+        };
+        
+        find_chapter_file(aaxc_file_path, &format!("{}-chapters.json", clean_name), options.chapter_search_parents)
+    };
+    
+    info!("Looking for chapter file: {}", chapter_file_path.display());
+    
+    // Embedded chapter markers carry no brand outro duration
+    let chapters = if !chapter_file_path.exists() && options.chapters_from_ffprobe {
+        info!(
+            "Chapter file {} not found, falling back to embedded chapter markers",
+            chapter_file_path.display()
+        );
+        let ffprobe_output = ffprobe_chapters(aaxc_file_path)
+            .with_context(|| format!(
+                "Failed to read embedded chapters from input file: {}.",
+                aaxc_file_path.display()
+            ))?;
+        let embedded_chapters = parse_ffprobe_chapters(&ffprobe_output)?;
+        if embedded_chapters.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No embedded chapter markers found in {}. Please provide a chapters.json file or disable --split.",
+                aaxc_file_path.display()
+            ).into());
+        }
+        (embedded_chapters, 0, None)
+    } else {
+        // Check if chapter file exists
+        if !chapter_file_path.exists() {
+            return Err(AudibleUtilError::ChapterFileNotFound(chapter_file_path));
+        }
+    
+        if !chapter_file_path.is_file() {
+            return Err(anyhow::anyhow!(
+                "Chapter path is not a file: {}. Please provide a valid chapters.json file.",
+                chapter_file_path.display()
+            ).into());
+        }
+    
+        if std::fs::File::open(&chapter_file_path).is_err() {
+            return Err(anyhow::anyhow!(
+                "Chapter file is not readable: {}. Please check file permissions.",
+                chapter_file_path.display()
+            ).into());
+        }
+    
+        // Parse chapter file
+        info!("Parsing chapter file: {}", chapter_file_path.display());
+        let chapter_bytes = std::fs::read(&chapter_file_path)
+            .with_context(|| format!(
+                "Failed to open chapter file: {}. Please ensure the file exists and is readable.",
+                chapter_file_path.display()
+            ))?;
+        let chapter_json = decode_chapter_file(&chapter_bytes, options.chapter_encoding)
+            .with_context(|| format!(
+                "Failed to decode chapter file: {}. Try a different --chapter-encoding.",
+                chapter_file_path.display()
+            ))?;
+    
+        // Check the document's structure before serde fills in defaults
+        validate_chapter_json_schema(&chapter_json)
+            .map_err(AudibleUtilError::ChapterValidationError)?;
+    
+        let chapters: AudibleChapters = if options.strict_json {
+            serde_json::from_str::<StrictAudibleChapters>(&chapter_json)
+                .map(AudibleChapters::from)
+                .map_err(|e| anyhow::anyhow!(
+                    "Chapter file {} does not match the expected schema (--strict-json): {}",
+                    chapter_file_path.display(),
+                    e
+                ))?
+        } else {
+            serde_json::from_str(&chapter_json)
+                .with_context(|| format!(
+                    "Failed to parse chapter file: {}. Please ensure it is a valid JSON file.",
+                    chapter_file_path.display()
+                ))?
+        };
+    
+        info!("Chapter file parsed successfully");
+        info!("Response groups: {:?}", chapters.response_groups);
+        info!("Chapter count: {}", chapters.content_metadata.chapter_info.chapters.len());
+    
+        let chapter_warnings = chapters.validate()
+            .map_err(AudibleUtilError::ChapterValidationError)?;
+        for warning in &chapter_warnings {
+            warn!("Chapter data: {}", warning);
+        }
+        info!("Chapter data validated successfully");
+    
+        // Flatten chapters with a single global counter, or one per part
+        (
+            chapters.content_metadata.chapter_info.flatten(options.reset_chapter_numbers_per_part),
+            chapters.content_metadata.chapter_info.brand_outro_duration_ms,
+            Some(chapters.content_metadata.content_reference.asin.trim().to_string()),
+        )
+    };
+    let (mut flattened_chapters, brand_outro_ms, chapters_asin) = chapters;
+    if options.chapter_time_offset != 0 {
+        info!("Moving all chapters by {} ms (--chapter-time-offset)", options.chapter_time_offset);
+        offset_chapters(&mut flattened_chapters, options.chapter_time_offset);
+    }
+    Ok((flattened_chapters, brand_outro_ms, chapters_asin))
+}
+
+/// `file_name` next to the input file, or in the closest of up to `parents` directories above it.
+/// Falls back to the path next to the input file when none has it, for the not-found error.
+fn find_chapter_file(aaxc_file_path: &Path, file_name: &str, parents: usize) -> PathBuf {
+    let next_to_input = aaxc_file_path.with_file_name(file_name);
+    if parents == 0 || next_to_input.exists() {
+        return next_to_input;
+    }
+    // A relative input path has no parents to walk up to
+    let Ok(aaxc_file_path) = std::path::absolute(aaxc_file_path) else {
+        return next_to_input;
+    };
+    aaxc_file_path
+        .ancestors()
+        .skip(1)
+        .take(parents + 1)
+        .map(|dir| dir.join(file_name))
+        .find(|path| path.exists())
+        .unwrap_or(next_to_input)
+}
+
+/// Convert in a pipeline, reading the book from stdin (`--aaxc_path -`) and/or writing
+/// the audio to stdout (`--output-path -`). Nothing is probed, so the duration comes from --duration.
+fn run_pipeline(options: &ConvertOptions, aaxc_file_path: &Path, input_format: &InputFormat, book: &BookInfo) -> Result<()> {
+    if options.splits() {
+        anyhow::bail!("--split is not supported when reading from stdin or writing to stdout.");
+    }
+    if options.embed_chapters {
+        anyhow::bail!("--embed-chapters is not supported when reading from stdin or writing to stdout.");
+    }
+    if options.benchmark {
+        anyhow::bail!("--benchmark is not supported when reading from stdin or writing to stdout.");
+    }
+
+    use crate::cli::OutputFormat;
+    let output_format: Box<dyn OutputFormat> = options.output_format();
+    let input = if aaxc_file_path == Path::new("-") {
+        PathBuf::from("pipe:0")
+    } else {
+        aaxc_file_path.to_path_buf()
+    };
+    let duration = options.duration.unwrap_or(0.0);
+
+    if options.output_path.as_deref() == Some(Path::new("-")) {
+        // stdout carries the audio, so events go to stderr
+        if options.machine_readable {
+            let event = ProgressEvent::ConversionStarted {
+                total_chapters: 1,
+                output_format: output_format.extension().to_string(),
+                output_path: "-".to_string(),
+                asin: book.asin.clone(),
+                book_title: String::new(),
+                artist: String::new(),
+                audio_codec: None,
+                sample_rate_hz: None,
+                channels: None,
+                source_bitrate_bps: None,
+                chapter_count: book.chapter_stats.chapter_count,
+                total_audio_duration_ms: book.chapter_stats.total_duration_ms,
+                max_chapter_depth: book.chapter_stats.max_depth,
+                shortest_chapter_ms: book.chapter_stats.shortest_ms(),
+                longest_chapter_ms: book.chapter_stats.longest_ms(),
+            };
+            eprintln!("{}", event.to_json());
+        }
+
+        info!("Starting ffmpeg conversion to stdout");
+        let mut cmd = ffmpeg_to_stdout(
+            &input,
+            input_format,
+            output_format.as_ref(),
+            &options.threads,
+            options.ffmpeg_log_level,
+            !options.no_ffmpeg_metadata,
+            &ConversionFlags::from_options(options),
+        )?;
+        let status = cmd.wait()
+            .with_context(|| "ffmpeg process failed to complete. Please check your input and try again.")?;
+
+        if options.machine_readable {
+            let event = if status.success() {
+                ProgressEvent::ConversionCompleted {
+                    total_chapters: 1,
+                    total_duration_seconds: duration,
+                    success: true,
+                    peak_speed: 0.0,
+                    average_speed: 0.0,
+                    total_output_bytes: 0,
+                }
+            } else {
+                ProgressEvent::Error {
+                    message: "ffmpeg conversion failed".to_string(),
+                    chapter_number: Some(1),
+                }
+            };
+            eprintln!("{}", event.to_json());
+        }
+        if !status.success() {
+            return Err(AudibleUtilError::FfmpegFailed { exit_code: status.code().unwrap_or(-1) }.into());
+        }
+        info!("ffmpeg conversion completed successfully");
+        return Ok(());
+    }
+
+    // Reading from stdin into a file: without ffprobe there's no album name to name the file after
+    let output_path = options.output_path.as_ref()
+        .context("--output-path is required when reading from stdin.")?;
+    let file_name = if output_path.is_dir() {
+        output_path.join(format!("audiobook.{}", output_format.extension()))
+    } else {
+        output_path.clone()
+    };
+    info!("Output file name: {}", file_name.display());
+
+    if options.machine_readable {
+        let event = ProgressEvent::ConversionStarted {
+            total_chapters: 1,
+            output_format: output_format.extension().to_string(),
+            output_path: file_name.to_string_lossy().to_string(),
+            asin: book.asin.clone(),
+            book_title: String::new(),
+            artist: String::new(),
+            audio_codec: None,
+            sample_rate_hz: None,
+            channels: None,
+            source_bitrate_bps: None,
+            chapter_count: book.chapter_stats.chapter_count,
+            total_audio_duration_ms: book.chapter_stats.total_duration_ms,
+            max_chapter_depth: book.chapter_stats.max_depth,
+            shortest_chapter_ms: book.chapter_stats.shortest_ms(),
+            longest_chapter_ms: book.chapter_stats.longest_ms(),
+        };
+        println!("{}", event.to_json());
+    }
+
+    info!("Starting ffmpeg conversion");
+    let temp_file = TempFileGuard::new(temp_output_path(&file_name), options.keep_temp_files);
+    let (mut cmd, progress) = ffmpeg(
+        input,
+        input_format,
+        duration.to_string(),
+        temp_file.path().to_string_lossy().to_string(),
+        None,
+        output_format.codec(),
+        options.verbose_progress,
+        options.machine_readable,
+        options.progress_batch as usize,
+        &options.threads,
+        options.ffmpeg_log_level,
+        !options.no_ffmpeg_metadata,
+        &ConversionFlags::from_options(options),
+    )?;
+    let status = cmd.wait()
+        .with_context(|| "ffmpeg process failed to complete. Please check your input and try again.")?;
+    if status.success() {
+        temp_file.persist(&file_name)
+            .with_context(|| format!("Failed to move the converted file into place: {}", file_name.display()))?;
+    }
+
+    if options.machine_readable {
+        let event = if status.success() {
+            ProgressEvent::ConversionCompleted {
+                total_chapters: 1,
+                total_duration_seconds: duration,
+                success: true,
+                peak_speed: progress.peak_speed,
+                average_speed: progress.average_speed,
+                total_output_bytes: 0,
+            }
+        } else {
+            ProgressEvent::Error {
+                message: "ffmpeg conversion failed".to_string(),
+                chapter_number: Some(1),
+            }
+        };
+        println!("{}", event.to_json());
+    }
+    if !status.success() {
+        return Err(AudibleUtilError::FfmpegFailed { exit_code: status.code().unwrap_or(-1) }.into());
+    }
+    info!("ffmpeg conversion completed successfully");
+    Ok(())
+}
+
+/// The key and IV given with --key/--iv or read with --key-from-cmd/--iv-from-cmd, None when
+/// they come from a voucher
+fn command_line_key(options: &ConvertOptions) -> Result<Option<(String, String)>> {
+    let key = match (&options.key, &options.key_from_cmd) {
+        (Some(key), _) => key.clone(),
+        (None, Some(command)) => read_secret_from_command(command, "key")?,
+        (None, None) => return Ok(None),
+    };
+    let iv = match (&options.iv, &options.iv_from_cmd) {
+        (Some(iv), _) => iv.clone(),
+        (None, Some(command)) => read_secret_from_command(command, "IV")?,
+        (None, None) => return Ok(None),
+    };
+
+    // Other processes of the same user can read the environment
+    for (name, value) in std::env::vars() {
+        if value.trim().eq_ignore_ascii_case(&key) || value.trim().eq_ignore_ascii_case(&iv) {
+            warn!("The decryption key or IV is stored in the environment variable {}, where other processes can read it.", name);
+        }
+    }
+    Ok(Some((key, iv)))
+}
+
+/// Run `command` with `sh -c` and return its trimmed output, which has to be hex
+fn read_secret_from_command(command: &str, what: &str) -> Result<String> {
+    info!("Reading the decryption {} from a command", what);
+    let output = Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("Failed to run the command for the decryption {}.", what))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "The command for the decryption {} failed with {}.",
+            what,
+            output.status.code().map_or("a signal".to_string(), |code| format!("exit code {}", code))
+        );
+    }
+
+    let secret = String::from_utf8(output.stdout)
+        .with_context(|| format!("The command for the decryption {} printed invalid UTF-8.", what))?
+        .trim()
+        .to_string();
+    // The output is deliberately left out of the error, it may be a mistyped secret
+    if secret.is_empty() || !secret.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("The command for the decryption {} did not print a hex string.", what);
+    }
+    Ok(secret)
+}
+
+/// Read, validate, and check the expiry of an audible-cli voucher file
+fn load_voucher(voucher_file_path: &Path) -> Result<models::AudibleCliVoucher> {
+    // Use serde to deserialize voucher file into `AudibleCliVoucher`
+    info!("Opening voucher file: {}", voucher_file_path.display());
+    let voucher_file = std::fs::File::open(voucher_file_path)
+        .with_context(|| format!(
+            "Failed to open voucher file: {}. Please ensure the file exists and is readable.",
+            voucher_file_path.display()
+        ))?;
+    info!("Parsing voucher file");
+    let voucher: models::AudibleCliVoucher = serde_json::from_reader(voucher_file)
+        .map_err(|source| AudibleUtilError::VoucherParseError { path: voucher_file_path.to_path_buf(), source })?;
+    let voucher_warnings = voucher.validate()
+        .map_err(AudibleUtilError::VoucherValidationError)?;
+    for warning in &voucher_warnings {
+        warn!("Voucher: {}", warning);
+    }
+    info!("Voucher validated successfully");
+
+    // An expired license doesn't stop local decryption, so this only warns
+    if let Some(expiry) = voucher.license_expiry() {
+        let remaining = expiry - chrono::Utc::now();
+        if remaining <= chrono::TimeDelta::zero() {
+            warn!("The voucher license expired on {}. Decryption usually still works, but the voucher can't be renewed.", expiry.format("%Y-%m-%d %H:%M UTC"));
+        } else if remaining <= chrono::TimeDelta::days(7) {
+            warn!("The voucher license expires on {}, in {} days.", expiry.format("%Y-%m-%d %H:%M UTC"), remaining.num_days());
+        } else {
+            info!("Voucher license expires on {}", expiry.format("%Y-%m-%d %H:%M UTC"));
+        }
+    }
+
+    Ok(voucher)
+}
+
+/// Read the chapter markers embedded in the input file as ffprobe JSON
+fn ffprobe_chapters(aaxc_file_path: &Path) -> Result<String> {
+    let ffprobe_cmd = Command::new("ffprobe")
+        .args([
+            "-i",
+            aaxc_file_path
+                .to_str()
+                .context("Failed to convert input file path to string.")?,
+            "-print_format",
+            "json",
+            "-show_chapters",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .with_context(|| "Failed to execute ffprobe. Is ffprobe installed and available in your PATH?")?;
+
+    if !ffprobe_cmd.status.success() {
+        let stderr = String::from_utf8_lossy(&ffprobe_cmd.stderr);
+        anyhow::bail!(
+            "ffprobe failed with error:\n{}\nPlease ensure the input file is a valid Audible AAXC file.",
+            stderr
+        );
+    }
+
+    String::from_utf8(ffprobe_cmd.stdout).context("Failed to parse ffprobe output as UTF-8.")
+}
+
+/// How far the end of the last chapter may be from the book's runtime without a warning
+const RUNTIME_TOLERANCE_MS: i64 = 1_000;
+
+/// Check a chapters file and print the report, what `audible-util validate-chapters` does.
+/// Warnings are allowed, errors and unparsable files fail with their own exit code.
+pub fn validate_chapters(path: &Path, encoding: ChapterEncoding, machine_readable: bool) -> Result<(), AudibleUtilError> {
+    let report = validate_chapter_file(path, encoding);
+    report.print(machine_readable);
+    match (report.parsed, report.valid) {
+        (false, _) => Err(AudibleUtilError::ChapterReportUnparsable(path.to_path_buf())),
+        (true, false) => Err(AudibleUtilError::ChapterReportInvalid(path.to_path_buf())),
+        (true, true) => Ok(()),
+    }
+}
+
+/// Check a chapters file on its own for `validate-chapters`: its schema and chapter data, gaps
+/// and overlaps between chapters, and whether the chapters fit the book's runtime
+fn validate_chapter_file(path: &Path, encoding: ChapterEncoding) -> ValidationReport {
+    let mut report = ValidationReport { path: path.display().to_string(), ..Default::default() };
+    let chapters = match parse_chapter_file(path, encoding) {
+        Ok(chapters) => chapters,
+        Err(errors) => {
+            report.errors = errors;
+            return report;
+        }
+    };
+    report.parsed = true;
+
+    match chapters.validate() {
+        Ok(warnings) => report.warnings = warnings,
+        Err(errors) => report.errors = errors,
+    }
+    let info = &chapters.content_metadata.chapter_info;
+    let flattened = info.flatten(false);
+    report.chapter_count = flattened.len();
+    report.total_duration_ms = flattened.iter().map(|chapter| chapter.length_ms.max(0)).sum();
+    report.runtime_length_ms = info.runtime_length_ms;
+    let (errors, warnings) = chapter_timeline_issues(&flattened, info.runtime_length_ms);
+    report.errors.extend(errors);
+    report.warnings.extend(warnings);
+    if (info.runtime_length_sec - info.runtime_length_ms / 1000).abs() > 1 {
+        report.warnings.push(format!(
+            "runtime_length_sec {} doesn't match runtime_length_ms {}",
+            info.runtime_length_sec, info.runtime_length_ms
+        ));
+    }
+    report.valid = report.errors.is_empty();
+    report
+}
+
+/// Read, decode and parse a chapters file, or every reason it couldn't be
+fn parse_chapter_file(path: &Path, encoding: ChapterEncoding) -> Result<AudibleChapters, Vec<String>> {
+    let bytes = std::fs::read(path).map_err(|e| vec![format!("Failed to read the file: {}", e)])?;
+    let json = decode_chapter_file(&bytes, encoding).map_err(|e| vec![format!("{:#}", e)])?;
+    validate_chapter_json_schema(&json)?;
+    serde_json::from_str(&json).map_err(|e| vec![format!("Failed to parse the file: {}", e)])
+}
+
+/// Overlaps between consecutive chapters and chapters running past `runtime_length_ms` as errors,
+/// gaps and chapters ending well before the runtime as warnings
+fn chapter_timeline_issues(chapters: &[FlattenedChapter], runtime_length_ms: i64) -> (Vec<String>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    if let Some(first) = chapters.first().filter(|first| first.start_offset_ms > 0) {
+        warnings.push(format!("\"{}\" starts at {}, the audio before it is in no chapter", first, format_time_from_ms(first.start_offset_ms)));
+    }
+    for pair in chapters.windows(2) {
+        let gap_ms = pair[1].start_offset_ms - (pair[0].start_offset_ms + pair[0].length_ms);
+        if gap_ms > 0 {
+            warnings.push(format!("Gap of {} ms between \"{}\" and \"{}\"", gap_ms, pair[0], pair[1]));
+        } else if gap_ms < 0 {
+            errors.push(format!("\"{}\" overlaps \"{}\" by {} ms", pair[0], pair[1], -gap_ms));
+        }
+    }
+    if let Some(end_ms) = chapters.iter().map(|chapter| chapter.start_offset_ms + chapter.length_ms).max() {
+        if end_ms > runtime_length_ms {
+            errors.push(format!(
+                "Chapters end at {}, after the book's runtime of {}",
+                format_time_from_ms(end_ms),
+                format_time_from_ms(runtime_length_ms)
+            ));
+        } else if runtime_length_ms - end_ms > RUNTIME_TOLERANCE_MS {
+            warnings.push(format!(
+                "Chapters end at {}, {} ms before the book's runtime of {}",
+                format_time_from_ms(end_ms),
+                runtime_length_ms - end_ms,
+                format_time_from_ms(runtime_length_ms)
+            ));
+        }
+    }
+    (errors, warnings)
+}
+
+/// Write the chapter markers embedded in `aaxc_file_path` as an audible-cli chapters file
+pub fn export_chapters(aaxc_file_path: &Path, voucher_path: Option<&Path>, output: Option<&Path>, force: bool) -> Result<()> {
+    if !aaxc_file_path.is_file() {
+        anyhow::bail!("Input file does not exist: {}. Please provide a valid .aaxc file.", aaxc_file_path.display());
+    }
+    let stem = aaxc_file_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .context("Could not get file stem from the input file path.")?;
+    let output_path = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| aaxc_file_path.with_file_name(format!("{}-chapters.json", stem)));
+    if output_path.exists() && !force {
+        anyhow::bail!(
+            "Chapters file already exists: {}. Use --force to overwrite it.",
+            output_path.display()
+        );
+    }
+    check_external_tool("ffprobe")?;
+
+    // The content reference only identifies the book, so a missing voucher isn't fatal
+    let voucher_path = voucher_path
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| aaxc_file_path.with_file_name(format!("{}.voucher", stem)));
+    let content_reference = if voucher_path.is_file() {
+        let voucher = load_voucher(&voucher_path)?;
+        (&voucher.content_license.content_metadata.content_reference).into()
+    } else {
+        warn!(
+            "No voucher found at {}, the content reference in the chapters file will be empty",
+            voucher_path.display()
+        );
+        Default::default()
+    };
+
+    let ffprobe_output = ffprobe_chapters(aaxc_file_path)?;
+    let ffprobe_chapters: FFProbeChapters = serde_json::from_str(&ffprobe_output)
+        .context("Failed to parse ffprobe chapter output as JSON.")?;
+    if ffprobe_chapters.chapters.is_empty() {
+        anyhow::bail!("No embedded chapter markers found in {}.", aaxc_file_path.display());
+    }
+    let chapter_count = ffprobe_chapters.chapters.len();
+    let chapters = models::ffprobe_chapters_to_audible_chapters(ffprobe_chapters.chapters, &content_reference)
+        .map_err(|e| anyhow::anyhow!("Invalid embedded chapter marker in {}: {}", aaxc_file_path.display(), e))?;
+
+    let json = serde_json::to_string_pretty(&chapters).context("Failed to serialize chapters.")?;
+    std::fs::write(&output_path, json)
+        .with_context(|| format!("Failed to write chapters file: {}", output_path.display()))?;
+    info!("Exported {} chapters to {}", chapter_count, output_path.display());
+    Ok(())
+}
+
+/// Convert ffprobe's chapter list into flattened chapters with millisecond timestamps, read the
+/// same way as for export-chapters
+fn parse_ffprobe_chapters(ffprobe_output: &str) -> Result<Vec<FlattenedChapter>> {
+    let ffprobe_chapters: FFProbeChapters = serde_json::from_str(ffprobe_output)
+        .context("Failed to parse ffprobe chapter output as JSON.")?;
+
+    let mut chapters = Vec::with_capacity(ffprobe_chapters.chapters.len());
+    for (index, chapter) in ffprobe_chapters.chapters.iter().enumerate() {
+        let chapter_number = index + 1;
+        let (start_offset_ms, end_offset_ms) = chapter.span_ms()
+            .map_err(|e| anyhow::anyhow!("Embedded chapter {}: {}", chapter_number, e))?;
+        // Embedded markers don't always carry a title
+        let title = chapter.title_or_numbered(chapter_number);
+
+        chapters.push(FlattenedChapter {
+            title: title.clone(),
+            full_path: title,
+            start_offset_ms,
+            length_ms: end_offset_ms - start_offset_ms,
+            start_offset_sec: start_offset_ms / 1000,
+            level: 0,
+            chapter_number,
+            global_chapter_number: chapter_number,
+            disc_number: 1,
+            disc_title: String::new(),
+        });
+    }
+    Ok(chapters)
+}
+
+/// Create and return the `<base>/<asin>` directory used by --asin-dirs
+fn asin_dir(base: Option<&Path>, asin: &str) -> Result<PathBuf> {
+    let asin = sanitize_title(asin.trim(), &SanitizeConfig::default());
+    if asin.is_empty() {
+        anyhow::bail!("--asin-dirs requires an ASIN, but none was found in the chapters JSON, voucher or file tags.");
+    }
+    let dir = base.unwrap_or_else(|| Path::new(".")).join(asin);
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create ASIN directory: {}", dir.display()))?;
+    info!("Writing output to {}", dir.display());
+    Ok(dir)
+}
+
+/// Create and return the `<base>/<album_name>` directory used by --album-dir
+fn album_dir(base: &Path, album: &str) -> Result<PathBuf> {
+    let album_name = sanitize_title(&album.to_snake_case(), &SanitizeConfig::default());
+    if album_name.is_empty() {
+        warn!("--album-dir: the input file has no usable album tag, writing chapters to {}", base.display());
+        return Ok(base.to_path_buf());
+    }
+    let dir = base.join(album_name);
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create album directory: {}", dir.display()))?;
+    info!("Writing chapters to {}", dir.display());
+    Ok(dir)
+}
+
+/// Audio checked for silence on each side of a chapter boundary
+const SILENCE_WINDOW_MS: i64 = 2_000;
+/// How far a silence may be from a boundary and still count for it
+const SILENCE_TOLERANCE_MS: i64 = 200;
+
+/// Whether there is silence at each boundary between consecutive chapters, found by running
+/// ffmpeg's silencedetect on the audio around it
+fn detect_silent_boundaries(aaxc_file_path: &Path, input_format: &InputFormat, chapters: &[FlattenedChapter]) -> Result<Vec<bool>> {
+    let boundaries: Vec<i64> = chapters.iter().skip(1).map(|chapter| chapter.start_offset_ms).collect();
+    info!("Detecting silence at {} chapter boundaries", boundaries.len());
+    boundaries
+        .iter()
+        .map(|&boundary_ms| {
+            let window_start_ms = (boundary_ms - SILENCE_WINDOW_MS).max(0);
+            let window_length_ms = boundary_ms + SILENCE_WINDOW_MS - window_start_ms;
+            let args = input_format
+                .apply_decryption(FFmpegArgs::new())
+                .log_level("info")
+                .no_stats()
+                .input_time_range(&format_time_from_ms(window_start_ms), &format_time_from_ms(window_length_ms))
+                .input(aaxc_file_path)
+                .extra_args(["-af", "silencedetect=noise=-30dB:d=0.3", "-vn", "-f", "null"])
+                .output(null_device())
+                .build();
+            let output = Command::new("ffmpeg")
+                .args(args)
+                .stdin(Stdio::null())
+                .output()
+                .context("Failed to execute ffmpeg for silence detection. Is ffmpeg installed and available in your PATH?")?;
+            if !output.status.success() {
+                anyhow::bail!(
+                    "ffmpeg silence detection failed at {}: {}",
+                    format_time_from_ms(boundary_ms),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            let silences = parse_silencedetect(&String::from_utf8_lossy(&output.stderr), window_length_ms);
+            let silent = is_silent_boundary(&silences, boundary_ms - window_start_ms);
+            info!("Chapter boundary at {}: {}", format_time_from_ms(boundary_ms), if silent { "silence" } else { "no silence" });
+            Ok(silent)
+        })
+        .collect()
+}
+
+/// Silence detection settings for --split-at-silence: quieter than -30 dB for at least 2 seconds
+const SPLIT_SILENCE_FILTER: &str = "silencedetect=noise=-30dB:d=2.0";
+
+/// Parts for --split-at-silence, found by running ffmpeg's silencedetect over the whole book
+fn detect_silence_chapters(aaxc_file_path: &Path, input_format: &InputFormat, duration_ms: i64, title_from_position: bool) -> Result<Vec<FlattenedChapter>> {
+    info!("Detecting silence in {}, this decodes the whole book", aaxc_file_path.display());
+    let args = input_format
+        .apply_decryption(FFmpegArgs::new())
+        .log_level("info")
+        .no_stats()
+        .input(aaxc_file_path)
+        .extra_args(["-af", SPLIT_SILENCE_FILTER, "-vn", "-f", "null"])
+        .output(null_device())
+        .build();
+    let output = Command::new("ffmpeg")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .context("Failed to execute ffmpeg for silence detection. Is ffmpeg installed and available in your PATH?")?;
+    if !output.status.success() {
+        anyhow::bail!("ffmpeg silence detection failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let silences = parse_silencedetect(&String::from_utf8_lossy(&output.stderr), duration_ms);
+    let chapters = silence_chapters(&silences, duration_ms, title_from_position);
+    info!("Found {} silences, splitting into {} parts", silences.len(), chapters.len());
+    Ok(chapters)
+}
+
+/// Chapters split at the middle of each silence. Silences at the very start or end of the
+/// book don't split it.
+fn silence_chapters(silences: &[(i64, i64)], duration_ms: i64, title_from_position: bool) -> Vec<FlattenedChapter> {
+    let split_points: Vec<i64> = silences
+        .iter()
+        .filter(|&&(start, end)| start > 0 && end < duration_ms)
+        .map(|&(start, end)| (start + end) / 2)
+        .collect();
+    let starts = std::iter::once(0).chain(split_points.iter().copied());
+    let ends = split_points.iter().copied().chain(std::iter::once(duration_ms));
+    starts
+        .zip(ends)
+        .enumerate()
+        .map(|(index, (start_offset_ms, end_ms))| {
+            let title = if title_from_position {
+                ConversionProgress::format_time(start_offset_ms as f64 / 1000.0)
+            } else {
+                format!("Part {}", index + 1)
+            };
+            FlattenedChapter {
+                full_path: title.clone(),
+                title,
+                start_offset_ms,
+                length_ms: end_ms - start_offset_ms,
+                start_offset_sec: start_offset_ms / 1000,
+                level: 0,
+                chapter_number: index + 1,
+                global_chapter_number: index + 1,
+                disc_number: 1,
+                disc_title: String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Silences from silencedetect's `silence_start: S` and `silence_end: E | ...` log lines, in ms.
+/// A silence still running at the end of the window ends at `window_length_ms`.
+fn parse_silencedetect(stderr: &str, window_length_ms: i64) -> Vec<(i64, i64)> {
+    let seconds_after = |line: &str, key: &str| -> Option<i64> {
+        let value = line.split(key).nth(1)?.split_whitespace().next()?;
+        value.parse::<f64>().ok().map(|seconds| (seconds * 1000.0).round() as i64)
+    };
+    let mut silences = Vec::new();
+    let mut start = None;
+    for line in stderr.lines() {
+        if let Some(silence_start) = seconds_after(line, "silence_start:") {
+            start = Some(silence_start.max(0));
+        } else if let Some(silence_end) = seconds_after(line, "silence_end:") {
+            silences.push((start.take().unwrap_or(0), silence_end));
+        }
+    }
+    if let Some(start) = start {
+        silences.push((start, window_length_ms));
+    }
+    silences
+}
+
+/// Whether a silence covers `boundary_ms`, give or take [`SILENCE_TOLERANCE_MS`]
+fn is_silent_boundary(silences: &[(i64, i64)], boundary_ms: i64) -> bool {
+    silences
+        .iter()
+        .any(|&(start, end)| start - SILENCE_TOLERANCE_MS <= boundary_ms && boundary_ms <= end + SILENCE_TOLERANCE_MS)
+}
+
+/// Merge each chapter into the previous one unless there is silence at the boundary between
+/// them; `silent_boundaries[i]` is the boundary between chapters `i` and `i + 1`
+fn merge_on_silence(chapters: &[FlattenedChapter], silent_boundaries: &[bool]) -> Vec<MergedChapter> {
+    let mut merged_chapters: Vec<MergedChapter> = Vec::new();
+    for (index, chapter) in chapters.iter().enumerate() {
+        let boundary_is_silent = index == 0 || silent_boundaries.get(index - 1).copied().unwrap_or(true);
+        match merged_chapters.last_mut() {
+            Some(previous) if !boundary_is_silent => previous.merge_with(chapter),
+            _ => merged_chapters.push(MergedChapter::from_flattened(chapter)),
+        }
+    }
+    merged_chapters
+}
+
+/// Find consecutive chapters where a chapter runs past the start of the next one.
+/// Returns the index of each such chapter, the index of the next one, and the overlap in ms.
+fn detect_overlaps(chapters: &[MergedChapter]) -> Vec<(usize, usize, i64)> {
+    chapters
+        .windows(2)
+        .enumerate()
+        .filter_map(|(i, pair)| {
+            let overlap_ms = pair[0].start_offset_ms + pair[0].length_ms - pair[1].start_offset_ms;
+            (overlap_ms > 0).then_some((i, i + 1, overlap_ms))
+        })
+        .collect()
+}
+
+/// Handle overlapping chapters according to `strategy`
+fn resolve_overlaps(chapters: &mut [MergedChapter], strategy: OverlapStrategy) -> Result<()> {
+    let overlaps = detect_overlaps(chapters);
+    for &(current, next, overlap_ms) in &overlaps {
+        let message = format!(
+            "Chapter '{}' overlaps the next chapter '{}' by {} ms",
+            chapters[current].title, chapters[next].title, overlap_ms
+        );
+        match strategy {
+            OverlapStrategy::Warn => warn!("{}", message),
+            OverlapStrategy::Error => anyhow::bail!("{}. Use --overlap-strategy truncate or warn to convert anyway.", message),
+            OverlapStrategy::Truncate => {
+                let length_ms = chapters[current].length_ms - overlap_ms;
+                if length_ms <= 0 {
+                    anyhow::bail!("{}, and starts after it. Check the order of your chapter data.", message);
+                }
+                info!("{}, truncating it to {} ms", message, length_ms);
+                chapters[current].length_ms = length_ms;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `title` without the part matched by --strip-title-prefix, or unchanged when nothing would be left
+fn strip_title_prefix(title: &str, pattern: &regex::Regex) -> String {
+    let stripped = pattern.replace(title, "");
+    if stripped.trim().is_empty() {
+        warn!("--strip-title-prefix would leave chapter title '{}' empty, keeping it", title);
+        return title.to_string();
+    }
+    stripped.into_owned()
+}
+
+/// Audio encoders rarely get faster with more threads than this per ffmpeg process
+const MAX_AUTO_THREADS: usize = 4;
+
+/// ffmpeg's `-threads` value for --threads: `auto` becomes the number of available cores, at most
+/// [`MAX_AUTO_THREADS`], anything else (including ffmpeg's own `0`) is passed on unchanged
+fn ffmpeg_threads(threads: &str) -> String {
+    if !threads.eq_ignore_ascii_case("auto") {
+        return threads.to_string();
+    }
+    let available = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    let count = available.clamp(1, MAX_AUTO_THREADS);
+    info!("--threads auto: using {} ffmpeg threads ({} cores available)", count, available);
+    count.to_string()
+}
+
+/// Move every chapter by `offset_ms`, for chapter data made from a different encode of the book.
+/// A chapter that would start before the audio starts at 0 instead and keeps its end, so it gets shorter.
+fn offset_chapters(chapters: &mut [FlattenedChapter], offset_ms: i64) {
+    for chapter in chapters {
+        let start_ms = chapter.start_offset_ms + offset_ms;
+        if start_ms < 0 {
+            let length_ms = (chapter.length_ms + start_ms).max(0);
+            warn!(
+                "Chapter '{}' would start at {} ms after --chapter-time-offset, starting it at 0 with a length of {} ms",
+                chapter, start_ms, length_ms
+            );
+            chapter.start_offset_ms = 0;
+            chapter.length_ms = length_ms;
+        } else {
+            chapter.start_offset_ms = start_ms;
+        }
+        chapter.start_offset_sec = chapter.start_offset_ms / 1000;
+    }
+}
+
+/// Extend each chapter to the start of the next one, and the last chapter to `audio_end_ms`,
+/// so audio between chapters isn't lost. Chapters are only ever extended, never shortened.
+fn fill_chapter_gaps(chapters: &mut [MergedChapter], audio_end_ms: i64) {
+    let next_starts: Vec<i64> = chapters
+        .iter()
+        .skip(1)
+        .map(|chapter| chapter.start_offset_ms)
+        .chain(std::iter::once(audio_end_ms))
+        .collect();
+
+    for (chapter, next_start_ms) in chapters.iter_mut().zip(next_starts) {
+        let gap_ms = next_start_ms - (chapter.start_offset_ms + chapter.length_ms);
+        if gap_ms > 0 {
+            info!("Extending chapter '{}' by {} ms to close the gap to the next chapter", chapter.title, gap_ms);
+            chapter.length_ms += gap_ms;
+        }
+    }
+}
+
+/// Convert multiple chapters to individual files
+#[allow(clippy::too_many_arguments)]
+fn convert_chapters(
+    aaxc_file_path: &Path,
+    input_format: &InputFormat,
+    chapters: &[MergedChapter],
+    naming_format: &ChapterNamingFormat,
+    filename_title_case: TitleCase,
+    split_structure: &SplitStructure,
+    output_base_path: &Path,
+    extension: &str,
+    duplicate_titles: &DuplicateTitles,
+    codec: &str,
+    verbose: bool,
+    machine_readable: bool,
+    progress_batch: usize,
+    threads: &str,
+    ffmpeg_log_level: FfmpegLogLevel,
+    disc_tags: bool,
+    relative_paths: bool,
+    retries: u32,
+    copy_metadata: bool,
+    conversion_flags: &ConversionFlags,
+    keep_temp_files: bool,
+    book: &BookInfo,
+) -> Result<()> {
+    let total_chapters = chapters.len();
+    let total_discs = chapters
+        .iter()
+        .map(|chapter| chapter.disc_number)
+        .collect::<std::collections::HashSet<_>>()
+        .len();
+    info!("Converting {} chapters", total_chapters);
+    
+    // Initialize progress manager
+    let mut progress_manager = if machine_readable {
+        ProgressManager::new_machine_readable(total_chapters, progress_batch)
+    } else {
+        ProgressManager::new_with_verbose(total_chapters, verbose, conversion_flags.progress_bars)
+    };
+
+    if relative_paths {
+        progress_manager.set_relative_base(output_base_path);
+    }
+    progress_manager.set_total_audio_ms(chapters.iter().map(|chapter| chapter.length_ms).sum());
+
+    // Emit conversion started event
+    progress_manager.emit_conversion_started(extension, &output_base_path.to_string_lossy(), book);
+    
+    for (index, chapter) in chapters.iter().enumerate() {
+        let chapter_number = index + 1;
+        info!("Converting chapter {}/{}: {}", chapter_number, total_chapters, chapter.title);
+        
+        let output_path = chapter_output_path(chapter, naming_format, filename_title_case, split_structure, output_base_path, extension, duplicate_titles);
+        
+        info!("Output file: {}", output_path.display());
+        
+        // Convert time to ffmpeg format (HH:MM:SS.mmm)
+        let start_time = format_time_from_ms(chapter.start_offset_ms);
+        let duration_time = format_time_from_ms(chapter.length_ms);
+        let duration_seconds = chapter.length_ms as f64 / 1000.0;
+        
+        info!("Chapter time range: {} to {} (duration: {})", 
+              start_time, 
+              format_time_from_ms(chapter.start_offset_ms + chapter.length_ms),
+              duration_time);
+        
+        // Create parent directories if needed
+        if let Some(parent) = output_path.parent().filter(|_| conversion_flags.benchmark_muxer.is_none()) {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {}", parent.display()))?;
+        }
+        
+        let mut metadata = chapter_metadata(chapter, chapter_number, total_chapters, book);
+        if disc_tags {
+            metadata.push(format!("disc={}/{}", chapter.disc_number, total_discs));
+        }
+
+        // Start progress tracking for this chapter
+        progress_manager.start_chapter(&chapter.title, duration_seconds);
+        
+        let temp_file = TempFileGuard::new(temp_output_path(&output_path), keep_temp_files);
+        let mut attempt = 0;
+        let status = loop {
+            // Run ffmpeg for this chapter with enhanced progress tracking
+            let mut cmd = ffmpeg_chapter_with_progress(
+                aaxc_file_path.to_path_buf(),
+                input_format,
+                start_time.clone(),
+                duration_time.clone(),
+                temp_file.path().to_string_lossy().to_string(),
+                codec,
+                &progress_manager,
+                threads,
+                ffmpeg_log_level,
+                &metadata,
+                copy_metadata,
+                conversion_flags,
+            )?;
+
+            // Parse ffmpeg progress in the main thread
+            if let Some(stdout) = cmd.stdout.as_mut() {
+                let stdout_reader = std::io::BufReader::new(stdout);
+                let mut progress = ConversionProgress::new(duration_seconds);
+                let started = Instant::now();
+
+                for l in stdout_reader.lines().map_while(Result::ok) {
+                    let kind = parse_ffmpeg_progress_line(&l, &mut progress);
+                    progress_manager.update_chapter_progress(&progress);
+                    match kind {
+                        // Report the final values now rather than when ffmpeg exits
+                        ProgressLineKind::End => progress_manager.flush_chapter_progress(),
+                        ProgressLineKind::Continue => limit_speed(&progress, started, conversion_flags),
+                        _ => {}
+                    }
+                }
+            }
+
+            let status = cmd.wait()
+                .with_context(|| format!("ffmpeg process failed for chapter: {}", chapter.title))?;
+            if status.success() || attempt >= retries {
+                break status;
+            }
+
+            // Retry with exponential backoff, starting over from a clean output file
+            attempt += 1;
+            let backoff = Duration::from_secs(1 << (attempt - 1));
+            let message = format!(
+                "ffmpeg failed to convert chapter '{}', retrying in {}s (attempt {}/{})",
+                chapter.title, backoff.as_secs(), attempt, retries
+            );
+            warn!("{}", message);
+            progress_manager.emit_warning("retry", &message, Some(chapter_number), Some(attempt));
+            if temp_file.path().exists() {
+                std::fs::remove_file(temp_file.path())
+                    .with_context(|| format!("Failed to remove partial output file: {}", temp_file.path().display()))?;
+            }
+            std::thread::sleep(backoff);
+        };
+        
+        if status.success() && conversion_flags.benchmark_muxer.is_some() {
+            progress_manager.complete_chapter(&chapter.title, null_device(), duration_seconds, 0);
+            info!("Chapter {}/{} benchmarked", chapter_number, total_chapters);
+        } else if status.success() {
+            temp_file.persist(&output_path)
+                .with_context(|| format!("Failed to move the converted chapter into place: {}", output_path.display()))?;
+            let output_bytes = std::fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+            progress_manager.complete_chapter(&chapter.title, &output_path.to_string_lossy(), duration_seconds, output_bytes);
+            info!("Chapter {}/{} completed: {}", chapter_number, total_chapters, output_path.display());
+        } else {
+            error!("ffmpeg conversion failed for chapter: {}", chapter.title);
+            progress_manager.emit_error(&format!("ffmpeg failed to convert chapter '{}'", chapter.title), Some(chapter_number));
+            return Err(AudibleUtilError::FfmpegFailed { exit_code: status.code().unwrap_or(-1) }.into());
+        }
+    }
+    
+    progress_manager.complete_all(true);
+    info!("All {} chapters converted successfully", total_chapters);
+    if conversion_flags.benchmark_muxer.is_some() {
+        let total_audio_ms = chapters.iter().map(|chapter| chapter.length_ms).sum();
+        progress_manager.benchmark_result(total_audio_ms).print(machine_readable);
+    }
+    Ok(())
+}
+
+/// `-metadata` entries placing a chapter file in the book: its title, `track=N/TOTAL` and the
+/// book's album, artist, genre (`Audiobook` when the book has none) and date when it has one
+fn chapter_metadata(chapter: &MergedChapter, chapter_number: usize, total_chapters: usize, book: &BookInfo) -> Vec<String> {
+    let mut metadata = vec![
+        format!("title={}", chapter.title),
+        format!("track={}/{}", chapter_number, total_chapters),
+        format!("album={}", book.album),
+        format!("artist={}", book.artist),
+        format!("genre={}", book.genre.as_deref().unwrap_or("Audiobook")),
+    ];
+    if let Some(date) = &book.date {
+        metadata.push(format!("date={}", date));
+    }
+    metadata
+}
+
+/// Chapter numbers selected by --min-chapter/--max-chapter, None when neither is given.
+/// An open end extends to the highest chapter number of the book, and numbers past it are dropped.
+fn chapter_number_range(min_chapter: Option<u64>, max_chapter: Option<u64>, chapter_count: usize) -> Result<Option<HashSet<usize>>> {
+    if min_chapter.is_none() && max_chapter.is_none() {
+        return Ok(None);
+    }
+    let min = min_chapter.unwrap_or(1);
+    let max = match max_chapter {
+        Some(max) if max < min => anyhow::bail!("--min-chapter {} is greater than --max-chapter {}.", min, max),
+        // Clamped before collecting, --max-chapter has no upper bound
+        Some(max) => max.min(chapter_count as u64),
+        None => chapter_count as u64,
+    };
+    // Within the book, so every number fits in usize
+    Ok(Some((min..=max).map(|number| number as usize).collect()))
+}
+
+/// Read the chapter numbers listed in a --chapter-list-file, one number or `N-M` range per line
+fn read_chapter_list(path: &Path) -> Result<Vec<RangeInclusive<usize>>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read chapter list file: {}", path.display()))?;
+    parse_chapter_list(&content).map_err(|(line_number, message)| {
+        anyhow::anyhow!("{}:{}: {}", path.display(), line_number, message)
+    })
+}
+
+/// Parse chapter list lines, returning the 1-based line number with the error.
+/// Ranges are kept as they are, the file is read before the number of chapters is known.
+fn parse_chapter_list(content: &str) -> std::result::Result<Vec<RangeInclusive<usize>>, (usize, String)> {
+    let parse_number = |value: &str| match value.trim().parse::<usize>() {
+        Ok(number) if number > 0 => Ok(number),
+        _ => Err(format!("'{}' is not a chapter number", value.trim())),
+    };
+
+    let mut chapters = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let range = match line.split_once('-') {
+            Some((start, end)) => parse_number(start).and_then(|start| {
+                let end = parse_number(end)?;
+                if end < start {
+                    return Err(format!("range {} ends before it starts", line));
+                }
+                Ok(start..=end)
+            }),
+            None => parse_number(line).map(|number| number..=number),
+        };
+        chapters.push(range.map_err(|message| (index + 1, message))?);
+    }
+    Ok(chapters)
+}
+
+/// Make it obvious that a --test-chapter run leaves incomplete output behind
+fn print_test_run_banner(chapter: &MergedChapter, chapter_number: usize, total_chapters: usize) {
+    let rule = "=".repeat(72);
+    warn!("Test run: only chapter {}/{} is converted", chapter_number, total_chapters);
+    eprintln!("{}", rule);
+    eprintln!("  TEST RUN: converting only chapter {}/{}: {}", chapter_number, total_chapters, chapter.title);
+    eprintln!("  The output is INCOMPLETE. Do not use it as a finished conversion.");
+    eprintln!("{}", rule);
+}
+
+/// Generate the output path of a chapter file based on the split structure.
+/// `filename_title_case` transforms the title for the file name only.
+#[allow(clippy::too_many_arguments)]
+fn chapter_output_path(
+    chapter: &MergedChapter,
+    naming_format: &ChapterNamingFormat,
+    filename_title_case: TitleCase,
+    split_structure: &SplitStructure,
+    output_base_path: &Path,
+    extension: &str,
+    duplicate_titles: &DuplicateTitles,
+) -> PathBuf {
+    let renamed;
+    let chapter = if filename_title_case == TitleCase::Preserve {
+        chapter
+    } else {
+        renamed = MergedChapter {
+            title: filename_title_case.apply(&chapter.title),
+            ..chapter.clone()
+        };
+        &renamed
+    };
+    match split_structure {
+        SplitStructure::Flat => {
+            let filename = chapter.generate_filename(naming_format, extension, duplicate_titles);
+            output_base_path.join(filename)
+        },
+        SplitStructure::Hierarchical => {
+            chapter.get_hierarchical_output_path(output_base_path, naming_format, extension, duplicate_titles)
+        },
+        SplitStructure::ByDisc => {
+            chapter.get_disc_output_path(output_base_path, naming_format, extension, duplicate_titles)
+        }
+    }
+}
+
+/// Check that no two chapters are written to the same output path.
+/// Each conflict is reported as the path and the numbers of the chapters producing it.
+fn check_unique_filenames(
+    chapters: &[MergedChapter],
+    output_path: impl Fn(&MergedChapter) -> PathBuf,
+) -> std::result::Result<(), Vec<String>> {
+    let mut chapters_by_path: Vec<(PathBuf, Vec<usize>)> = Vec::new();
+    for chapter in chapters {
+        let path = output_path(chapter);
+        match chapters_by_path.iter_mut().find(|(existing, _)| *existing == path) {
+            Some((_, numbers)) => numbers.push(chapter.chapter_number),
+            None => chapters_by_path.push((path, vec![chapter.chapter_number])),
+        }
+    }
+
+    let conflicts: Vec<String> = chapters_by_path
+        .into_iter()
+        .filter(|(_, numbers)| numbers.len() > 1)
+        .map(|(path, numbers)| {
+            let numbers: Vec<String> = numbers.iter().map(|number| number.to_string()).collect();
+            format!("{} (chapters {})", path.display(), numbers.join(", "))
+        })
+        .collect();
+    if conflicts.is_empty() { Ok(()) } else { Err(conflicts) }
+}
+
+/// Report the chapter output paths a split run would produce without running ffmpeg.
+/// With `verify`, the already-existing files are probed and their durations compared to the chapter data.
+#[allow(clippy::too_many_arguments)]
+fn plan_chapters(
+    chapters: &[MergedChapter],
+    naming_format: &ChapterNamingFormat,
+    filename_title_case: TitleCase,
+    split_structure: &SplitStructure,
+    output_base_path: &Path,
+    extension: &str,
+    duplicate_titles: &DuplicateTitles,
+    verify: bool,
+    timestamps: bool,
+    machine_readable: bool,
+) -> Result<()> {
+    // Allowed difference between the probed and the expected duration
+    const DURATION_TOLERANCE_SECONDS: f64 = 1.0;
+
+    let total_chapters = chapters.len();
+    let mut failures = 0;
+
+    for (index, chapter) in chapters.iter().enumerate() {
+        let chapter_number = index + 1;
+        let output_path = chapter_output_path(chapter, naming_format, filename_title_case, split_structure, output_base_path, extension, duplicate_titles);
+        let expected_seconds = chapter.length_ms as f64 / 1000.0;
+
+        let issue = if !verify {
+            None
+        } else if !output_path.is_file() {
+            Some("file is missing".to_string())
+        } else {
+            match ffprobe_duration(&output_path) {
+                Ok(actual_seconds) if (actual_seconds - expected_seconds).abs() > DURATION_TOLERANCE_SECONDS => Some(format!(
+                    "duration is {:.1}s, expected {:.1}s",
+                    actual_seconds, expected_seconds
+                )),
+                Ok(_) => None,
+                Err(e) => Some(format!("could not be probed: {e}")),
+            }
+        };
+
+        if machine_readable {
+            if let Some(ref issue) = issue {
+                let event = ProgressEvent::Error {
+                    message: format!("{}: {}", output_path.display(), issue),
+                    chapter_number: Some(chapter_number),
+                };
+                println!("{}", event.to_json());
+            }
+        } else {
+            let status = match (&issue, verify) {
+                (Some(issue), _) => format!(" [FAILED: {}]", issue),
+                (None, true) => " [OK]".to_string(),
+                (None, false) => String::new(),
+            };
+            let times = if timestamps { format!("{} ", chapter_time_range(chapter)) } else { String::new() };
+            println!("{:>3}/{} {}{}{}", chapter_number, total_chapters, times, output_path.display(), status);
+        }
+
+        if let Some(issue) = issue {
+            warn!("Chapter {} ({}): {}", chapter_number, output_path.display(), issue);
+            failures += 1;
+        }
+    }
+
+    if failures > 0 {
+        anyhow::bail!(
+            "{} of {} chapter files failed verification. Re-run without --metadata-only to convert them again.",
+            failures,
+            total_chapters
+        );
+    }
+    Ok(())
+}
+
+/// `[HH:MM:SS - HH:MM:SS] (MM:SS)` start, end and length of a chapter
+fn chapter_time_range(chapter: &MergedChapter) -> String {
+    // Milliseconds are left out for readability
+    let without_ms = |ms: i64| format_time_from_ms(ms).split('.').next().unwrap_or_default().to_string();
+    let length_seconds = chapter.length_ms.max(0) / 1000;
+    format!(
+        "[{} - {}] ({:02}:{:02})",
+        without_ms(chapter.start_offset_ms),
+        without_ms(chapter.start_offset_ms + chapter.length_ms),
+        length_seconds / 60,
+        length_seconds % 60
+    )
+}
+
+/// Probe the duration of an already-converted output file in seconds
+fn ffprobe_duration(path: &Path) -> Result<f64> {
+    let ffprobe_cmd = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+            path.to_str().context("Failed to convert output file path to string.")?,
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .with_context(|| "Failed to execute ffprobe. Is ffprobe installed and available in your PATH?")?;
+
+    if !ffprobe_cmd.status.success() {
+        anyhow::bail!("ffprobe failed: {}", String::from_utf8_lossy(&ffprobe_cmd.stderr).trim());
+    }
+
+    String::from_utf8_lossy(&ffprobe_cmd.stdout)
+        .trim()
+        .parse::<f64>()
+        .context("Failed to parse duration reported by ffprobe.")
+}
+
+/// Convert milliseconds to ffmpeg time format (HH:MM:SS.mmm)
+fn format_time_from_ms(ms: i64) -> String {
+    let total_seconds = ms / 1000;
+    let milliseconds = ms % 1000;
+    let hours = total_seconds / 3600;
+    let minutes = (total_seconds % 3600) / 60;
+    let seconds = total_seconds % 60;
+    
+    format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, seconds, milliseconds)
+}
+
+/// Run ffmpeg for a specific chapter with enhanced progress tracking
+#[allow(clippy::too_many_arguments)]
+fn ffmpeg_chapter_with_progress(
+    aaxc_file_path: PathBuf,
+    input_format: &InputFormat,
+    start_time: String,
+    duration: String,
+    file_name: String,
+    codec: &str,
+    _progress_manager: &ProgressManager,
+    threads: &str,
+    ffmpeg_log_level: FfmpegLogLevel,
+    metadata: &[String],
+    copy_metadata: bool,
+    conversion_flags: &ConversionFlags,
+) -> Result<Child> {
+    let args = input_format
+        .apply_decryption(FFmpegArgs::new())
+        .log_level(ffmpeg_log_level.as_str())
+        .input(&aaxc_file_path)
+        .threads(threads)
+        .time_range(&start_time, &duration)
+        .progress_pipe(progress_output_arg())
+        .overwrite()
+        .extra_args(map_metadata_args(copy_metadata))
+        // Per-chapter tags override the ones copied from the input
+        .extra_args(metadata.iter().flat_map(|entry| ["-metadata", entry.as_str()]))
+        .extra_args(metadata_map_args(conversion_flags))
+        .extra_args(audio_filter_args(conversion_flags))
+        .output_args(&conversion_flags.quality_args)
+        .codec(codec);
+    let args = output_or_null_device(args, &file_name, conversion_flags).build();
+    let mut cmd = Command::new("ffmpeg")
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| "Failed to execute ffmpeg. Is ffmpeg installed and available in your PATH?")?;
+
+    // Note: Progress parsing will be handled in the main thread
+    // The progress manager will be updated by the calling function
+    forward_ffmpeg_stderr(&mut cmd, ffmpeg_log_level);
+
+    info!("ffmpeg process started for chapter");
+    Ok(cmd)
+}
+
+/// Write to `file_name`, or to the null device with --benchmark
+fn output_or_null_device(args: FFmpegArgs, file_name: &str, flags: &ConversionFlags) -> FFmpegArgs {
+    match flags.benchmark_muxer {
+        // Without a file extension ffmpeg needs to be told the format
+        Some(muxer) => args.output_args(["-f", muxer]).output(null_device()),
+        None => args.output(file_name),
+    }
+}
+
+/// The platform's null device, for ffmpeg runs whose output is thrown away
+fn null_device() -> &'static str {
+    if cfg!(windows) { "NUL" } else { "/dev/null" }
+}
+
+/// Forward ffmpeg's stderr to the logger from a background thread while progress is read from stdout
+fn forward_ffmpeg_stderr(cmd: &mut Child, ffmpeg_log_level: FfmpegLogLevel) {
+    let Some(level) = ffmpeg_log_level.forward_level() else {
+        return;
+    };
+    if let Some(stderr) = cmd.stderr.take() {
+        std::thread::spawn(move || {
+            let stderr_reader = std::io::BufReader::new(stderr);
+            for line in stderr_reader.lines().map_while(Result::ok) {
+                log::log!(target: "ffmpeg", level, "{}", line);
+            }
+        });
+    }
+}
+
+/// With --max-speed, pause while ffmpeg is ahead of the limit
+///
+/// ffmpeg blocks on writing progress that isn't read, so pausing the reader holds it back too.
+fn limit_speed(progress: &ConversionProgress, started: Instant, flags: &ConversionFlags) {
+    if let Some(delay) = flags.max_speed.and_then(|max_speed| speed_limit_delay(progress.current_time, started.elapsed(), max_speed)) {
+        std::thread::sleep(delay);
+    }
+}
+
+/// `current_time / max_speed - elapsed`: how far ahead of `max_speed` times realtime the conversion is
+fn speed_limit_delay(current_time: f64, elapsed: Duration, max_speed: f64) -> Option<Duration> {
+    let target = Duration::try_from_secs_f64(current_time / max_speed).ok()?;
+    target.checked_sub(elapsed).filter(|delay| !delay.is_zero())
+}
+
+/// What a line of ffmpeg's `-progress` output was
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ProgressLineKind {
+    /// A progress value that was stored in the progress struct
+    Updated,
+    /// `progress=continue`, the end of a block with more to come
+    Continue,
+    /// `progress=end`, ffmpeg finished and won't report any more progress
+    End,
+    /// A key that isn't tracked, or a value that couldn't be parsed
+    Unknown,
+}
+
+/// Parse ffmpeg progress line and update progress struct
+pub(crate) fn parse_ffmpeg_progress_line(line: &str, progress: &mut ConversionProgress) -> ProgressLineKind {
+    let Some((key, value)) = line.split_once('=') else {
+        return ProgressLineKind::Unknown;
+    };
+    let value = value.trim();
+    match key.trim() {
+        "progress" if value == "continue" => return ProgressLineKind::Continue,
+        "progress" if value == "end" => return ProgressLineKind::End,
+        // time=HH:MM:SS.mmm
+        "time" => {
+            if let Ok(time_seconds) = parse_time_to_seconds(value) {
+                progress.current_time = time_seconds;
+                return ProgressLineKind::Updated;
+            }
+        },
+        // speed=N.Nx
+        "speed" => {
+            if let Some(Ok(speed)) = value.strip_suffix('x').map(str::parse::<f64>) {
+                progress.record_speed(speed);
+                return ProgressLineKind::Updated;
+            }
+        },
+        // bitrate=N
+        "bitrate" => {
+            if let Ok(bitrate) = value.parse::<f64>() {
+                progress.bitrate = bitrate;
+                return ProgressLineKind::Updated;
+            }
+        },
+        // size=N
+        "size" => {
+            if let Ok(size) = value.parse::<u64>() {
+                progress.size = size;
+                return ProgressLineKind::Updated;
+            }
+        },
+        // fps=N.N
+        "fps" => {
+            if let Ok(fps) = value.parse::<f64>() {
+                progress.fps = fps;
+                return ProgressLineKind::Updated;
+            }
+        },
+        _ => {},
+    }
+    ProgressLineKind::Unknown
+}
+
+/// Parse time string (HH:MM:SS.mmm) to seconds
+fn parse_time_to_seconds(time_str: &str) -> Result<f64, std::num::ParseFloatError> {
+    let parts: Vec<&str> = time_str.split(':').collect();
+    if parts.len() == 3 {
+        let hours: f64 = parts[0].parse()?;
+        let minutes: f64 = parts[1].parse()?;
+        let seconds: f64 = parts[2].parse()?;
+        Ok(hours * 3600.0 + minutes * 60.0 + seconds)
+    } else {
+        // Fallback to direct parsing
+        time_str.parse()
+    }
+}
+
+/// Parse duration string (HH:MM:SS.mmm) to seconds
+fn parse_duration_to_seconds(duration: &str) -> f64 {
+    parse_time_to_seconds(duration).unwrap_or(0.0)
+}
+
+/// `-map_metadata` arguments copying the input's tags, or dropping them for --no-ffmpeg-metadata
+fn map_metadata_args(copy_metadata: bool) -> [&'static str; 2] {
+    ["-map_metadata", if copy_metadata { "0" } else { "-1" }]
+}
+
+/// Combine the audio filters for a conversion into one comma-separated `-af` chain
+fn build_audio_filter(flags: &ConversionFlags) -> Option<String> {
+    let filters: Vec<&str> = flags
+        .audio_filter
+        .iter()
+        .map(|filter| filter.trim().trim_matches(','))
+        .filter(|filter| !filter.is_empty())
+        .collect();
+    (!filters.is_empty()).then(|| filters.join(","))
+}
+
+/// `-metadata` arguments for the --metadata-map overrides, sorted by field
+fn metadata_map_args(flags: &ConversionFlags) -> Vec<String> {
+    let mut fields: Vec<(&String, &String)> = flags.metadata_map.iter().collect();
+    fields.sort();
+    fields
+        .into_iter()
+        .flat_map(|(field, value)| ["-metadata".to_string(), format!("{}={}", field, value)])
+        .collect()
+}
+
+/// `-af` arguments for the filter chain, empty when there is nothing to filter
+fn audio_filter_args(flags: &ConversionFlags) -> Vec<String> {
+    build_audio_filter(flags)
+        .map(|filter| vec!["-af".to_string(), filter])
+        .unwrap_or_default()
+}
+
+/// Start ffmpeg writing the converted audio to stdout, without progress reporting
+#[allow(clippy::too_many_arguments)]
+fn ffmpeg_to_stdout(
+    input: &Path,
+    input_format: &InputFormat,
+    output_format: &dyn crate::cli::OutputFormat,
+    threads: &str,
+    ffmpeg_log_level: FfmpegLogLevel,
+    copy_metadata: bool,
+    conversion_flags: &ConversionFlags,
+) -> Result<Child> {
+    let args = input_format
+        .apply_decryption(FFmpegArgs::new())
+        .log_level(ffmpeg_log_level.as_str())
+        .no_stats()
+        .input(input)
+        .threads(threads)
+        .extra_args(map_metadata_args(copy_metadata))
+        .extra_args(metadata_map_args(conversion_flags))
+        .extra_args(audio_filter_args(conversion_flags))
+        .output_args(&conversion_flags.quality_args)
+        .output_args(output_format.streaming_args())
+        .output_args(["-f", output_format.muxer()])
+        .codec(output_format.codec())
+        .output("pipe:1")
+        .build();
+    let mut cmd = Command::new("ffmpeg")
+        .args(args)
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| "Failed to execute ffmpeg. Is ffmpeg installed and available in your PATH?")?;
+
+    forward_ffmpeg_stderr(&mut cmd, ffmpeg_log_level);
+    Ok(cmd)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn ffmpeg(
+    aaxc_file_path: PathBuf,
+    input_format: &InputFormat,
+    duration: String,
+    file_name: String,
+    chapters_metadata: Option<&Path>,
+    codec: &str,
+    verbose: bool,
+    machine_readable: bool,
+    progress_batch: usize,
+    threads: &str,
+    ffmpeg_log_level: FfmpegLogLevel,
+    copy_metadata: bool,
+    conversion_flags: &ConversionFlags,
+) -> Result<(Child, ConversionProgress)> {
+    let mut args = input_format
+        .apply_decryption(FFmpegArgs::new())
+        .log_level(ffmpeg_log_level.as_str())
+        .input(&aaxc_file_path)
+        .threads(threads)
+        .progress_pipe(progress_output_arg())
+        .overwrite()
+        .extra_args(map_metadata_args(copy_metadata))
+        .extra_args(metadata_map_args(conversion_flags))
+        .extra_args(audio_filter_args(conversion_flags))
+        .output_args(&conversion_flags.quality_args)
+        .codec(codec);
+    args = output_or_null_device(args, &file_name, conversion_flags);
+    if let Some(chapters_metadata) = chapters_metadata {
+        // Tags still come from the book, only the chapters from the metadata file
+        args = args.metadata_input(chapters_metadata).extra_args(["-map_chapters", "1"]);
+    }
+    let mut cmd = Command::new("ffmpeg")
+        .args(args.build())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| "Failed to execute ffmpeg. Is ffmpeg installed and available in your PATH?")?;
+
+    forward_ffmpeg_stderr(&mut cmd, ffmpeg_log_level);
+
+    let mut progress = ConversionProgress::new(parse_duration_to_seconds(&duration));
+    let started = Instant::now();
+    {
+        let stdout = cmd.stdout.as_mut().context("Failed to capture ffmpeg stdout.")?;
+        let stdout_reader = std::io::BufReader::new(stdout);
+
+        if machine_readable {
+            // Machine-readable mode: output JSON progress events
+            let progress_batch = progress_batch.max(1);
+            let mut progress_lines = 0;
+
+            for line in stdout_reader.lines() {
+                let l = line.context("Failed to read line from ffmpeg output.")?;
+                let kind = parse_ffmpeg_progress_line(&l, &mut progress);
+                if kind == ProgressLineKind::Continue {
+                    limit_speed(&progress, started, conversion_flags);
+                }
+
+                // Only emit every `progress_batch` lines, and the final values once ffmpeg is done
+                progress_lines += 1;
+                if progress_lines >= progress_batch || kind == ProgressLineKind::End {
+                    progress_lines = 0;
+                    println!("{}", single_file_progress_event(&progress).to_json());
+                }
+            }
+            if progress_lines > 0 {
+                println!("{}", single_file_progress_event(&progress).to_json());
+            }
+        } else {
+            // Enhanced progress bar setup
+            let pb = ProgressBar::with_draw_target(Some(100), progress_draw_target(conversion_flags.progress_bars));
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("{bar:40.cyan/blue} {percent:>3}% [{elapsed_precise}] {msg}")
+                    .unwrap()
+                    .progress_chars("█▉▊▋▌▍▎▏  "),
+            );
+            pb.set_message("Starting conversion...");
+            if conversion_flags.progress_bars {
+                pb.enable_steady_tick(std::time::Duration::from_millis(100));
+            }
+
+
+            for line in stdout_reader.lines() {
+                let l = line.context("Failed to read line from ffmpeg output.")?;
+                if parse_ffmpeg_progress_line(&l, &mut progress) == ProgressLineKind::Continue {
+                    limit_speed(&progress, started, conversion_flags);
+                }
+                
+                // Update progress bar
+                let percentage = progress.percentage() as u64;
+                pb.set_position(percentage);
+                
+                let eta_str = progress.eta()
+                    .map(|eta| format!("ETA: {}", ProgressManager::format_duration(eta)))
+                    .unwrap_or_else(|| "ETA: --:--:--".to_string());
+                
+                let speed_str = if progress.speed > 0.0 {
+                    format!("Speed: {:.1}x", progress.speed)
+                } else {
+                    "Speed: --".to_string()
+                };
+                
+                let bitrate_str = if progress.bitrate > 0.0 {
+                    format!("Bitrate: {:.0} kbps", progress.bitrate / 1000.0)
+                } else {
+                    "Bitrate: --".to_string()
+                };
+                
+                let size_str = if progress.size > 0 {
+                    format!("Size: {}", ConversionProgress::format_size(progress.size))
+                } else {
+                    "Size: --".to_string()
+                };
+
+                let fps_str = if verbose && progress.fps > 0.0 {
+                    format!("FPS: {:.1}", progress.fps)
+                } else {
+                    String::new()
+                };
+
+                let time_str = if verbose {
+                    format!("Time: {}/{}", 
+                        ConversionProgress::format_time(progress.current_time),
+                        ConversionProgress::format_time(progress.total_duration))
+                } else {
+                    String::new()
+                };
+
+                let mut message_parts = vec![eta_str, speed_str, bitrate_str, size_str];
+                if verbose {
+                    if !fps_str.is_empty() {
+                        message_parts.push(fps_str);
+                    }
+                    if !time_str.is_empty() {
+                        message_parts.push(time_str);
+                    }
+                }
+
+                pb.set_message(message_parts.join(" | "));
+
+                // Log detailed progress in verbose mode
+                if verbose {
+                    info!("Progress: {:.1}% | Time: {}/{} | Speed: {:.1}x (avg {:.1}x, peak {:.1}x) | Bitrate: {:.0} kbps | Size: {}", 
+                        progress.percentage(),
+                        ConversionProgress::format_time(progress.current_time),
+                        ConversionProgress::format_time(progress.total_duration),
+                        progress.speed,
+                        progress.average_speed,
+                        progress.peak_speed,
+                        progress.bitrate / 1000.0,
+                        ConversionProgress::format_size(progress.size)
+                    );
+                }
+            }
+            pb.finish_with_message("Conversion complete");
+        }
+    }
+    info!("ffmpeg process finished");
+    Ok((cmd, progress))
+}
+
+/// Build the machine-readable progress event for a single-file conversion
+fn single_file_progress_event(progress: &ConversionProgress) -> ProgressEvent {
+    ProgressEvent::ChapterProgress {
+        chapter_number: 1,
+        total_chapters: 1,
+        chapter_title: "Single File".to_string(),
+        progress_percentage: progress.percentage(),
+        current_time: progress.current_time,
+        total_duration: progress.total_duration,
+        speed: progress.speed,
+        bitrate: progress.bitrate,
+        file_size: progress.size,
+        fps: progress.fps,
+        eta_seconds: progress.eta().map(|eta| eta.as_secs() as f64),
+    }
+}
+
+/// Decode the raw bytes of a chapter file into a string for serde_json
+fn decode_chapter_file(bytes: &[u8], encoding: ChapterEncoding) -> Result<String> {
+    let (text, had_errors) = match encoding {
+        ChapterEncoding::Utf8 => encoding_rs::UTF_8.decode_with_bom_removal(bytes),
+        ChapterEncoding::Utf16Le => encoding_rs::UTF_16LE.decode_with_bom_removal(bytes),
+        ChapterEncoding::Utf16Be => encoding_rs::UTF_16BE.decode_with_bom_removal(bytes),
+        ChapterEncoding::Latin1 => encoding_rs::WINDOWS_1252.decode_with_bom_removal(bytes),
+        ChapterEncoding::Auto => {
+            // decode() switches to the encoding of a byte order mark when there is one
+            let (text, detected, had_errors) = encoding_rs::UTF_8.decode(bytes);
+            info!("Chapter file encoding detected as {}", detected.name());
+            (text, had_errors)
+        }
+    };
+    if had_errors {
+        anyhow::bail!("The file contains byte sequences that are invalid in this encoding");
+    }
+    Ok(text.into_owned())
+}
+
+/// Chapter metadata file written next to the output, e.g. `book.chapters.ffmetadata` for `book.m4a`
+fn chapters_metadata_path(output_path: &Path) -> PathBuf {
+    let stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
+    output_path.with_file_name(format!("{}.chapters.ffmetadata", stem))
+}
+
+/// The chapters as an ffmpeg metadata file, titled with their path such as "Part 1 > Chapter 01"
+fn ffmetadata_chapters(chapters: &[FlattenedChapter]) -> String {
+    let mut metadata = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        metadata.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            chapter.start_offset_ms,
+            chapter.start_offset_ms + chapter.length_ms,
+            escape_ffmetadata(&chapter.to_string()),
+        ));
+    }
+    metadata
+}
+
+/// Backslash-escape the characters that are special in ffmpeg metadata files
+fn escape_ffmetadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Write an M3U playlist of the chapter files, with paths relative to the playlist.
+/// The extended format adds the duration and the book metadata before each file.
+fn write_m3u_playlist(
+    playlist_path: &Path,
+    chapters: &[MergedChapter],
+    chapter_paths: &[PathBuf],
+    tags: &MappedTags,
+    extended: bool,
+) -> Result<()> {
+    let playlist_dir = playlist_path.parent().unwrap_or(Path::new(""));
+    let mut playlist = String::new();
+    if extended {
+        playlist.push_str("#EXTM3U\n");
+    }
+    for (chapter, path) in chapters.iter().zip(chapter_paths) {
+        if extended {
+            let duration_seconds = (chapter.length_ms as f64 / 1000.0).round() as i64;
+            playlist.push_str(&format!("#EXTINF:{},{} - {}\n", duration_seconds, tags.artist(), chapter.title));
+            playlist.push_str(&format!("#EXTALB:{}\n", tags.album()));
+            playlist.push_str(&format!("#EXTART:{}\n", tags.artist()));
+            if let Some(genre) = tags.genre().filter(|genre| !genre.trim().is_empty()) {
+                playlist.push_str(&format!("#EXTGENRE:{}\n", genre));
+            }
+            playlist.push_str(&format!("#EXTCHAP:{}\n", chapter.chapter_number));
+        }
+        playlist.push_str(&relative_path(path, playlist_dir).to_string_lossy());
+        playlist.push('\n');
+    }
+    std::fs::write(playlist_path, playlist)
+        .with_context(|| format!("Failed to write playlist: {}", playlist_path.display()))
+}
+
+/// Whether `output` exists and was modified after `source`, for --conditional.
+/// Missing files or modification times mean the output has to be (re)created.
+fn output_is_up_to_date(source: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified());
+    match (modified(source), modified(output)) {
+        (Ok(source_modified), Ok(output_modified)) => output_modified > source_modified,
+        _ => false,
+    }
+}
+
+/// Express `path` relative to `base`, going up with `..` where `path` is outside of `base`
+pub(crate) fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let absolute = |path: &Path| {
+        let path = if path.as_os_str().is_empty() { Path::new(".") } else { path };
+        std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+    };
+    let path = absolute(path);
+    let base = absolute(base);
+
+    let path_components: Vec<_> = path.components().collect();
+    let base_components: Vec<_> = base.components().collect();
+    let common = path_components
+        .iter()
+        .zip(&base_components)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..base_components.len() {
+        relative.push("..");
+    }
+    for component in &path_components[common..] {
+        relative.push(component);
+    }
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+/// Where ffmpeg writes its -progress output, which is read back from the child's piped stdout.
+/// Windows has no /dev/stdout, there ffmpeg's pipe protocol writes to the same stdout handle.
+fn progress_output_arg() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "pipe:1"
+    } else {
+        "/dev/stdout"
+    }
+}
+
+/// Checks if an external tool is available in PATH, returns error with guidance if not.
+/// For ffmpeg, the installed version is also checked against `FFMPEG_MIN_VERSION`.
+fn check_external_tool(tool: &str) -> Result<()> {
+    if which::which(tool).is_err() {
+        anyhow::bail!(
+            "Required external tool '{}' is not installed or not found in your PATH.\n\
+            Please install '{}' and ensure it is available in your system PATH.\n\
+            See the README for installation instructions.",
+            tool, tool
+        );
+    }
+    if tool == "ffmpeg" {
+        check_ffmpeg_version()?;
+        check_ffmpeg_audible_support()?;
+    }
+    Ok(())
+}
+
+/// Oldest ffmpeg release known to support the -audible_key/-audible_iv options
+const FFMPEG_MIN_VERSION: (u32, u32, u32) = (4, 4, 0);
+
+fn check_ffmpeg_version() -> Result<()> {
+    let output = Command::new("ffmpeg")
+        .arg("-version")
+        .output()
+        .context("Failed to execute ffmpeg. Is ffmpeg installed and available in your PATH?")?;
+    let version_output = String::from_utf8_lossy(&output.stdout);
+
+    match parse_ffmpeg_version(&version_output) {
+        Some(version) if version < FFMPEG_MIN_VERSION => anyhow::bail!(
+            "ffmpeg {}.{}.{} is too old. Version {}.{}.{} or newer is required to decrypt .aaxc files.",
+            version.0, version.1, version.2,
+            FFMPEG_MIN_VERSION.0, FFMPEG_MIN_VERSION.1, FFMPEG_MIN_VERSION.2
+        ),
+        Some(version) => info!("Found ffmpeg {}.{}.{}", version.0, version.1, version.2),
+        // Git snapshots report e.g. "N-109389-g0a2b3c4" and can't be compared
+        None => warn!("Could not determine the ffmpeg version, continuing without a version check"),
+    }
+    Ok(())
+}
+
+/// Check that ffmpeg knows the -audible_key option. Builds without it fail every conversion
+/// with "Unrecognized option", which would otherwise only surface as a generic ffmpeg failure.
+fn check_ffmpeg_audible_support() -> Result<()> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-audible_key", "", "-i", null_device(), "-f", "null", null_device()])
+        .output()
+        .context("Failed to execute ffmpeg. Is ffmpeg installed and available in your PATH?")?;
+    let messages = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+
+    if is_unknown_audible_option(&messages) {
+        anyhow::bail!(
+            "Your ffmpeg build does not support the -audible_key option, which is needed to decrypt .aaxc files.\n\
+            Audible support requires an ffmpeg build with Audible decryption, for example:\n\
+            - the ffmpeg-audible package from the AUR (Arch Linux)\n\
+            - ffmpeg from Homebrew/Linuxbrew with Audible support\n\
+            - a recent static build from https://ffmpeg.org/download.html"
+        );
+    }
+    Ok(())
+}
+
+/// Whether ffmpeg's output reports -audible_key as an unknown option
+fn is_unknown_audible_option(messages: &str) -> bool {
+    messages.lines().any(|line| {
+        (line.contains("Unrecognized option") || line.contains("Unknown option")) && line.contains("audible_key")
+    })
+}
+
+/// Parse the version from the first line of `ffmpeg -version`, e.g. "ffmpeg version 4.4.2-0ubuntu0.22.04.1"
+fn parse_ffmpeg_version(output: &str) -> Option<(u32, u32, u32)> {
+    let version = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("ffmpeg version "))?
+        .split_whitespace()
+        .next()?;
+    // Some builds prefix the release with 'n', e.g. "n6.0"
+    let version = version.strip_prefix('n').unwrap_or(version);
+    let numeric_end = version
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(version.len());
+
+    let mut parts = version[..numeric_end].split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().map_or(Some(0), |part| part.parse().ok())?;
+    let patch = parts.next().map_or(Some(0), |part| part.parse().ok())?;
+    Some((major, minor, patch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speed_running_average_and_peak() {
+        let mut progress = ConversionProgress::new(60.0);
+        for line in ["speed=10.0x", "speed=30.0x", "speed=20.0x", "speed=N/A"] {
+            parse_ffmpeg_progress_line(line, &mut progress);
+        }
+        assert_eq!(progress.speed, 20.0);
+        assert_eq!(progress.peak_speed, 30.0);
+        assert_eq!(progress.speed_samples, 3);
+        assert!((progress.average_speed - 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn speed_limit_delay_only_when_ahead() {
+        // 30s of audio after 10s at a 1.5x limit should have taken 20s
+        assert_eq!(speed_limit_delay(30.0, Duration::from_secs(10), 1.5), Some(Duration::from_secs(10)));
+        assert_eq!(speed_limit_delay(30.0, Duration::from_secs(20), 1.5), None);
+        assert_eq!(speed_limit_delay(30.0, Duration::from_secs(25), 1.5), None);
+        assert_eq!(speed_limit_delay(0.0, Duration::ZERO, 2.0), None);
+    }
+
+    #[test]
+    fn progress_line_kinds() {
+        let mut progress = ConversionProgress::new(60.0);
+        assert_eq!(parse_ffmpeg_progress_line("time=00:00:30.500", &mut progress), ProgressLineKind::Updated);
+        assert_eq!(progress.current_time, 30.5);
+        assert_eq!(parse_ffmpeg_progress_line("speed=12.5x", &mut progress), ProgressLineKind::Updated);
+        assert_eq!(parse_ffmpeg_progress_line("speed=N/A", &mut progress), ProgressLineKind::Unknown);
+        assert_eq!(parse_ffmpeg_progress_line("out_time_us=30500000", &mut progress), ProgressLineKind::Unknown);
+        assert_eq!(parse_ffmpeg_progress_line("progress=continue", &mut progress), ProgressLineKind::Continue);
+        assert_eq!(parse_ffmpeg_progress_line("progress=end", &mut progress), ProgressLineKind::End);
+        assert_eq!(parse_ffmpeg_progress_line("progress=paused", &mut progress), ProgressLineKind::Unknown);
+        assert_eq!(parse_ffmpeg_progress_line("", &mut progress), ProgressLineKind::Unknown);
+        assert_eq!(progress.speed, 12.5);
+    }
+
+    #[test]
+    fn parse_ffmpeg_version_release() {
+        let output = "ffmpeg version 5.1.2 Copyright (c) 2000-2022 the FFmpeg developers\nbuilt with gcc 12.2.0";
+        assert_eq!(parse_ffmpeg_version(output), Some((5, 1, 2)));
+    }
+
+    #[test]
+    fn parse_ffmpeg_version_distribution_suffix() {
+        let output = "ffmpeg version 4.4.2-0ubuntu0.22.04.1 Copyright (c) 2000-2021 the FFmpeg developers";
+        assert_eq!(parse_ffmpeg_version(output), Some((4, 4, 2)));
+        let output = "ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023 the FFmpeg developers";
+        assert_eq!(parse_ffmpeg_version(output), Some((6, 1, 1)));
+    }
+
+    #[test]
+    fn parse_ffmpeg_version_short_and_prefixed() {
+        assert_eq!(parse_ffmpeg_version("ffmpeg version 7.0 Copyright (c) 2000-2024"), Some((7, 0, 0)));
+        assert_eq!(parse_ffmpeg_version("ffmpeg version n6.0 Copyright (c) 2000-2023"), Some((6, 0, 0)));
+        assert_eq!(parse_ffmpeg_version("ffmpeg version 4.2.7-0+deb10u1 Copyright"), Some((4, 2, 7)));
+    }
+
+    #[test]
+    fn parse_ffmpeg_version_unknown() {
+        assert_eq!(parse_ffmpeg_version("ffmpeg version N-109389-g0a2b3c4d5e Copyright"), None);
+        assert_eq!(parse_ffmpeg_version("not ffmpeg output"), None);
+        assert_eq!(parse_ffmpeg_version(""), None);
+    }
+
+    #[test]
+    fn ffmpeg_versions_compare_against_minimum() {
+        assert!(parse_ffmpeg_version("ffmpeg version 4.2.7").unwrap() < FFMPEG_MIN_VERSION);
+        assert!(parse_ffmpeg_version("ffmpeg version 4.4").unwrap() >= FFMPEG_MIN_VERSION);
+        assert!(parse_ffmpeg_version("ffmpeg version 10.0.1").unwrap() >= FFMPEG_MIN_VERSION);
+    }
+
+    #[test]
+    fn decryption_args_depend_on_input_format() {
+        let aaxc = InputFormat::Aaxc { key: "k".to_string(), iv: "i".to_string() };
+        assert_eq!(aaxc.apply_decryption(FFmpegArgs::new()).build(), ["-audible_key", "k", "-audible_iv", "i"]);
+        let aax = InputFormat::Aax { activation_bytes: "1a2b3c4d".to_string() };
+        assert_eq!(aax.apply_decryption(FFmpegArgs::new()).build(), ["-activation_bytes", "1a2b3c4d"]);
+    }
+
+    #[test]
+    fn source_quality_summary() {
+        let mut ffprobe = FFProbeFormat::default();
+        ffprobe.format.bit_rate = "64000".to_string();
+        ffprobe.streams = vec![
+            models::Stream { index: 0, codec_type: "video".to_string(), ..Default::default() },
+            models::Stream {
+                index: 1,
+                codec_type: "audio".to_string(),
+                codec_name: Some("aac".to_string()),
+                sample_rate: Some("22050".to_string()),
+                channels: Some(1),
+                bit_rate: None,
+            },
+        ];
+        let mut book = BookInfo::default();
+        book.set_source_audio(&ffprobe);
+        // The stream reports no bit rate, so the container's is used
+        assert_eq!(book.source_bitrate_bps, Some(64000));
+        assert_eq!(book.source_quality(), "aac, 22050 Hz, mono, 64 kbps");
+        assert_eq!(BookInfo::default().source_quality(), "?, ? Hz, ? channels, ? kbps");
+    }
+
+    #[test]
+    fn chapter_number_range_fills_open_ends() {
+        assert_eq!(chapter_number_range(None, None, 10).unwrap(), None);
+        assert_eq!(chapter_number_range(Some(8), None, 10).unwrap(), Some(HashSet::from([8, 9, 10])));
+        assert_eq!(chapter_number_range(None, Some(2), 10).unwrap(), Some(HashSet::from([1, 2])));
+        assert_eq!(chapter_number_range(Some(3), Some(4), 10).unwrap(), Some(HashSet::from([3, 4])));
+        assert!(chapter_number_range(Some(5), Some(4), 10).is_err());
+    }
+
+    #[test]
+    fn chapter_number_range_clamps_to_the_book() {
+        assert_eq!(chapter_number_range(Some(9), Some(4_000_000_000), 10).unwrap(), Some(HashSet::from([9, 10])));
+        assert_eq!(chapter_number_range(None, Some(u64::MAX), 3).unwrap(), Some(HashSet::from([1, 2, 3])));
+        assert_eq!(chapter_number_range(Some(u64::MAX), None, 3).unwrap(), Some(HashSet::new()));
+    }
+
+    #[test]
+    fn chapter_list_numbers_and_ranges() {
+        let list = "# reviewed so far\n3\n\n  10-12 \n5-5\n3\n";
+        assert_eq!(parse_chapter_list(list), Ok(vec![3..=3, 10..=12, 5..=5, 3..=3]));
+        assert_eq!(parse_chapter_list(""), Ok(Vec::new()));
+        assert_eq!(parse_chapter_list("1\n# ok\nseven\n"), Err((3, "'seven' is not a chapter number".to_string())));
+        assert_eq!(parse_chapter_list("0"), Err((1, "'0' is not a chapter number".to_string())));
+        assert_eq!(parse_chapter_list("1\n9-4"), Err((2, "range 9-4 ends before it starts".to_string())));
+    }
+
+    #[test]
+    fn chapter_list_keeps_huge_ranges_unexpanded() {
+        let list = parse_chapter_list(&format!("2-{}\n", usize::MAX)).unwrap();
+        assert_eq!(list, vec![2..=usize::MAX]);
+        assert!(list[0].contains(&5));
+        assert!(!list[0].contains(&1));
+    }
+
+    #[test]
+    fn chapter_time_range_without_milliseconds() {
+        let chapter = MergedChapter::from_flattened(&flattened("Chapter 5", 930_250, 495_500, 5));
+        assert_eq!(chapter_time_range(&chapter), "[00:15:30 - 00:23:45] (08:15)");
+    }
+
+    #[test]
+    fn progress_output_arg_matches_platform() {
+        if cfg!(target_os = "windows") {
+            assert_eq!(progress_output_arg(), "pipe:1");
+        } else {
+            assert_eq!(progress_output_arg(), "/dev/stdout");
+        }
+    }
+
+    #[test]
+    fn detects_missing_audible_support() {
+        assert!(is_unknown_audible_option(
+            "Unrecognized option 'audible_key'.\nError splitting the argument list: Option not found\n"
+        ));
+        assert!(is_unknown_audible_option("Unknown option: audible_key"));
+        assert!(!is_unknown_audible_option("/dev/null: Invalid data found when processing input"));
+        assert!(!is_unknown_audible_option("Unrecognized option 'foo'.\naudible_key set"));
+    }
+
+    fn flattened(title: &str, start_offset_ms: i64, length_ms: i64, chapter_number: usize) -> FlattenedChapter {
+        FlattenedChapter {
+            title: title.to_string(),
+            full_path: title.to_string(),
+            start_offset_ms,
+            length_ms,
+            start_offset_sec: start_offset_ms / 1000,
+            level: 0,
+            chapter_number,
+            global_chapter_number: chapter_number,
+            disc_number: 1,
+            disc_title: String::new(),
+        }
+    }
+
+    #[test]
+    fn benchmark_result_table() {
+        let mut speed = ConversionProgress::new(0.0);
+        for line in ["speed=20.0x", "speed=30.0x"] {
+            parse_ffmpeg_progress_line(line, &mut speed);
+        }
+        let result = BenchmarkResult::new(12, 9 * 3_600_000, &speed, Duration::from_secs(1_230));
+        assert_eq!(
+            result.to_string(),
+            "Benchmark (no output written)\n  Chapters:       12\n  Audio:          9.00 h\n  \
+             Average speed:  25.0x\n  Peak speed:     30.0x\n  Wall time:      00:20:30\n"
+        );
+        let json = ProgressEvent::BenchmarkResult(result).to_json();
+        assert!(json.starts_with(r#"{"type":"benchmark_result","total_chapters":12,"total_audio_hours":9.0,"#), "{}", json);
+    }
+
+    #[test]
+    fn chapter_metadata_from_chapter_and_book() {
+        let chapter = MergedChapter::from_flattened(&flattened("Prologue", 0, 60_000, 3));
+        let mut book = BookInfo { album: "Dune".to_string(), artist: "Frank Herbert".to_string(), ..Default::default() };
+        assert_eq!(
+            chapter_metadata(&chapter, 3, 47, &book),
+            ["title=Prologue", "track=3/47", "album=Dune", "artist=Frank Herbert", "genre=Audiobook"]
+        );
+        book.genre = Some("Science Fiction".to_string());
+        assert_eq!(chapter_metadata(&chapter, 3, 47, &book)[4], "genre=Science Fiction");
+        book.date = Some("2023".to_string());
+        assert_eq!(chapter_metadata(&chapter, 3, 47, &book)[5], "date=2023");
+    }
+
+    #[test]
+    fn chapters_as_ffmetadata() {
+        let mut nested = flattened("Chapter 1", 40_000, 60_000, 2);
+        nested.full_path = format!("Part 1{}Chapter 1", models::CHAPTER_PATH_SEPARATOR);
+        let metadata = ffmetadata_chapters(&[flattened("Intro; a=b #1", 0, 40_000, 1), nested]);
+        assert_eq!(
+            metadata,
+            ";FFMETADATA1\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=40000\ntitle=Intro\\; a\\=b \\#1\n\
+             [CHAPTER]\nTIMEBASE=1/1000\nSTART=40000\nEND=100000\ntitle=Part 1 > Chapter 1\n"
+        );
+    }
+
+    #[test]
+    fn parses_silencedetect_output() {
+        let stderr = "[silencedetect @ 0x55d5c8] silence_start: 1.2345\n\
+                      size=N/A time=00:00:02.00 bitrate=N/A\n\
+                      [silencedetect @ 0x55d5c8] silence_end: 2.1 | silence_duration: 0.8655\n\
+                      [silencedetect @ 0x55d5c8] silence_start: 3.5\n";
+        assert_eq!(parse_silencedetect(stderr, 4_000), vec![(1_235, 2_100), (3_500, 4_000)]);
+        assert!(parse_silencedetect("", 4_000).is_empty());
+    }
+
+    #[test]
+    fn parses_embedded_ffprobe_chapters() {
+        let output = r#"{"chapters": [
+            {"id": 0, "time_base": "1/1000", "start": 0, "start_time": "0.000000", "end": 40123, "end_time": "40.123456", "tags": {"title": "Opening Credits"}},
+            {"id": 1, "time_base": "1/1000", "start": 40123, "start_time": "40.123456", "end": 100000, "end_time": "100.000000", "tags": {"title": " "}},
+            {"id": 2, "time_base": "1/1000", "start": 100000, "start_time": "100.000000", "end": 110000, "end_time": "110.000000"}
+        ]}"#;
+        let chapters = parse_ffprobe_chapters(output).unwrap();
+        let spans: Vec<(&str, i64, i64, usize)> = chapters
+            .iter()
+            .map(|chapter| (chapter.title.as_str(), chapter.start_offset_ms, chapter.length_ms, chapter.chapter_number))
+            .collect();
+        assert_eq!(spans, [("Opening Credits", 0, 40_123, 1), ("Chapter 2", 40_123, 59_877, 2), ("Chapter 3", 100_000, 10_000, 3)]);
+        assert_eq!(chapters[1].start_offset_sec, 40);
+
+        let invalid = r#"{"chapters": [{"start_time": "soon", "end_time": "1.0"}]}"#;
+        let error = parse_ffprobe_chapters(invalid).unwrap_err();
+        assert_eq!(error.to_string(), "Embedded chapter 1: invalid start_time 'soon'");
+        assert!(parse_ffprobe_chapters(r#"{"chapters": []}"#).unwrap().is_empty());
+    }
+
+    #[test]
+    fn estimates_output_size_from_bitrate() {
+        // 128 kbps is 16 kB per second
+        assert_eq!(estimated_output_bytes(128_000, 60_000), 960_000);
+        assert_eq!(estimated_output_bytes(96_000, 0), 0);
+        assert_eq!(estimated_output_bytes(96_000, -1_000), 0);
+    }
+
+    #[test]
+    fn large_split_prompt_states_size_and_time() {
+        assert_eq!(
+            large_split_prompt(347, 7_200_000, Some(115_200_000), Some(4.0)),
+            "This will create 347 files from 02:00:00 of audio (estimated disk usage: 109.9 MB, estimated time: at least 00:30:00). Continue? [y/N] "
+        );
+        assert!(large_split_prompt(347, 7_200_000, None, None).contains("(estimated disk usage: unknown, estimated time: unknown)"));
+    }
+
+    #[test]
+    fn estimated_bitrate_follows_quality_settings() {
+        use crate::cli::{FlacFormat, Mp3Format, OutputFormat, WavFormat};
+        assert_eq!(Mp3Format { quality: None }.estimated_bitrate_bps(Some(22_050), Some(1)), Some(128_000));
+        assert_eq!(Mp3Format { quality: Some(2) }.estimated_bitrate_bps(Some(22_050), Some(1)), None);
+        assert_eq!(WavFormat.estimated_bitrate_bps(Some(22_050), Some(2)), Some(705_600));
+        assert_eq!(WavFormat.estimated_bitrate_bps(None, Some(2)), None);
+        assert_eq!(FlacFormat.estimated_bitrate_bps(Some(22_050), Some(2)), None);
+    }
+
+    #[test]
+    fn splits_at_the_middle_of_silences() {
+        let silences = [(0, 1_500), (60_000, 63_000), (120_000, 122_000), (178_000, 180_000)];
+        let parts = silence_chapters(&silences, 180_000, false);
+        let spans: Vec<(&str, i64, i64)> =
+            parts.iter().map(|part| (part.title.as_str(), part.start_offset_ms, part.length_ms)).collect();
+        assert_eq!(spans, [("Part 1", 0, 61_500), ("Part 2", 61_500, 59_500), ("Part 3", 121_000, 59_000)]);
+        assert_eq!(parts[2].chapter_number, 3);
+
+        let titles: Vec<String> = silence_chapters(&silences, 180_000, true).into_iter().map(|part| part.title).collect();
+        assert_eq!(titles, ["00:00:00", "00:01:01", "00:02:01"]);
+        assert_eq!(silence_chapters(&[], 180_000, false).len(), 1);
+    }
+
+    #[test]
+    fn silent_boundaries_within_tolerance() {
+        let silences = [(1_235, 2_100)];
+        assert!(is_silent_boundary(&silences, 2_000));
+        assert!(is_silent_boundary(&silences, 2_300));
+        assert!(is_silent_boundary(&silences, 1_035));
+        assert!(!is_silent_boundary(&silences, 2_301));
+        assert!(!is_silent_boundary(&[], 2_000));
+    }
+
+    #[test]
+    fn merges_chapters_across_boundaries_without_silence() {
+        let chapters = vec![
+            flattened("One", 0, 60_000, 1),
+            flattened("Two", 60_000, 30_000, 2),
+            flattened("Three", 90_000, 30_000, 3),
+            flattened("Four", 120_000, 30_000, 4),
+        ];
+        let merged = merge_on_silence(&chapters, &[true, false, false]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].merged_chapters, vec!["One"]);
+        assert_eq!(merged[1].merged_chapters, vec!["Two", "Three", "Four"]);
+        assert_eq!((merged[1].start_offset_ms, merged[1].length_ms), (60_000, 90_000));
+    }
+
+    #[test]
+    fn chapter_timeline_gaps_overlaps_and_runtime() {
+        let chapters = vec![
+            flattened("One", 0, 60_000, 1),
+            flattened("Two", 62_000, 30_000, 2),
+            flattened("Three", 91_000, 30_000, 3),
+        ];
+        let (errors, warnings) = chapter_timeline_issues(&chapters, 121_000);
+        assert_eq!(errors, ["\"Two\" overlaps \"Three\" by 1000 ms"]);
+        assert_eq!(warnings, ["Gap of 2000 ms between \"One\" and \"Two\""]);
+
+        let (errors, _) = chapter_timeline_issues(&chapters[..1], 50_000);
+        assert_eq!(errors, ["Chapters end at 00:01:00.000, after the book's runtime of 00:00:50.000"]);
+        let (errors, warnings) = chapter_timeline_issues(&chapters[1..2], 100_000);
+        assert!(errors.is_empty());
+        assert_eq!(warnings, [
+            "\"Two\" starts at 00:01:02.000, the audio before it is in no chapter",
+            "Chapters end at 00:01:32.000, 8000 ms before the book's runtime of 00:01:40.000",
+        ]);
+        assert_eq!(chapter_timeline_issues(&[], 100_000), (vec![], vec![]));
+    }
+
+    #[test]
+    fn chapter_time_offset_moves_chapters_and_clamps_at_zero() {
+        let mut chapters = vec![flattened("One", 0, 60_000, 1), flattened("Two", 60_000, 30_000, 2)];
+        offset_chapters(&mut chapters, 3_000);
+        let times = |chapters: &[FlattenedChapter]| -> Vec<(i64, i64, i64)> {
+            chapters.iter().map(|chapter| (chapter.start_offset_ms, chapter.length_ms, chapter.start_offset_sec)).collect()
+        };
+        assert_eq!(times(&chapters), [(3_000, 60_000, 3), (63_000, 30_000, 63)]);
+
+        // Too early by more than the first chapter's start: it starts at 0 and keeps its end
+        offset_chapters(&mut chapters, -5_000);
+        assert_eq!(times(&chapters), [(0, 58_000, 0), (58_000, 30_000, 58)]);
+        offset_chapters(&mut chapters, -100_000);
+        assert_eq!(times(&chapters), [(0, 0, 0), (0, 0, 0)]);
+    }
+
+    #[test]
+    fn threads_auto_is_capped_and_numbers_pass_through() {
+        assert_eq!(ffmpeg_threads("4"), "4");
+        assert_eq!(ffmpeg_threads("0"), "0");
+        let auto: usize = ffmpeg_threads("auto").parse().unwrap();
+        assert!((1..=MAX_AUTO_THREADS).contains(&auto), "{}", auto);
+        assert_eq!(ffmpeg_threads("AUTO"), auto.to_string());
+    }
+
+    #[test]
+    fn strips_title_prefix() {
+        let pattern = regex::Regex::new(r"^Chapter \d+:?\s*").unwrap();
+        assert_eq!(strip_title_prefix("Chapter 1: Introduction", &pattern), "Introduction");
+        assert_eq!(strip_title_prefix("Chapter 12 The End", &pattern), "The End");
+        assert_eq!(strip_title_prefix("Prologue", &pattern), "Prologue");
+        // Nothing would be left of the title
+        assert_eq!(strip_title_prefix("Chapter 3", &pattern), "Chapter 3");
+    }
+
+    #[test]
+    fn duplicate_output_paths_are_reported() {
+        let chapters: Vec<MergedChapter> = [("Interlude", 1), ("Chapter 1", 2), ("Interlude", 3), ("Interlude", 4)]
+            .iter()
+            .map(|(title, number)| MergedChapter::from_flattened(&flattened(title, 0, 1000, *number)))
+            .collect();
+        let title_only = |chapter: &MergedChapter| PathBuf::from(format!("{}.mp3", chapter.title));
+        assert_eq!(check_unique_filenames(&chapters, title_only), Err(vec!["Interlude.mp3 (chapters 1, 3, 4)".to_string()]));
+
+        let numbered = |chapter: &MergedChapter| PathBuf::from(format!("{:02}_{}.mp3", chapter.chapter_number, chapter.title));
+        assert_eq!(check_unique_filenames(&chapters, numbered), Ok(()));
+    }
+
+    #[test]
+    fn audio_filter_chain() {
+        assert_eq!(build_audio_filter(&ConversionFlags::default()), None);
+        let flags = ConversionFlags { audio_filter: Some(" loudnorm,atempo=1.25, ".to_string()), ..Default::default() };
+        assert_eq!(build_audio_filter(&flags).as_deref(), Some("loudnorm,atempo=1.25"));
+        assert_eq!(audio_filter_args(&flags), ["-af", "loudnorm,atempo=1.25"]);
+        let flags = ConversionFlags { audio_filter: Some("  ".to_string()), ..Default::default() };
+        assert!(audio_filter_args(&flags).is_empty());
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+/// Errors returned by [`convert`](crate::convert::convert) and the other subcommands.
+///
+/// Each variant has its own process exit code (see [`AudibleUtilError::exit_code`]), so scripts
+/// and tests can tell failures apart without matching on the message. Anything not modeled here
+/// ends up in `Other` and exits with 1.
+#[derive(Debug, Error)]
+pub enum AudibleUtilError {
+    /// The input file doesn't exist
+    #[error("Input file does not exist: {}. Please provide a valid .aaxc file.", .0.display())]
+    InputFileNotFound(PathBuf),
+
+    /// The input file exists but can't be read
+    #[error("Input file is not readable: {}. Please check file permissions.", .0.display())]
+    InputFileNotReadable(PathBuf),
+
+    /// The voucher file doesn't exist
+    #[error("Voucher file does not exist: {}. Please provide a valid voucher file or use --voucher-path.", .0.display())]
+    VoucherFileNotFound(PathBuf),
+
+    /// The voucher file exists but can't be read
+    #[error("Voucher file is not readable: {}. Please check file permissions.", .0.display())]
+    VoucherFileNotReadable(PathBuf),
+
+    /// The voucher file isn't valid JSON or doesn't match [`AudibleCliVoucher`](crate::models::AudibleCliVoucher)
+    #[error("Failed to parse voucher file: {}. Please ensure it is a valid JSON file generated by audible-cli.", path.display())]
+    VoucherParseError {
+        /// Path of the voucher file
+        path: PathBuf,
+        /// Error from serde_json
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// The voucher parsed but has missing or invalid fields, one message per problem
+    #[error("Invalid voucher:\n  - {}", .0.join("\n  - "))]
+    VoucherValidationError(Vec<String>),
+
+    /// The chapters file doesn't exist
+    #[error("Chapter file does not exist: {}. Please provide a chapters.json file, use --chapters-from-ffprobe, or disable --split.", .0.display())]
+    ChapterFileNotFound(PathBuf),
+
+    /// The chapters parsed but have missing or invalid fields, one message per problem
+    #[error("Invalid chapter data:\n  - {}", .0.join("\n  - "))]
+    ChapterValidationError(Vec<String>),
+
+    /// --check-chapters found problems in the chapters file
+    #[error("Chapter file is not valid: {}", .0.display())]
+    ChapterReportInvalid(PathBuf),
+
+    /// --check-chapters couldn't parse the chapters file
+    #[error("Chapter file could not be parsed: {}", .0.display())]
+    ChapterReportUnparsable(PathBuf),
+
+    /// ffmpeg exited unsuccessfully
+    #[error("ffmpeg failed with exit code {exit_code}. Please check your input files and decryption key and try again.")]
+    FfmpegFailed {
+        /// ffmpeg's exit code, -1 when it was killed by a signal
+        exit_code: i32,
+    },
+
+    /// Any other error
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl AudibleUtilError {
+    /// Process exit code for this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // validate-chapters keeps to the plain 0/1/2 convention for scripts
+            AudibleUtilError::Other(_) | AudibleUtilError::ChapterReportInvalid(_) => 1,
+            AudibleUtilError::ChapterReportUnparsable(_) => 2,
+            AudibleUtilError::InputFileNotFound(_) | AudibleUtilError::InputFileNotReadable(_) => 3,
+            AudibleUtilError::VoucherFileNotFound(_)
+            | AudibleUtilError::VoucherFileNotReadable(_)
+            | AudibleUtilError::VoucherParseError { .. }
+            | AudibleUtilError::VoucherValidationError(_) => 4,
+            AudibleUtilError::ChapterFileNotFound(_) | AudibleUtilError::ChapterValidationError(_) => 5,
+            AudibleUtilError::FfmpegFailed { .. } => 6,
+        }
+    }
+}
+
+/// Helpers still return `anyhow::Result`; recover typed errors they raised, wrap everything else
+impl From<anyhow::Error> for AudibleUtilError {
+    fn from(error: anyhow::Error) -> Self {
+        error.downcast::<AudibleUtilError>().unwrap_or_else(AudibleUtilError::Other)
+    }
+}
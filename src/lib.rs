@@ -0,0 +1,56 @@
+//! Library API of audible-util, the chapter handling and file formats behind the
+//! `audible-util` binary.
+//!
+//! ```no_run
+//! use audible_util::models::AudibleChapters;
+//!
+//! let json = std::fs::read_to_string("book-chapters.json")?;
+//! let chapters: AudibleChapters = serde_json::from_str(&json)?;
+//! let flattened = audible_util::flatten_chapters(&chapters);
+//! for chapter in audible_util::merge_short_chapters(&flattened, 10_000) {
+//!     println!("{} ({} ms)", chapter.title, chapter.length_ms);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+#![warn(missing_docs)]
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::cli::MergeDirection;
+use crate::models::{AudibleChapters, FFProbeFormat, FlattenedChapter, MergedChapter};
+
+/// Value types of the command line options and the output formats they select
+pub mod cli;
+/// Converting a book with ffmpeg, the pipeline behind the binary
+pub mod convert;
+/// Errors of a conversion, each with its own exit code
+pub mod error;
+/// Chapter, voucher and ffprobe file formats
+pub mod models;
+/// Helpers for file names, ffmpeg arguments, archives and chapter grouping
+pub mod util;
+
+mod progress;
+
+/// How long [`ffprobe`] waits, the binary's default `--ffprobe-timeout`
+pub const DEFAULT_FFPROBE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Probe an .aaxc file's format, streams and tags with ffprobe, which must be in `PATH`.
+pub fn ffprobe(path: &Path) -> Result<FFProbeFormat> {
+    util::probe::ffprobe(path, DEFAULT_FFPROBE_TIMEOUT)
+}
+
+/// Flatten the book's chapter tree into its leaf chapters, numbered through the whole book.
+pub fn flatten_chapters(chapters: &AudibleChapters) -> Vec<FlattenedChapter> {
+    chapters.content_metadata.chapter_info.flatten(false)
+}
+
+/// Merge chapters shorter than `min_ms` into the next chapter, or the previous one at the end
+/// of the book.
+pub fn merge_short_chapters(chapters: &[FlattenedChapter], min_ms: i64) -> Vec<MergedChapter> {
+    util::grouping::merge_short_chapters(chapters, min_ms, MergeDirection::Next)
+}
@@ -0,0 +1,86 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use log::{Log, Metadata, Record};
+
+use audible_util::cli::LogFileFormat;
+
+/// Logger writing to stderr through env_logger and, optionally, to a log file.
+/// Both outputs use the level configured with RUST_LOG.
+struct Logger {
+    stderr: env_logger::Logger,
+    file: Option<(Mutex<File>, LogFileFormat)>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.stderr.matches(record) {
+            return;
+        }
+        self.stderr.log(record);
+
+        if let Some((file, format)) = &self.file {
+            let timestamp = chrono::Utc::now();
+            let module = record.module_path().unwrap_or_default();
+            let line = match format {
+                LogFileFormat::Text => format!(
+                    "[{} {:<5} {}] {}",
+                    timestamp.format("%Y-%m-%dT%H:%M:%SZ"),
+                    record.level(),
+                    module,
+                    record.args()
+                ),
+                LogFileFormat::Json => serde_json::json!({
+                    "level": record.level().as_str(),
+                    "timestamp": timestamp.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                    "message": record.args().to_string(),
+                    "module": module,
+                })
+                .to_string(),
+            };
+            // Logging must never abort a conversion, write errors are ignored
+            if let Ok(mut file) = file.lock() {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+        if let Some((file, _)) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Initialize logging to stderr and, with `log_file`, append log events to that file as well
+pub fn init(log_file: Option<&Path>, format: LogFileFormat) -> Result<()> {
+    let stderr = env_logger::Builder::from_default_env().build();
+    let max_level = stderr.filter();
+
+    let file = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file: {}", path.display()))?;
+            Some((Mutex::new(file), format))
+        }
+        None => None,
+    };
+
+    log::set_boxed_logger(Box::new(Logger { stderr, file }))
+        .context("Failed to initialize logging")?;
+    log::set_max_level(max_level);
+    Ok(())
+}
@@ -1,217 +1,363 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use super::validation::{ValidationResult, Validator};
+use super::FFProbeChapter;
+use crate::util::filename::{sanitize_title, SanitizeConfig};
+use std::collections::HashMap;
+use std::fmt;
 use std::path::{Path, PathBuf};
 
 /// Deserialize chapters information with recursive structure to handle unlimited nesting levels
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AudibleChapters {
+    /// Chapters and the book they belong to
     #[serde(rename = "content_metadata")]
     pub content_metadata: ContentMetadata,
+    /// API response groups audible-cli requested
     #[serde(rename = "response_groups")]
     pub response_groups: Vec<String>,
 }
 
 impl AudibleChapters {
-    pub fn validate(&self) -> Result<(), String> {
-        self.content_metadata.validate().map_err(|e| format!("content_metadata: {}", e))?;
-        if self.response_groups.is_empty() {
-            return Err("response_groups is empty".to_string());
-        }
-        Ok(())
+    /// Every problem in the file, prefixed with the path of the field
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        validator.nested("content_metadata", self.content_metadata.validate());
+        if self.response_groups.is_empty() { validator.error("response_groups is empty"); }
+        validator.finish()
     }
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Metadata section of the chapters file
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ContentMetadata {
+    /// The chapter tree and runtime
     #[serde(rename = "chapter_info")]
     pub chapter_info: ChapterInfo,
+    /// Identity of the book file
     #[serde(rename = "content_reference")]
     pub content_reference: ContentReference,
+    /// Where listening last stopped
     #[serde(rename = "last_position_heard")]
     pub last_position_heard: LastPositionHeard,
 }
 
 impl ContentMetadata {
-    pub fn validate(&self) -> Result<(), String> {
-        self.chapter_info.validate().map_err(|e| format!("chapter_info: {}", e))?;
-        self.content_reference.validate().map_err(|e| format!("content_reference: {}", e))?;
-        self.last_position_heard.validate().map_err(|e| format!("last_position_heard: {}", e))?;
-        Ok(())
+    /// Problems in the chapter info, content reference and listening position
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        validator.nested("chapter_info", self.chapter_info.validate());
+        validator.nested("content_reference", self.content_reference.validate());
+        validator.nested("last_position_heard", self.last_position_heard.validate());
+        validator.finish()
     }
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// The chapter tree with the book's runtime
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ChapterInfo {
+    /// Length of the Audible intro at the start of the book
     #[serde(rename = "brandIntroDurationMs")]
     pub brand_intro_duration_ms: i64,
+    /// Length of the Audible outro at the end of the book
     #[serde(rename = "brandOutroDurationMs")]
     pub brand_outro_duration_ms: i64,
+    /// Top-level chapters, or parts containing chapters
     pub chapters: Vec<ChapterNode>,
+    /// Whether Audible considers the chapter offsets accurate
     #[serde(rename = "is_accurate")]
     pub is_accurate: bool,
+    /// Length of the book in ms
     #[serde(rename = "runtime_length_ms")]
     pub runtime_length_ms: i64,
+    /// Length of the book in whole seconds
     #[serde(rename = "runtime_length_sec")]
     pub runtime_length_sec: i64,
 }
 
 impl ChapterInfo {
-    pub fn validate(&self) -> Result<(), String> {
-        if self.brand_intro_duration_ms < 0 { return Err("brand_intro_duration_ms is negative".to_string()); }
-        if self.brand_outro_duration_ms < 0 { return Err("brand_outro_duration_ms is negative".to_string()); }
-        if self.runtime_length_ms <= 0 { return Err("runtime_length_ms is not positive".to_string()); }
-        if self.runtime_length_sec <= 0 { return Err("runtime_length_sec is not positive".to_string()); }
+    /// Problems in the runtime and the chapter tree
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        if self.brand_intro_duration_ms < 0 { validator.error("brand_intro_duration_ms is negative"); }
+        if self.brand_outro_duration_ms < 0 { validator.error("brand_outro_duration_ms is negative"); }
+        if self.runtime_length_ms <= 0 { validator.error("runtime_length_ms is not positive"); }
+        if self.runtime_length_sec <= 0 { validator.error("runtime_length_sec is not positive"); }
+        if !self.is_accurate { validator.warning("is_accurate is false, chapter boundaries may be imprecise"); }
         for (i, chapter) in self.chapters.iter().enumerate() {
-            chapter.validate().map_err(|e| format!("chapters[{}]: {}", i, e))?;
+            validator.nested(&format!("chapters[{}]", i), chapter.validate());
         }
-        Ok(())
+        validator.finish()
+    }
+}
+
+impl ChapterInfo {
+    /// Flatten all chapters, numbered with a single global counter.
+    ///
+    /// When the book is divided into parts (top-level chapters with children), each chapter's
+    /// `disc_number` and `disc_title` are the part it descends from; otherwise every chapter is on
+    /// disc 1 with an empty `disc_title`.
+    /// With `reset_per_part`, `chapter_number` restarts at 1 in each part; `global_chapter_number`
+    /// is always the position in the whole book. Books without parts are numbered globally either way.
+    pub fn flatten(&self, reset_per_part: bool) -> Vec<FlattenedChapter> {
+        let has_parts = self.chapters.iter().any(|chapter| !chapter.chapters.is_empty());
+        let mut result = Vec::new();
+        let mut chapter_counter = 1;
+
+        for (index, chapter) in self.chapters.iter().enumerate() {
+            let first = result.len();
+            if has_parts && reset_per_part {
+                chapter_counter = 1;
+            }
+            result.extend(ChapterIter::new(chapter, chapter_counter));
+            chapter_counter += result.len() - first;
+            if has_parts {
+                for flattened in &mut result[first..] {
+                    flattened.disc_number = index + 1;
+                    flattened.disc_title = chapter.title.clone();
+                }
+            }
+        }
+        for (index, flattened) in result.iter_mut().enumerate() {
+            flattened.global_chapter_number = index + 1;
+        }
+        result
     }
 }
 
 /// Recursive chapter structure that can handle unlimited nesting levels
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ChapterNode {
+    /// Length of the chapter in ms, including its sub-chapters
     #[serde(rename = "length_ms")]
     pub length_ms: i64,
+    /// Start of the chapter from the beginning of the book, in ms
     #[serde(rename = "start_offset_ms")]
     pub start_offset_ms: i64,
+    /// Start of the chapter in whole seconds
     #[serde(rename = "start_offset_sec")]
     pub start_offset_sec: i64,
+    /// Chapter title
     pub title: String,
+    /// Sub-chapters, empty for a leaf chapter
     #[serde(default)]
     pub chapters: Vec<ChapterNode>,
 }
 
 impl ChapterNode {
-    pub fn validate(&self) -> Result<(), String> {
-        if self.title.trim().is_empty() { return Err("title is empty".to_string()); }
-        if self.length_ms <= 0 { return Err("length_ms is not positive".to_string()); }
-        if self.start_offset_ms < 0 { return Err("start_offset_ms is negative".to_string()); }
-        if self.start_offset_sec < 0 { return Err("start_offset_sec is negative".to_string()); }
+    /// Problems in this chapter and its sub-chapters
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        if self.title.trim().is_empty() { validator.error("title is empty"); }
+        // Container nodes may carry no time of their own, it's encoded in their children
+        if self.chapters.is_empty() && self.length_ms <= 0 { validator.error("length_ms is not positive"); }
+        if self.length_ms < 0 { validator.error("length_ms is negative"); }
+        if self.start_offset_ms < 0 { validator.error("start_offset_ms is negative"); }
+        if self.start_offset_sec < 0 { validator.error("start_offset_sec is negative"); }
         for (i, chapter) in self.chapters.iter().enumerate() {
-            chapter.validate().map_err(|e| format!("chapters[{}]: {}", i, e))?;
+            validator.nested(&format!("chapters[{}]", i), chapter.validate());
         }
-        Ok(())
+        validator.finish()
     }
 
-    /// Flatten the hierarchical chapter structure into a flat list
-    pub fn flatten(&self) -> Vec<FlattenedChapter> {
-        let mut result = Vec::new();
-        let mut chapter_counter = 1;
-        
-        self.flatten_recursive(&mut result, &mut chapter_counter, String::new(), 0);
-        result
+    /// Iterate over the hierarchical chapter structure as flattened chapters, depth first,
+    /// numbered from 1. `ChapterInfo::flatten` builds on the same iterator.
+    pub fn flatten(&self) -> ChapterIter<'_> {
+        ChapterIter::new(self, 1)
     }
-    
-    pub fn flatten_recursive(
-        &self, 
-        result: &mut Vec<FlattenedChapter>,
-        counter: &mut usize,
-        parent_path: String,
-        level: usize
-    ) {
+
+    fn full_path(&self, parent_path: &str) -> String {
+        if parent_path.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{}{}{}", parent_path, CHAPTER_PATH_SEPARATOR, self.title)
+        }
+    }
+
+    /// This node as a flattened chapter, `None` for containers without content of their own
+    fn to_flattened(&self, parent_path: &str, level: usize, chapter_number: usize) -> Option<FlattenedChapter> {
+        // A parent with length_ms <= 0 is only a container, its time is covered by its children
+        if !self.chapters.is_empty() && self.length_ms <= 0 {
+            return None;
+        }
+
         // Build full path
-        let full_path = if parent_path.is_empty() {
+        let full_path = self.full_path(parent_path);
+
+        // Create a hierarchical title that includes parent context
+        let hierarchical_title = if parent_path.is_empty() {
             self.title.clone()
         } else {
-            format!("{} > {}", parent_path, self.title)
+            // Convert "Part One: Empire > Chapter 1" to "Part_One_Empire_Chapter_1"
+            let path_parts: Vec<&str> = full_path.split(CHAPTER_PATH_SEPARATOR).collect();
+            path_parts.join("_")
+                .replace(":", "")
+                .replace(" ", "_")
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+                .collect()
         };
-        
-        // Add this chapter if it's a leaf chapter (no children)
-        // Parent chapters with children are handled by their sub-chapters
-        if self.chapters.is_empty() {
-            // Create a hierarchical title that includes parent context
-            let hierarchical_title = if parent_path.is_empty() {
-                self.title.clone()
-            } else {
-                // Convert "Part One: Empire > Chapter 1" to "Part_One_Empire_Chapter_1"
-                let path_parts: Vec<&str> = full_path.split(" > ").collect();
-                path_parts.join("_")
-                    .replace(":", "")
-                    .replace(" ", "_")
-                    .chars()
-                    .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
-                    .collect()
-            };
-            
-            result.push(FlattenedChapter {
-                title: hierarchical_title,
-                full_path: full_path.clone(),
-                start_offset_ms: self.start_offset_ms,
-                length_ms: self.length_ms,
-                start_offset_sec: self.start_offset_sec,
-                level,
-                chapter_number: *counter,
-            });
-            *counter += 1;
-        } else if self.length_ms > 0 {
-            // This is a parent chapter with its own content - add it as a content chapter
-            // but don't include it in the hierarchical path for its children
-            // The parent chapter should be placed in its own directory
-            let hierarchical_title = if parent_path.is_empty() {
-                self.title.clone()
-            } else {
-                // Convert "Part One: Empire > Chapter 1" to "Part_One_Empire_Chapter_1"
-                let path_parts: Vec<&str> = full_path.split(" > ").collect();
-                path_parts.join("_")
-                    .replace(":", "")
-                    .replace(" ", "_")
-                    .chars()
-                    .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
-                    .collect()
-            };
-            
-            // For parent chapters with content, we need to create a special full_path
-            // that will place them in their own directory
-            // The parent chapter should be treated as if it has no parent path
-            // so it gets placed in its own directory
-            let parent_full_path = self.title.clone();
-            
-            result.push(FlattenedChapter {
-                title: hierarchical_title,
-                full_path: parent_full_path,
-                start_offset_ms: self.start_offset_ms,
-                length_ms: self.length_ms,
-                start_offset_sec: self.start_offset_sec,
-                level,
-                chapter_number: *counter,
-            });
-            *counter += 1;
+
+        // A parent chapter with its own content is placed in its own directory,
+        // as if it had no parent path
+        let full_path = if self.chapters.is_empty() { full_path } else { self.title.clone() };
+
+        Some(FlattenedChapter {
+            title: hierarchical_title,
+            full_path,
+            start_offset_ms: self.start_offset_ms,
+            length_ms: self.length_ms,
+            start_offset_sec: self.start_offset_sec,
+            level,
+            chapter_number,
+            global_chapter_number: chapter_number,
+            disc_number: 1,
+            disc_title: String::new(),
+        })
+    }
+}
+
+/// Lazy depth-first iterator over a chapter tree. Containers without content of their own are
+/// skipped, their children are always visited.
+pub struct ChapterIter<'a> {
+    /// Nodes still to visit, with their parent path and level
+    stack: Vec<(&'a ChapterNode, String, usize)>,
+    next_chapter_number: usize,
+}
+
+impl<'a> ChapterIter<'a> {
+    /// Iterate over `root` and its descendants, numbering chapters from `first_chapter_number`
+    pub fn new(root: &'a ChapterNode, first_chapter_number: usize) -> Self {
+        Self {
+            stack: vec![(root, String::new(), 0)],
+            next_chapter_number: first_chapter_number,
         }
-        
-        // Recursively process children
-        for child in &self.chapters {
-            child.flatten_recursive(result, counter, full_path.clone(), level + 1);
+    }
+}
+
+impl Iterator for ChapterIter<'_> {
+    type Item = FlattenedChapter;
+
+    fn next(&mut self) -> Option<FlattenedChapter> {
+        while let Some((node, parent_path, level)) = self.stack.pop() {
+            // Children go on the stack in reverse so the first child is visited next
+            let full_path = node.full_path(&parent_path);
+            for child in node.chapters.iter().rev() {
+                self.stack.push((child, full_path.clone(), level + 1));
+            }
+
+            if let Some(chapter) = node.to_flattened(&parent_path, level, self.next_chapter_number) {
+                self.next_chapter_number += 1;
+                return Some(chapter);
+            }
         }
+        None
     }
 }
 
+/// Separates the hierarchy levels in `full_path`. Titles can't contain this control
+/// character, unlike " > ", so splitting the path back into levels is unambiguous.
+pub const CHAPTER_PATH_SEPARATOR: &str = "\x1F";
+
+/// `full_path` as shown to users, e.g. "Part 1 > Chapter 01"
+pub fn display_chapter_path(full_path: &str) -> String {
+    full_path.replace(CHAPTER_PATH_SEPARATOR, " > ")
+}
+
 /// A flattened chapter with metadata for file generation
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Chapters order by their position in the book, `start_offset_ms` then `length_ms`; the other
+/// fields only break ties, so the order agrees with `==`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FlattenedChapter {
+    /// Chapter title
     pub title: String,
-    pub full_path: String,        // e.g., "Part 1 > Chapter 01"
+    /// Titles joined by `CHAPTER_PATH_SEPARATOR`, displayed as "Part 1 > Chapter 01"
+    pub full_path: String,
+    /// Start from the beginning of the book, in ms
     pub start_offset_ms: i64,
+    /// Length in ms
     pub length_ms: i64,
+    /// Start in whole seconds
     pub start_offset_sec: i64,
-    pub level: usize,             // How deep in the hierarchy
-    pub chapter_number: usize,    // Sequential number for naming (starts from 1)
+    /// How deep in the hierarchy, 0 for top-level chapters
+    pub level: usize,
+    /// Sequential number for naming (starts from 1)
+    pub chapter_number: usize,
+    /// Position in the whole book, even when numbering restarts per part
+    pub global_chapter_number: usize,
+    /// Top-level part the chapter belongs to (starts from 1)
+    pub disc_number: usize,
+    /// Title of that part, empty when the book has no parts
+    pub disc_title: String,
+}
+
+impl Ord for FlattenedChapter {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let key = |chapter: &Self| {
+            (
+                chapter.start_offset_ms,
+                chapter.length_ms,
+                chapter.global_chapter_number,
+                chapter.chapter_number,
+                chapter.disc_number,
+                chapter.level,
+                chapter.start_offset_sec,
+            )
+        };
+        key(self)
+            .cmp(&key(other))
+            .then_with(|| self.full_path.cmp(&other.full_path))
+            .then_with(|| self.title.cmp(&other.title))
+            .then_with(|| self.disc_title.cmp(&other.disc_title))
+    }
+}
+
+impl PartialOrd for FlattenedChapter {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for FlattenedChapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&display_chapter_path(&self.full_path))
+    }
 }
 
 /// Represents a chapter that may have been merged with previous short chapters
 #[derive(Debug, Clone, PartialEq)]
 pub struct MergedChapter {
+    /// Title of the chapter the short ones were merged into
     pub title: String,
+    /// Titles joined by `CHAPTER_PATH_SEPARATOR`
     pub full_path: String,
+    /// Start of the first merged chapter, in ms
     pub start_offset_ms: i64,
+    /// Combined length in ms
     pub length_ms: i64,
+    /// Start in whole seconds
     pub start_offset_sec: i64,
+    /// How deep in the hierarchy
     pub level: usize,
+    /// Sequential number for naming (starts from 1)
     pub chapter_number: usize,
-    pub merged_chapters: Vec<String>, // Titles of chapters that were merged into this one
+    /// Top-level part the chapter belongs to (starts from 1)
+    pub disc_number: usize,
+    /// Title of that part, empty when the book has no parts
+    pub disc_title: String,
+    /// Titles of chapters that were merged into this one
+    pub merged_chapters: Vec<String>,
+}
+
+impl fmt::Display for MergedChapter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&display_chapter_path(&self.full_path))
+    }
 }
 
 impl MergedChapter {
@@ -225,16 +371,19 @@ impl MergedChapter {
             start_offset_sec: chapter.start_offset_sec,
             level: chapter.level,
             chapter_number: chapter.chapter_number,
+            disc_number: chapter.disc_number,
+            disc_title: chapter.disc_title.clone(),
             merged_chapters: vec![chapter.title.clone()],
         }
     }
     
     /// Merge another chapter into this one
     pub fn merge_with(&mut self, other: &FlattenedChapter) {
-        // Extend the length to include the other chapter
-        let other_end = other.start_offset_ms + other.length_ms;
-        self.length_ms = other_end - self.start_offset_ms;
-        self.length_ms = self.length_ms.max(other.length_ms); // Ensure we don't go backwards
+        // Extend the range to cover the other chapter, which may come before or after this one
+        let end_ms = (self.start_offset_ms + self.length_ms).max(other.start_offset_ms + other.length_ms);
+        self.start_offset_ms = self.start_offset_ms.min(other.start_offset_ms);
+        self.start_offset_sec = self.start_offset_ms / 1000;
+        self.length_ms = end_ms - self.start_offset_ms;
         
         // Add the other chapter's title to merged chapters
         self.merged_chapters.push(other.title.clone());
@@ -247,68 +396,27 @@ impl MergedChapter {
         }
     }
     
-    /// Generate filename based on format pattern
-    pub fn generate_filename(&self, format: &ChapterNamingFormat, extension: &str) -> String {
-        match format {
-            ChapterNamingFormat::ChapterNumberTitle => {
-                format!("Chapter{:02}_{}.{}", 
-                    self.chapter_number, 
-                    self.sanitize_title(&self.title), 
-                    extension)
-            },
-            ChapterNamingFormat::NumberTitle => {
-                format!("{:02}_{}.{}", 
-                    self.chapter_number, 
-                    self.sanitize_title(&self.title), 
-                    extension)
-            },
-            ChapterNamingFormat::TitleOnly => {
-                format!("{}.{}", 
-                    self.sanitize_title(&self.title), 
-                    extension)
-            },
-            ChapterNamingFormat::Custom(pattern) => {
-                pattern
-                    .replace("{chapter:02}", &format!("{:02}", self.chapter_number))
-                    .replace("{chapter}", &format!("{}", self.chapter_number))
-                    .replace("{number:02}", &format!("{:02}", self.chapter_number))
-                    .replace("{number}", &format!("{}", self.chapter_number))
-                    .replace("{title}", &self.sanitize_title(&self.title))
-                    .replace("{extension}", extension)
-            }
+    /// Generate filename based on format pattern.
+    /// Titles in `duplicate_titles` get the chapter's start time appended, e.g. `Interlude_00-45-30`.
+    pub fn generate_filename(&self, format: &ChapterNamingFormat, extension: &str, duplicate_titles: &DuplicateTitles) -> String {
+        match duplicate_titles.suffix(self) {
+            Some(suffix) => chapter_filename(format, self.chapter_number, &format!("{}_{}", self.title, suffix), extension),
+            None => chapter_filename(format, self.chapter_number, &self.title, extension),
         }
     }
     
-    /// Sanitize title for use in filename
-    fn sanitize_title(&self, title: &str) -> String {
-        title
-            .replace(":", "")
-            .replace("/", "_")
-            .replace("\\", "_")
-            .replace("?", "")
-            .replace("*", "")
-            .replace("\"", "")
-            .replace("<", "")
-            .replace(">", "")
-            .replace("|", "")
-            .replace(" ", "_")
-            .chars()
-            .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
-            .collect()
-    }
-    
     /// Get hierarchical output path for this chapter
-    pub fn get_hierarchical_output_path(&self, base_path: &Path, format: &ChapterNamingFormat, extension: &str) -> PathBuf {
-        let filename = self.generate_filename(format, extension);
+    pub fn get_hierarchical_output_path(&self, base_path: &Path, format: &ChapterNamingFormat, extension: &str, duplicate_titles: &DuplicateTitles) -> PathBuf {
+        let filename = self.generate_filename(format, extension, duplicate_titles);
         
         // Parse the full_path to create directory structure
         // e.g., "Part One: Empire > Chapter 1" -> "Part_One_Empire/Chapter_1.mp3"
-        let path_parts: Vec<&str> = self.full_path.split(" > ").collect();
+        let path_parts: Vec<&str> = self.full_path.split(CHAPTER_PATH_SEPARATOR).collect();
         
         if path_parts.len() <= 1 {
             // No hierarchy - check if this is a parent chapter that should be in its own directory
             // If the title contains the chapter number pattern, it's a parent chapter
-            if filename.contains("Chapter") && !self.full_path.contains(" > ") {
+            if filename.contains("Chapter") && !self.full_path.contains(CHAPTER_PATH_SEPARATOR) {
                 // This is a parent chapter with content - place it in its own directory
                 let dir_name = self.full_path
                     .replace(":", "")
@@ -341,9 +449,208 @@ impl MergedChapter {
             path
         }
     }
+
+    /// Get the by-disc output path for this chapter: `Disc01_<Part_Title>/<filename>`,
+    /// or just `Disc01/<filename>` when the book has no parts
+    pub fn get_disc_output_path(&self, base_path: &Path, format: &ChapterNamingFormat, extension: &str, duplicate_titles: &DuplicateTitles) -> PathBuf {
+        let filename = self.generate_filename(format, extension, duplicate_titles);
+        let disc_title = sanitize_title(&self.disc_title, &SanitizeConfig::default());
+        let dir_name = if disc_title.is_empty() {
+            format!("Disc{:02}", self.disc_number)
+        } else {
+            format!("Disc{:02}_{}", self.disc_number, disc_title)
+        };
+        base_path.join(dir_name).join(filename)
+    }
+}
+
+/// What to append to the file names of chapters sharing a title
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TitleDisambiguation {
+    /// Nothing, chapters with the same title may overwrite each other
+    #[default]
+    None,
+    /// The chapter number, e.g. Interlude_03
+    Number,
+    /// The start time as HH-MM-SS, e.g. Interlude_00-45-30
+    Time,
+}
+
+/// Titles shared by more than one chapter, which would produce the same filename,
+/// and how to tell them apart
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DuplicateTitles {
+    counts: HashMap<String, usize>,
+    strategy: TitleDisambiguation,
+}
+
+impl DuplicateTitles {
+    /// Count the occurrences of each title
+    pub fn new(chapters: &[MergedChapter], strategy: TitleDisambiguation) -> Self {
+        let mut counts = HashMap::new();
+        if strategy != TitleDisambiguation::None {
+            for chapter in chapters {
+                *counts.entry(chapter.title.clone()).or_insert(0) += 1;
+            }
+        }
+        Self { counts, strategy }
+    }
+
+    /// Whether more than one chapter has this title
+    pub fn is_duplicate(&self, title: &str) -> bool {
+        self.counts.get(title).is_some_and(|&count| count > 1)
+    }
+
+    /// Suffix to append to the title of `chapter`, None when its title is unique
+    fn suffix(&self, chapter: &MergedChapter) -> Option<String> {
+        if !self.is_duplicate(&chapter.title) {
+            return None;
+        }
+        match self.strategy {
+            TitleDisambiguation::None => None,
+            TitleDisambiguation::Number => Some(format!("{:02}", chapter.chapter_number)),
+            TitleDisambiguation::Time => Some(format_start_time(chapter.start_offset_ms)),
+        }
+    }
+}
+
+/// Aggregate figures about a book's chapters
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChapterStats {
+    /// Number of chapters
+    pub chapter_count: u64,
+    /// Sum of the chapter lengths
+    pub total_duration_ms: u64,
+    /// Number of hierarchy levels, 1 for a flat book
+    pub max_depth: u64,
+    /// Mean chapter length, 0 without chapters
+    pub average_duration_ms: u64,
+    /// Displayed path and length of the shortest chapter
+    pub shortest: Option<(String, u64)>,
+    /// Displayed path and length of the longest chapter
+    pub longest: Option<(String, u64)>,
+}
+
+impl ChapterStats {
+    /// Statistics of flattened chapters, all zero for an empty slice
+    pub fn from_chapters(chapters: &[FlattenedChapter]) -> Self {
+        let length = |chapter: &FlattenedChapter| chapter.length_ms.max(0) as u64;
+        let total_duration_ms: u64 = chapters.iter().map(length).sum();
+        let chapter_count = chapters.len() as u64;
+        // The first of equally long chapters is reported
+        let shortest = chapters.iter().min_by_key(|chapter| length(chapter));
+        let longest = chapters.iter().rev().max_by_key(|chapter| length(chapter));
+        Self {
+            chapter_count,
+            total_duration_ms,
+            max_depth: chapters.iter().map(|chapter| chapter.level as u64 + 1).max().unwrap_or(0),
+            average_duration_ms: total_duration_ms.checked_div(chapter_count).unwrap_or(0),
+            shortest: shortest.map(|chapter| (chapter.to_string(), length(chapter))),
+            longest: longest.map(|chapter| (chapter.to_string(), length(chapter))),
+        }
+    }
+
+    /// Length of the shortest chapter, 0 without chapters
+    pub fn shortest_ms(&self) -> u64 {
+        self.shortest.as_ref().map_or(0, |(_, length_ms)| *length_ms)
+    }
+
+    /// Length of the longest chapter, 0 without chapters
+    pub fn longest_ms(&self) -> u64 {
+        self.longest.as_ref().map_or(0, |(_, length_ms)| *length_ms)
+    }
+}
+
+impl fmt::Display for ChapterStats {
+    /// e.g. `12 chapters, 10:32:05 total, 2 levels, average 00:52:40, shortest "Opening Credits" (00:00:40), longest "Chapter 3" (01:10:00)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} chapters, {} total, {} levels, average {}",
+            self.chapter_count,
+            format_duration(self.total_duration_ms),
+            self.max_depth,
+            format_duration(self.average_duration_ms),
+        )?;
+        if let Some((title, length_ms)) = &self.shortest {
+            write!(f, ", shortest \"{}\" ({})", title, format_duration(*length_ms))?;
+        }
+        if let Some((title, length_ms)) = &self.longest {
+            write!(f, ", longest \"{}\" ({})", title, format_duration(*length_ms))?;
+        }
+        Ok(())
+    }
+}
+
+/// Format a duration as `HH:MM:SS`
+fn format_duration(duration_ms: u64) -> String {
+    let total_seconds = duration_ms / 1000;
+    format!("{:02}:{:02}:{:02}", total_seconds / 3600, total_seconds / 60 % 60, total_seconds % 60)
+}
+
+/// Format a start offset as `HH-MM-SS` for use in a filename
+fn format_start_time(start_offset_ms: i64) -> String {
+    let total_seconds = start_offset_ms.max(0) / 1000;
+    format!("{:02}-{:02}-{:02}", total_seconds / 3600, total_seconds / 60 % 60, total_seconds % 60)
+}
+
+/// Build a chapter filename for `format`.
+///
+/// Titles that sanitize to nothing are left out of the numbered formats and replaced by
+/// `Chapter<NN>` elsewhere, and the extension is appended when a custom pattern has no
+/// `{extension}` placeholder, so the result is never a bare extension.
+fn chapter_filename(format: &ChapterNamingFormat, chapter_number: usize, title: &str, extension: &str) -> String {
+    let title = sanitize_title(title, &SanitizeConfig::default());
+    let fallback_title = || format!("{}{:02}", DEFAULT_CHAPTER_PREFIX, chapter_number);
+    match format {
+        ChapterNamingFormat::ChapterNumberTitle { prefix } if title.is_empty() => {
+            format!("{}{:02}.{}", prefix, chapter_number, extension)
+        },
+        ChapterNamingFormat::ChapterNumberTitle { prefix } => {
+            format!("{}{:02}_{}.{}", prefix, chapter_number, title, extension)
+        },
+        ChapterNamingFormat::NumberTitle if title.is_empty() => {
+            format!("{:02}.{}", chapter_number, extension)
+        },
+        ChapterNamingFormat::NumberTitle => {
+            format!("{:02}_{}.{}", chapter_number, title, extension)
+        },
+        ChapterNamingFormat::TrackNumberTitle if title.is_empty() => {
+            format!("Track{:02}.{}", chapter_number, extension)
+        },
+        ChapterNamingFormat::TrackNumberTitle => {
+            format!("Track{:02}_{}.{}", chapter_number, title, extension)
+        },
+        ChapterNamingFormat::TitleOnly if title.is_empty() => {
+            format!("{}.{}", fallback_title(), extension)
+        },
+        ChapterNamingFormat::TitleOnly => {
+            format!("{}.{}", title, extension)
+        },
+        ChapterNamingFormat::Custom(pattern) => {
+            let title = if title.is_empty() { fallback_title() } else { title };
+            let filename = pattern
+                .replace("{chapter:02}", &format!("{:02}", chapter_number))
+                .replace("{chapter}", &format!("{}", chapter_number))
+                .replace("{number:02}", &format!("{:02}", chapter_number))
+                .replace("{number}", &format!("{}", chapter_number))
+                .replace("{title}", &title);
+            if pattern.contains("{extension}") {
+                filename.replace("{extension}", extension)
+            } else {
+                format!("{}.{}", filename, extension)
+            }
+        }
+    }
 }
 
 impl FlattenedChapter {
+    /// Whether both chapters cover the same audio, whatever their titles and numbers.
+    /// For removing duplicate chapters with `dedup_by`.
+    pub fn same_span(&self, other: &FlattenedChapter) -> bool {
+        (self.start_offset_ms, self.length_ms) == (other.start_offset_ms, other.length_ms)
+    }
+
     /// Check if this chapter should be included based on minimum duration
     pub fn should_include(&self, min_duration_ms: i64) -> bool {
         self.length_ms >= min_duration_ms
@@ -356,57 +663,11 @@ impl FlattenedChapter {
     
     /// Generate filename based on format pattern
     pub fn generate_filename(&self, format: &ChapterNamingFormat, extension: &str) -> String {
-        match format {
-            ChapterNamingFormat::ChapterNumberTitle => {
-                format!("Chapter{:02}_{}.{}", 
-                    self.chapter_number, 
-                    self.sanitize_title(&self.title),
-                    extension
-                )
-            },
-            ChapterNamingFormat::NumberTitle => {
-                format!("{:02}_{}.{}", 
-                    self.chapter_number, 
-                    self.sanitize_title(&self.title),
-                    extension
-                )
-            },
-            ChapterNamingFormat::TitleOnly => {
-                format!("{}.{}", 
-                    self.sanitize_title(&self.title),
-                    extension
-                )
-            },
-            ChapterNamingFormat::Custom(pattern) => {
-                pattern
-                    .replace("{number:02}", &format!("{:02}", self.chapter_number))
-                    .replace("{number}", &format!("{}", self.chapter_number))
-                    .replace("{title}", &self.sanitize_title(&self.title))
-                    .replace("{extension}", extension)
-            }
-        }
-    }
-    
-    /// Sanitize title for use in filename
-    fn sanitize_title(&self, title: &str) -> String {
-        title
-            .replace(":", "")
-            .replace("/", "_")
-            .replace("\\", "_")
-            .replace("?", "")
-            .replace("*", "")
-            .replace("\"", "")
-            .replace("<", "")
-            .replace(">", "")
-            .replace("|", "")
-            .replace(" ", "_")
-            .chars()
-            .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
-            .collect()
+        chapter_filename(format, self.chapter_number, &self.title, extension)
     }
     
     /// Get output path for this chapter
-    pub fn get_output_path(&self, base_path: &PathBuf, format: &ChapterNamingFormat, extension: &str) -> PathBuf {
+    pub fn get_output_path(&self, base_path: &Path, format: &ChapterNamingFormat, extension: &str) -> PathBuf {
         let filename = self.generate_filename(format, extension);
         base_path.join(filename)
     }
@@ -417,12 +678,12 @@ impl FlattenedChapter {
         
         // Parse the full_path to create directory structure
         // e.g., "Part One: Empire > Chapter 1" -> "Part_One_Empire/Chapter_1.mp3"
-        let path_parts: Vec<&str> = self.full_path.split(" > ").collect();
+        let path_parts: Vec<&str> = self.full_path.split(CHAPTER_PATH_SEPARATOR).collect();
         
         if path_parts.len() <= 1 {
             // No hierarchy - check if this is a parent chapter that should be in its own directory
             // If the title contains the chapter number pattern, it's a parent chapter
-            if filename.contains("Chapter") && !self.full_path.contains(" > ") {
+            if filename.contains("Chapter") && !self.full_path.contains(CHAPTER_PATH_SEPARATOR) {
                 // This is a parent chapter with content - place it in its own directory
                 let dir_name = self.full_path
                     .replace(":", "")
@@ -457,72 +718,634 @@ impl FlattenedChapter {
     }
 }
 
+/// Prefix of `ChapterNamingFormat::ChapterNumberTitle` file names unless overridden with --chapter-prefix
+pub const DEFAULT_CHAPTER_PREFIX: &str = "Chapter";
+
 /// Chapter naming format options
 #[derive(Debug, Clone, PartialEq)]
 pub enum ChapterNamingFormat {
-    /// Chapter01_Title.ext
-    ChapterNumberTitle,
+    /// Chapter01_Title.ext, with "Chapter" replaced by `prefix`
+    ChapterNumberTitle {
+        /// Replaces "Chapter" in the file name, [`DEFAULT_CHAPTER_PREFIX`] unless overridden
+        prefix: String,
+    },
     /// 01_Title.ext
     NumberTitle,
+    /// Track01_Title.ext, numbered like the tracks of a music album
+    TrackNumberTitle,
     /// Title.ext
     TitleOnly,
     /// Custom pattern with placeholders: {number:02}, {number}, {title}, {extension}
+    /// (`.{extension}` is appended when the pattern doesn't use it)
     Custom(String),
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Identity of the book file the chapters were fetched for
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ContentReference {
+    /// Audible content reference of the file
     pub acr: String,
+    /// ASIN of the book
     pub asin: String,
+    /// Audible codec of the file
     pub codec: String,
+    /// Audible format of the file
     #[serde(rename = "content_format")]
     pub content_format: String,
+    /// Size of the file in bytes
     #[serde(rename = "content_size_in_bytes")]
     pub content_size_in_bytes: i64,
+    /// Version of the file
     #[serde(rename = "file_version")]
     pub file_version: String,
+    /// Marketplace the book was bought in
     pub marketplace: String,
+    /// Audible SKU of the book
     pub sku: String,
+    /// Playback tempo the file was made for
     pub tempo: String,
+    /// Version of the content reference
     pub version: String,
 }
 
 impl ContentReference {
-    pub fn validate(&self) -> Result<(), String> {
-        if self.acr.trim().is_empty() { return Err("acr is empty".to_string()); }
-        if self.asin.trim().is_empty() { return Err("asin is empty".to_string()); }
-        if self.codec.trim().is_empty() { return Err("codec is empty".to_string()); }
-        if self.content_format.trim().is_empty() { return Err("content_format is empty".to_string()); }
-        if self.content_size_in_bytes <= 0 { return Err("content_size_in_bytes is not positive".to_string()); }
-        if self.file_version.trim().is_empty() { return Err("file_version is empty".to_string()); }
-        if self.marketplace.trim().is_empty() { return Err("marketplace is empty".to_string()); }
-        if self.sku.trim().is_empty() { return Err("sku is empty".to_string()); }
-        if self.tempo.trim().is_empty() { return Err("tempo is empty".to_string()); }
-        if self.version.trim().is_empty() { return Err("version is empty".to_string()); }
-        Ok(())
+    /// Empty fields and a non-positive size
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        if self.acr.trim().is_empty() { validator.error("acr is empty"); }
+        if self.asin.trim().is_empty() { validator.error("asin is empty"); }
+        if self.codec.trim().is_empty() { validator.error("codec is empty"); }
+        if self.content_format.trim().is_empty() { validator.error("content_format is empty"); }
+        if self.content_size_in_bytes <= 0 { validator.error("content_size_in_bytes is not positive"); }
+        if self.file_version.trim().is_empty() { validator.error("file_version is empty"); }
+        if self.marketplace.trim().is_empty() { validator.error("marketplace is empty"); }
+        if self.sku.trim().is_empty() { validator.error("sku is empty"); }
+        if self.tempo.trim().is_empty() { validator.error("tempo is empty"); }
+        if self.version.trim().is_empty() { validator.error("version is empty"); }
+        validator.finish()
     }
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Where listening last stopped, as synced by Audible
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct LastPositionHeard {
+    /// When the position was last synced
     #[serde(rename = "last_updated")]
     pub last_updated: Option<String>,
+    /// Position in ms
     #[serde(rename = "position_ms")]
     pub position_ms: Option<i64>,
+    /// Sync status as reported by Audible
     pub status: String,
 }
 
 impl LastPositionHeard {
-    pub fn validate(&self) -> Result<(), String> {
+    /// Empty fields and a negative position
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
         if let Some(ref last_updated) = self.last_updated {
-            if last_updated.trim().is_empty() { return Err("last_updated is empty".to_string()); }
+            if last_updated.trim().is_empty() { validator.error("last_updated is empty"); }
         }
         if let Some(position_ms) = self.position_ms {
-            if position_ms < 0 { return Err("position_ms is negative".to_string()); }
+            if position_ms < 0 { validator.error("position_ms is negative"); }
+        }
+        if self.status.trim().is_empty() { validator.error("status is empty"); }
+        validator.finish()
+    }
+}
+
+impl From<&super::voucher::ContentReference> for ContentReference {
+    fn from(reference: &super::voucher::ContentReference) -> Self {
+        Self {
+            acr: reference.acr.clone(),
+            asin: reference.asin.clone(),
+            codec: reference.codec.clone(),
+            content_format: reference.content_format.clone(),
+            content_size_in_bytes: reference.content_size_in_bytes,
+            file_version: reference.file_version.clone(),
+            marketplace: reference.marketplace.clone(),
+            sku: reference.sku.clone(),
+            tempo: reference.tempo.clone(),
+            version: reference.version.clone(),
+        }
+    }
+}
+
+/// Build an audible-cli chapters file from the chapter markers embedded in a book.
+///
+/// ffprobe markers are flat and carry no brand intro/outro information, so those durations are 0.
+/// Markers without a title are named "Chapter N". Fails on a marker whose times can't be read.
+pub fn ffprobe_chapters_to_audible_chapters(
+    ffprobe_chapters: Vec<FFProbeChapter>,
+    content_reference: &ContentReference,
+) -> Result<AudibleChapters, String> {
+    let chapters = ffprobe_chapters
+        .iter()
+        .enumerate()
+        .map(|(index, chapter)| {
+            let (start_offset_ms, end_offset_ms) = chapter.span_ms().map_err(|e| format!("chapter {}: {}", index + 1, e))?;
+            Ok(ChapterNode {
+                length_ms: end_offset_ms - start_offset_ms,
+                start_offset_ms,
+                start_offset_sec: start_offset_ms / 1000,
+                title: chapter.title_or_numbered(index + 1),
+                chapters: Vec::new(),
+            })
+        })
+        .collect::<Result<Vec<ChapterNode>, String>>()?;
+    let runtime_length_ms = chapters
+        .iter()
+        .map(|chapter| chapter.start_offset_ms + chapter.length_ms)
+        .max()
+        .unwrap_or(0);
+
+    Ok(AudibleChapters {
+        content_metadata: ContentMetadata {
+            chapter_info: ChapterInfo {
+                brand_intro_duration_ms: 0,
+                brand_outro_duration_ms: 0,
+                chapters,
+                is_accurate: true,
+                runtime_length_ms,
+                runtime_length_sec: runtime_length_ms / 1000,
+            },
+            content_reference: content_reference.clone(),
+            last_position_heard: LastPositionHeard {
+                last_updated: None,
+                position_ms: None,
+                status: "DoesNotExist".to_string(),
+            },
+        },
+        response_groups: vec!["chapter_info".to_string()],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(title: &str, start_offset_ms: i64, length_ms: i64, chapters: Vec<ChapterNode>) -> ChapterNode {
+        ChapterNode {
+            length_ms,
+            start_offset_ms,
+            start_offset_sec: start_offset_ms / 1000,
+            title: title.to_string(),
+            chapters,
+        }
+    }
+
+    #[test]
+    fn iterator_flattens_depth_first() {
+        let book = node("Book", 0, 0, vec![
+            node("Part One: Empire", 0, 5000, vec![
+                node("Chapter 1", 5000, 10000, vec![]),
+                node("Chapter 2", 15000, 10000, vec![
+                    node("Scene A", 15000, 4000, vec![]),
+                    node("Scene B", 19000, 6000, vec![]),
+                ]),
+            ]),
+            node("Part Two", 25000, 0, vec![node("Chapter 3", 25000, 10000, vec![])]),
+            node("Epilogue", 35000, 2000, vec![]),
+        ]);
+
+        let iterated: Vec<FlattenedChapter> = book.flatten().collect();
+        let titles: Vec<(&str, usize)> = iterated.iter().map(|chapter| (chapter.title.as_str(), chapter.level)).collect();
+        assert_eq!(titles, [
+            ("Book_Part_One_Empire", 1),
+            ("Book_Part_One_Empire_Chapter_1", 2),
+            ("Book_Part_One_Empire_Chapter_2", 2),
+            ("Book_Part_One_Empire_Chapter_2_Scene_A", 3),
+            ("Book_Part_One_Empire_Chapter_2_Scene_B", 3),
+            ("Book_Part_Two_Chapter_3", 2),
+            ("Book_Epilogue", 1),
+        ]);
+        let numbers: Vec<usize> = iterated.iter().map(|chapter| chapter.chapter_number).collect();
+        assert_eq!(numbers, (1..=7).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn iterator_on_leaf_and_empty_container() {
+        let leaf = node("Only Chapter", 0, 1000, vec![]);
+        let chapters: Vec<FlattenedChapter> = leaf.flatten().collect();
+        assert_eq!(chapters.len(), 1);
+        assert_eq!((chapters[0].title.as_str(), chapters[0].level, chapters[0].chapter_number), ("Only Chapter", 0, 1));
+
+        // A node without children is a chapter, even with no length
+        let empty = node("Empty Part", 0, 0, vec![]);
+        assert_eq!(empty.flatten().count(), 1);
+    }
+
+    #[test]
+    fn iterator_is_lazy() {
+        let book = node("Book", 0, 0, (0..500).map(|i| node(&format!("Chapter {}", i), i * 1000, 1000, vec![])).collect());
+        let mut chapters = book.flatten();
+        assert_eq!(chapters.next().unwrap().title, "Book_Chapter_0");
+        // Only the first chapter has been built, the rest are still unvisited nodes
+        assert_eq!(chapters.next_chapter_number, 2);
+        assert_eq!(chapters.stack.len(), 499);
+    }
+
+    fn ffprobe_chapter(start_time: &str, end_time: &str, title: Option<&str>) -> FFProbeChapter {
+        FFProbeChapter {
+            id: 0,
+            time_base: "1/1000".to_string(),
+            start: 0,
+            start_time: start_time.to_string(),
+            end: 0,
+            end_time: end_time.to_string(),
+            tags: crate::models::FFProbeChapterTags { title: title.map(str::to_string) },
+        }
+    }
+
+    #[test]
+    fn ffprobe_chapters_convert_to_audible_format() {
+        let reference = ContentReference { asin: "B000000000".to_string(), ..Default::default() };
+        let chapters = ffprobe_chapters_to_audible_chapters(
+            vec![
+                ffprobe_chapter("0.000000", "40.000000", Some("Opening Credits")),
+                ffprobe_chapter("40.000000", "100.500000", None),
+            ],
+            &reference,
+        ).unwrap();
+
+        let info = &chapters.content_metadata.chapter_info;
+        assert_eq!(info.brand_intro_duration_ms, 0);
+        assert_eq!(info.brand_outro_duration_ms, 0);
+        assert_eq!(info.runtime_length_ms, 100500);
+        assert_eq!(info.runtime_length_sec, 100);
+        assert_eq!(info.chapters[0].title, "Opening Credits");
+        assert_eq!(info.chapters[0].length_ms, 40000);
+        assert_eq!(info.chapters[1].title, "Chapter 2");
+        assert_eq!(info.chapters[1].start_offset_ms, 40000);
+        assert_eq!(info.chapters[1].start_offset_sec, 40);
+        assert_eq!(info.chapters[1].length_ms, 60500);
+        assert_eq!(chapters.content_metadata.content_reference.asin, "B000000000");
+    }
+
+    #[test]
+    fn ffprobe_chapters_round_trip_through_json() {
+        let chapters = ffprobe_chapters_to_audible_chapters(
+            vec![ffprobe_chapter("0", "1.5", Some("Only"))],
+            &ContentReference::default(),
+        ).unwrap();
+        let json = serde_json::to_string(&chapters).unwrap();
+        assert!(json.contains("\"brandIntroDurationMs\":0"));
+        assert_eq!(serde_json::from_str::<AudibleChapters>(&json).unwrap(), chapters);
+    }
+
+    #[test]
+    fn ffprobe_chapters_with_unreadable_times_fail() {
+        let mut chapter = ffprobe_chapter("N/A", "1.5", None);
+        chapter.time_base = String::new();
+        assert_eq!(
+            ffprobe_chapters_to_audible_chapters(vec![chapter], &ContentReference::default()),
+            Err("chapter 1: invalid start_time 'N/A'".to_string())
+        );
+    }
+
+    fn merged(title: &str, chapter_number: usize) -> MergedChapter {
+        MergedChapter {
+            title: title.to_string(),
+            full_path: title.to_string(),
+            start_offset_ms: 0,
+            length_ms: 1000,
+            start_offset_sec: 0,
+            level: 0,
+            chapter_number,
+            disc_number: 1,
+            disc_title: String::new(),
+            merged_chapters: vec![title.to_string()],
+        }
+    }
+
+    fn chapter_number_title() -> ChapterNamingFormat {
+        ChapterNamingFormat::ChapterNumberTitle { prefix: DEFAULT_CHAPTER_PREFIX.to_string() }
+    }
+
+    fn custom(pattern: &str) -> ChapterNamingFormat {
+        ChapterNamingFormat::Custom(pattern.to_string())
+    }
+
+    #[test]
+    fn generate_filename_normal_title() {
+        let chapter = merged("Opening Credits", 3);
+        assert_eq!(chapter.generate_filename(&chapter_number_title(), "mp3", &DuplicateTitles::default()), "Chapter03_Opening_Credits.mp3");
+        assert_eq!(chapter.generate_filename(&ChapterNamingFormat::NumberTitle, "mp3", &DuplicateTitles::default()), "03_Opening_Credits.mp3");
+        assert_eq!(chapter.generate_filename(&ChapterNamingFormat::TrackNumberTitle, "mp3", &DuplicateTitles::default()), "Track03_Opening_Credits.mp3");
+        assert_eq!(chapter.generate_filename(&ChapterNamingFormat::TitleOnly, "mp3", &DuplicateTitles::default()), "Opening_Credits.mp3");
+        assert_eq!(chapter.generate_filename(&custom("{number:02} - {title}.{extension}"), "mp3", &DuplicateTitles::default()), "03 - Opening_Credits.mp3");
+    }
+
+    #[test]
+    fn generate_filename_colons_and_slashes() {
+        let chapter = merged("Part 1: Arrival/Departure\\End", 1);
+        assert_eq!(chapter.generate_filename(&chapter_number_title(), "m4a", &DuplicateTitles::default()), "Chapter01_Part_1_Arrival_Departure_End.m4a");
+        assert_eq!(chapter.generate_filename(&ChapterNamingFormat::NumberTitle, "m4a", &DuplicateTitles::default()), "01_Part_1_Arrival_Departure_End.m4a");
+        assert_eq!(chapter.generate_filename(&ChapterNamingFormat::TitleOnly, "m4a", &DuplicateTitles::default()), "Part_1_Arrival_Departure_End.m4a");
+        assert_eq!(chapter.generate_filename(&custom("{title}"), "m4a", &DuplicateTitles::default()), "Part_1_Arrival_Departure_End.m4a");
+    }
+
+    #[test]
+    fn generate_filename_empty_title_falls_back() {
+        let chapter = merged("", 7);
+        assert_eq!(chapter.generate_filename(&chapter_number_title(), "mp3", &DuplicateTitles::default()), "Chapter07.mp3");
+        assert_eq!(chapter.generate_filename(&ChapterNamingFormat::NumberTitle, "mp3", &DuplicateTitles::default()), "07.mp3");
+        assert_eq!(chapter.generate_filename(&ChapterNamingFormat::TrackNumberTitle, "mp3", &DuplicateTitles::default()), "Track07.mp3");
+        assert_eq!(chapter.generate_filename(&ChapterNamingFormat::TitleOnly, "mp3", &DuplicateTitles::default()), "Chapter07.mp3");
+        assert_eq!(chapter.generate_filename(&custom("{title}.{extension}"), "mp3", &DuplicateTitles::default()), "Chapter07.mp3");
+    }
+
+    #[test]
+    fn generate_filename_special_characters_only() {
+        let chapter = merged("?!*:<>|", 12);
+        assert_eq!(chapter.generate_filename(&chapter_number_title(), "flac", &DuplicateTitles::default()), "Chapter12.flac");
+        assert_eq!(chapter.generate_filename(&ChapterNamingFormat::NumberTitle, "flac", &DuplicateTitles::default()), "12.flac");
+        assert_eq!(chapter.generate_filename(&ChapterNamingFormat::TitleOnly, "flac", &DuplicateTitles::default()), "Chapter12.flac");
+        assert_eq!(chapter.generate_filename(&custom("{number}-{title}"), "flac", &DuplicateTitles::default()), "12-Chapter12.flac");
+    }
+
+    #[test]
+    fn generate_filename_long_title_is_kept() {
+        let title = "Word ".repeat(40);
+        let chapter = merged(&title, 1);
+        let expected_title = "Word_".repeat(40);
+        assert_eq!(chapter.generate_filename(&ChapterNamingFormat::TitleOnly, "mp3", &DuplicateTitles::default()), format!("{}.mp3", expected_title));
+        assert_eq!(chapter.generate_filename(&chapter_number_title(), "mp3", &DuplicateTitles::default()), format!("Chapter01_{}.mp3", expected_title));
+    }
+
+    #[test]
+    fn generate_filename_custom_placeholders() {
+        let chapter = merged("Intro", 5);
+        assert_eq!(chapter.generate_filename(&custom("{number}"), "ogg", &DuplicateTitles::default()), "5.ogg");
+        assert_eq!(chapter.generate_filename(&custom("{number:02}"), "ogg", &DuplicateTitles::default()), "05.ogg");
+        assert_eq!(chapter.generate_filename(&custom("{chapter}"), "ogg", &DuplicateTitles::default()), "5.ogg");
+        assert_eq!(chapter.generate_filename(&custom("{chapter:02}"), "ogg", &DuplicateTitles::default()), "05.ogg");
+        assert_eq!(chapter.generate_filename(&custom("{title}"), "ogg", &DuplicateTitles::default()), "Intro.ogg");
+        assert_eq!(chapter.generate_filename(&custom("book.{extension}"), "ogg", &DuplicateTitles::default()), "book.ogg");
+        assert_eq!(chapter.generate_filename(&custom("static"), "ogg", &DuplicateTitles::default()), "static.ogg");
+        assert_eq!(
+            chapter.generate_filename(&custom("{chapter:02}_{number}_{title}.{extension}"), "ogg", &DuplicateTitles::default()),
+            "05_5_Intro.ogg"
+        );
+    }
+
+    #[test]
+    fn generate_filename_always_has_extension() {
+        let formats = [
+            chapter_number_title(),
+            ChapterNamingFormat::NumberTitle,
+            ChapterNamingFormat::TitleOnly,
+            custom("{title}"),
+            custom("{number:02}_{title}.{extension}"),
+        ];
+        for title in ["Opening Credits", "", "???"] {
+            for format in &formats {
+                let filename = merged(title, 1).generate_filename(format, "wav", &DuplicateTitles::default());
+                let (stem, extension) = filename.rsplit_once('.').expect("filename has an extension");
+                assert!(!stem.is_empty(), "empty stem for {:?} with {:?}", title, format);
+                assert_eq!(extension, "wav");
+            }
+        }
+    }
+
+    #[test]
+    fn duplicate_titles_get_start_time() {
+        let mut chapters = vec![merged("Interlude", 1), merged("Chapter One", 2), merged("Interlude", 3)];
+        chapters[0].start_offset_ms = 2_730_000;
+        chapters[2].start_offset_ms = 7_987_000;
+        let duplicates = DuplicateTitles::new(&chapters, TitleDisambiguation::Time);
+        assert!(duplicates.is_duplicate("Interlude"));
+        assert!(!duplicates.is_duplicate("Chapter One"));
+
+        let names: Vec<String> = chapters
+            .iter()
+            .map(|chapter| chapter.generate_filename(&ChapterNamingFormat::TitleOnly, "mp3", &duplicates))
+            .collect();
+        assert_eq!(names, ["Interlude_00-45-30.mp3", "Chapter_One.mp3", "Interlude_02-13-07.mp3"]);
+    }
+
+    #[test]
+    fn duplicate_titles_get_chapter_number() {
+        let chapters = vec![merged("Interlude", 3), merged("Chapter One", 4), merged("Interlude", 17)];
+        let name = |duplicates: &DuplicateTitles, chapter: &MergedChapter| chapter.generate_filename(&ChapterNamingFormat::TitleOnly, "mp3", duplicates);
+
+        let duplicates = DuplicateTitles::new(&chapters, TitleDisambiguation::Number);
+        let names: Vec<String> = chapters.iter().map(|chapter| name(&duplicates, chapter)).collect();
+        assert_eq!(names, ["Interlude_03.mp3", "Chapter_One.mp3", "Interlude_17.mp3"]);
+
+        let duplicates = DuplicateTitles::new(&chapters, TitleDisambiguation::None);
+        assert!(!duplicates.is_duplicate("Interlude"));
+        let names: Vec<String> = chapters.iter().map(|chapter| name(&duplicates, chapter)).collect();
+        assert_eq!(names, ["Interlude.mp3", "Chapter_One.mp3", "Interlude.mp3"]);
+    }
+
+    #[test]
+    fn flattened_chapters_sort_by_time() {
+        let info = ChapterInfo {
+            chapters: vec![
+                node("Intro", 0, 1000, vec![]),
+                node("Chapter 1", 1000, 5000, vec![]),
+                node("Chapter 2", 6000, 2000, vec![]),
+            ],
+            ..Default::default()
+        };
+        let in_order = info.flatten(false);
+        let mut chapters: Vec<FlattenedChapter> = in_order.iter().rev().cloned().collect();
+        chapters.sort();
+        assert_eq!(chapters, in_order);
+
+        // Numbers and titles only break ties between chapters at the same time
+        let mut renumbered = in_order[1].clone();
+        renumbered.title = "Another title".to_string();
+        renumbered.global_chapter_number = 7;
+        assert!(renumbered > in_order[1] && renumbered < in_order[2]);
+        assert!(renumbered.same_span(&in_order[1]));
+        assert!(!renumbered.same_span(&in_order[2]));
+
+        let mut duplicated = vec![in_order[0].clone(), in_order[1].clone(), renumbered, in_order[2].clone()];
+        duplicated.dedup_by(|a, b| a.same_span(b));
+        assert_eq!(duplicated, in_order);
+    }
+
+    #[test]
+    fn chapter_numbers_reset_per_part() {
+        let info = ChapterInfo {
+            chapters: vec![
+                node("Part 1", 0, 0, vec![node("Chapter 1", 0, 1000, vec![]), node("Chapter 2", 1000, 1000, vec![])]),
+                node("Part 2", 2000, 0, vec![node("Chapter 1", 2000, 1000, vec![])]),
+            ],
+            ..Default::default()
+        };
+
+        let numbers = |chapters: Vec<FlattenedChapter>| -> Vec<(usize, usize, usize)> {
+            chapters.iter().map(|chapter| (chapter.chapter_number, chapter.global_chapter_number, chapter.disc_number)).collect()
+        };
+        assert_eq!(numbers(info.flatten(false)), [(1, 1, 1), (2, 2, 1), (3, 3, 2)]);
+        assert_eq!(numbers(info.flatten(true)), [(1, 1, 1), (2, 2, 1), (1, 3, 2)]);
+
+        // Without parts every top-level chapter would be number 1, so numbering stays global
+        let flat = ChapterInfo {
+            chapters: vec![node("Chapter 1", 0, 1000, vec![]), node("Chapter 2", 1000, 1000, vec![])],
+            ..Default::default()
+        };
+        assert_eq!(numbers(flat.flatten(true)), [(1, 1, 1), (2, 2, 1)]);
+    }
+
+    #[test]
+    fn by_disc_paths_use_part_titles() {
+        let info = ChapterInfo {
+            chapters: vec![
+                node("Part 1: Empire", 0, 0, vec![node("Chapter 1", 0, 1000, vec![]), node("Chapter 2", 1000, 1000, vec![])]),
+                node("Part 2", 2000, 0, vec![node("Chapter 1", 2000, 1000, vec![])]),
+            ],
+            ..Default::default()
+        };
+        let paths: Vec<PathBuf> = info
+            .flatten(true)
+            .iter()
+            .map(|chapter| MergedChapter::from_flattened(chapter).get_disc_output_path(Path::new("out"), &ChapterNamingFormat::NumberTitle, "mp3", &DuplicateTitles::default()))
+            .collect();
+        assert_eq!(paths, [
+            PathBuf::from("out/Disc01_Part_1_Empire/01_Part_1_Empire_Chapter_1.mp3"),
+            PathBuf::from("out/Disc01_Part_1_Empire/02_Part_1_Empire_Chapter_2.mp3"),
+            PathBuf::from("out/Disc02_Part_2/01_Part_2_Chapter_1.mp3"),
+        ]);
+
+        // A book without parts is a single disc
+        assert_eq!(
+            merged("Chapter 1", 1).get_disc_output_path(Path::new("out"), &ChapterNamingFormat::NumberTitle, "mp3", &DuplicateTitles::default()),
+            PathBuf::from("out/Disc01/01_Chapter_1.mp3"),
+        );
+    }
+
+    #[test]
+    fn chapter_stats_of_no_chapters() {
+        let stats = ChapterStats::from_chapters(&[]);
+        assert_eq!(stats, ChapterStats::default());
+        assert_eq!((stats.shortest_ms(), stats.longest_ms()), (0, 0));
+    }
+
+    #[test]
+    fn chapter_stats_of_single_chapter() {
+        let info = ChapterInfo { chapters: vec![node("Only", 0, 90_500, vec![])], ..Default::default() };
+        let stats = ChapterStats::from_chapters(&info.flatten(false));
+        assert_eq!(stats.chapter_count, 1);
+        assert_eq!(stats.total_duration_ms, 90_500);
+        assert_eq!(stats.max_depth, 1);
+        assert_eq!(stats.average_duration_ms, 90_500);
+        assert_eq!(stats.shortest, Some(("Only".to_string(), 90_500)));
+        assert_eq!(stats.longest, stats.shortest);
+    }
+
+    #[test]
+    fn chapter_stats_of_nested_chapters() {
+        let info = ChapterInfo {
+            chapters: vec![
+                node("Opening Credits", 0, 10_000, vec![]),
+                node("Part 1", 10_000, 0, vec![
+                    node("Chapter 1", 10_000, 60_000, vec![]),
+                    node("Section A", 70_000, 0, vec![node("Chapter 2", 70_000, 20_000, vec![])]),
+                ]),
+                node("End Credits", 90_000, 10_000, vec![]),
+            ],
+            ..Default::default()
+        };
+        let stats = ChapterStats::from_chapters(&info.flatten(false));
+        assert_eq!(stats.chapter_count, 4);
+        assert_eq!(stats.total_duration_ms, 100_000);
+        assert_eq!(stats.max_depth, 3);
+        assert_eq!(stats.average_duration_ms, 25_000);
+        // The first of equally short chapters
+        assert_eq!(stats.shortest_ms(), 10_000);
+        assert_eq!(stats.shortest.as_ref().map(|(title, _)| title.as_str()), Some("Opening Credits"));
+        assert_eq!(stats.longest_ms(), 60_000);
+        assert_eq!(
+            stats.to_string(),
+            "4 chapters, 00:01:40 total, 3 levels, average 00:00:25, shortest \"Opening Credits\" (00:00:10), longest \"Part 1 > Chapter 1\" (00:01:00)",
+        );
+    }
+
+    /// A chapter at `full_path`, written with " > " between the levels
+    fn nested(full_path: &str, chapter_number: usize) -> MergedChapter {
+        let title = full_path.rsplit(" > ").next().unwrap();
+        MergedChapter { full_path: full_path.replace(" > ", CHAPTER_PATH_SEPARATOR), ..merged(title, chapter_number) }
+    }
+
+    fn hierarchical_path(chapter: &MergedChapter, format: &ChapterNamingFormat) -> PathBuf {
+        chapter.get_hierarchical_output_path(Path::new("out"), format, "mp3", &DuplicateTitles::default())
+    }
+
+    #[test]
+    fn hierarchical_path_flat_chapter() {
+        let chapter = nested("Opening Credits", 1);
+        // File names containing "Chapter" get a directory named after the chapter
+        assert_eq!(hierarchical_path(&chapter, &chapter_number_title()), PathBuf::from("out/Opening_Credits/Chapter01_Opening_Credits.mp3"));
+        assert_eq!(hierarchical_path(&chapter, &ChapterNamingFormat::TitleOnly), PathBuf::from("out/Opening_Credits.mp3"));
+    }
+
+    #[test]
+    fn hierarchical_path_two_levels() {
+        let chapter = nested("Part 1 > Chapter 2", 2);
+        assert_eq!(hierarchical_path(&chapter, &chapter_number_title()), PathBuf::from("out/Part_1/Chapter02_Chapter_2.mp3"));
+        assert_eq!(hierarchical_path(&chapter, &ChapterNamingFormat::NumberTitle), PathBuf::from("out/Part_1/02_Chapter_2.mp3"));
+    }
+
+    #[test]
+    fn hierarchical_path_three_levels() {
+        let chapter = nested("Book One > Part 1: Empire > Chapter 3", 3);
+        assert_eq!(hierarchical_path(&chapter, &chapter_number_title()), PathBuf::from("out/Book_One/Part_1_Empire/Chapter03_Chapter_3.mp3"));
+    }
+
+    #[test]
+    fn hierarchical_path_special_characters() {
+        let chapter = nested("Part 2: L'Été & Co. > Épilogue?", 4);
+        // Only ASCII letters, digits, '_' and '-' survive, in directory and file names
+        assert_eq!(hierarchical_path(&chapter, &chapter_number_title()), PathBuf::from("out/Part_2_Lt__Co/Chapter04_pilogue.mp3"));
+    }
+
+    #[test]
+    fn hierarchical_path_empty_last_component() {
+        let chapter = nested("Part 1 > ???", 5);
+        assert_eq!(hierarchical_path(&chapter, &chapter_number_title()), PathBuf::from("out/Part_1/Chapter05.mp3"));
+        assert_eq!(hierarchical_path(&chapter, &ChapterNamingFormat::TitleOnly), PathBuf::from("out/Part_1/Chapter05.mp3"));
+    }
+
+    #[test]
+    fn separator_in_title_keeps_directory_structure() {
+        let info = ChapterInfo {
+            chapters: vec![node("Part 1", 0, 0, vec![node("Love > Hate", 0, 1000, vec![])])],
+            ..Default::default()
+        };
+        let chapter = &info.flatten(false)[0];
+        assert_eq!(chapter.to_string(), "Part 1 > Love > Hate");
+        assert_eq!(
+            chapter.get_hierarchical_output_path(Path::new("out"), &ChapterNamingFormat::NumberTitle, "mp3"),
+            PathBuf::from("out/Part_1/01_Part_1_Love__Hate.mp3"),
+        );
+    }
+
+    #[test]
+    fn flattened_hierarchical_path_matches_merged() {
+        for (full_path, chapter_number) in [("Opening Credits", 1), ("Part 1 > Chapter 2", 2), ("Book One > Part 1: Empire > Chapter 3", 3), ("Part 1 > ???", 5)] {
+            let merged = nested(full_path, chapter_number);
+            let flattened = FlattenedChapter {
+                title: merged.title.clone(),
+                full_path: merged.full_path.clone(),
+                start_offset_ms: merged.start_offset_ms,
+                length_ms: merged.length_ms,
+                start_offset_sec: merged.start_offset_sec,
+                level: full_path.matches(" > ").count(),
+                chapter_number,
+                global_chapter_number: chapter_number,
+                disc_number: 1,
+                disc_title: String::new(),
+            };
+            assert_eq!(
+                flattened.get_hierarchical_output_path(Path::new("out"), &chapter_number_title(), "mp3"),
+                hierarchical_path(&merged, &chapter_number_title()),
+            );
         }
-        if self.status.trim().is_empty() { return Err("status is empty".to_string()); }
-        Ok(())
     }
 }
@@ -1,44 +1,122 @@
+use std::collections::HashMap;
+
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Deserializing the output of `ffprobe -show_format -print_format json`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct FFProbeFormat {
+    /// Container-level information
     pub format: Format,
+    /// Only present when ffprobe is run with `-show_streams`
+    #[serde(default)]
+    pub streams: Vec<Stream>,
 }
 
+/// Audio codecs ffprobe reports for Audible files
+pub const EXPECTED_AUDIO_CODECS: &[&str] = &["aac_audible", "aac"];
+
 impl FFProbeFormat {
+    /// The first problem in the format or streams, such as a file without audio
     pub fn validate(&self) -> Result<(), String> {
-        self.format.validate().map_err(|e| format!("format: {}", e))
+        self.format.validate().map_err(|e| format!("format: {}", e))?;
+        if self.streams.is_empty() {
+            return Err("streams: no streams found, the file may be empty or corrupted".to_string());
+        }
+        if self.audio_stream().is_none() {
+            let codec_types: Vec<&str> = self.streams.iter().map(|stream| stream.codec_type.as_str()).collect();
+            return Err(format!(
+                "streams: no audio stream found (only {}), the input may not be an audio file",
+                codec_types.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// The first audio stream
+    pub fn audio_stream(&self) -> Option<&Stream> {
+        self.streams.iter().find(|stream| stream.codec_type == "audio")
+    }
+}
+
+
+/// A stream from `-show_streams`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Stream {
+    /// Index of the stream in the file
+    pub index: i64,
+    /// `audio`, `video` (cover art) or `data`
+    #[serde(rename = "codec_type", default)]
+    pub codec_type: String,
+    /// ffmpeg's name for the codec
+    #[serde(rename = "codec_name", default)]
+    pub codec_name: Option<String>,
+    /// Sample rate in Hz, as a string like all of ffprobe's numbers
+    #[serde(rename = "sample_rate", default)]
+    pub sample_rate: Option<String>,
+    /// Number of audio channels
+    #[serde(default)]
+    pub channels: Option<i64>,
+    /// Bitrate in bits per second, as a string
+    #[serde(rename = "bit_rate", default)]
+    pub bit_rate: Option<String>,
+}
+
+impl Stream {
+    /// Sample rate, None when missing or unparsable
+    pub fn sample_rate_hz(&self) -> Option<u32> {
+        self.sample_rate.as_deref().and_then(|rate| rate.trim().parse().ok())
+    }
+
+    /// Bitrate, None when missing or unparsable
+    pub fn bit_rate_bps(&self) -> Option<u64> {
+        self.bit_rate.as_deref().and_then(|rate| rate.trim().parse().ok())
     }
 }
 
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Container-level information from `-show_format`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Format {
+    /// Path of the probed file
     pub filename: String,
+    /// Number of streams
     #[serde(rename = "nb_streams")]
     pub nb_streams: i64,
+    /// Number of programs
     #[serde(rename = "nb_programs")]
     pub nb_programs: i64,
+    /// Number of stream groups
     #[serde(rename = "nb_stream_groups")]
     pub nb_stream_groups: i64,
+    /// Short names of the demuxer, such as `mov,mp4,m4a,3gp,3g2,mj2`
     #[serde(rename = "format_name")]
     pub format_name: String,
+    /// Descriptive name of the demuxer
     #[serde(rename = "format_long_name")]
     pub format_long_name: String,
+    /// Start time in seconds
     #[serde(rename = "start_time")]
     pub start_time: String,
+    /// Duration in seconds
     pub duration: String,
+    /// File size in bytes
     pub size: String,
+    /// Overall bitrate in bits per second
     #[serde(rename = "bit_rate")]
     pub bit_rate: String,
+    /// How confident ffprobe is about the format, up to 100
     #[serde(rename = "probe_score")]
     pub probe_score: i64,
+    /// Metadata tags of the file
     pub tags: Tags,
 }
 
 impl Format {
+    /// The first empty or out of range field
     pub fn validate(&self) -> Result<(), String> {
         if self.filename.trim().is_empty() { return Err("filename is empty".to_string()); }
         if self.nb_streams <= 0 { return Err("nb_streams is not positive".to_string()); }
@@ -55,43 +133,314 @@ impl Format {
 }
 
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Metadata tags of the book
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Tags {
+    /// MP4 major brand
     #[serde(rename = "major_brand")]
     pub major_brand: String,
+    /// MP4 minor version
     #[serde(rename = "minor_version")]
     pub minor_version: String,
+    /// MP4 compatible brands
     #[serde(rename = "compatible_brands")]
     pub compatible_brands: String,
+    /// When the file was created
     #[serde(rename = "creation_time")]
     pub creation_time: String,
-    pub genre: String,
+    /// Often missing or empty on files in the wild
+    #[serde(default)]
+    pub genre: Option<String>,
+    /// Book title
     pub title: String,
+    /// Author
     pub artist: String,
+    /// Album artist, normally the author as well
     #[serde(rename = "album_artist")]
     pub album_artist: String,
+    /// Album, normally the book title
     pub album: String,
-    pub comment: String,
-    pub copyright: String,
-    pub date: String,
+    /// Comment, often the publisher's summary
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// Copyright notice
+    #[serde(default)]
+    pub copyright: Option<String>,
+    /// Release date
+    #[serde(default)]
+    pub date: Option<String>,
+    /// Not part of the standard tag set, only present on some files
+    #[serde(default)]
+    pub asin: Option<String>,
 }
 
 impl Tags {
+    /// Tags with only the given fields set, keyed by their ffprobe names (e.g. `album_artist`).
+    /// Missing fields are empty, or `None` for optional ones, and unknown keys are ignored.
+    pub fn from_map(mut fields: HashMap<&str, String>) -> Tags {
+        let mut take = |name: &str| fields.remove(name);
+        Tags {
+            major_brand: take("major_brand").unwrap_or_default(),
+            minor_version: take("minor_version").unwrap_or_default(),
+            compatible_brands: take("compatible_brands").unwrap_or_default(),
+            creation_time: take("creation_time").unwrap_or_default(),
+            genre: take("genre"),
+            title: take("title").unwrap_or_default(),
+            artist: take("artist").unwrap_or_default(),
+            album_artist: take("album_artist").unwrap_or_default(),
+            album: take("album").unwrap_or_default(),
+            comment: take("comment"),
+            copyright: take("copyright"),
+            date: take("date"),
+            asin: take("asin"),
+        }
+    }
+
+    /// The first required tag that is empty
     pub fn validate(&self) -> Result<(), String> {
+        // Only the tags the conversion depends on are required, the rest are often empty
         if self.major_brand.trim().is_empty() { return Err("major_brand is empty".to_string()); }
-        if self.minor_version.trim().is_empty() { return Err("minor_version is empty".to_string()); }
-        if self.compatible_brands.trim().is_empty() { return Err("compatible_brands is empty".to_string()); }
-        if self.creation_time.trim().is_empty() { return Err("creation_time is empty".to_string()); }
-        if self.genre.trim().is_empty() { return Err("genre is empty".to_string()); }
         if self.title.trim().is_empty() { return Err("title is empty".to_string()); }
         if self.artist.trim().is_empty() { return Err("artist is empty".to_string()); }
-        if self.album_artist.trim().is_empty() { return Err("album_artist is empty".to_string()); }
         if self.album.trim().is_empty() { return Err("album is empty".to_string()); }
-        if self.comment.trim().is_empty() { return Err("comment is empty".to_string()); }
-        if self.copyright.trim().is_empty() { return Err("copyright is empty".to_string()); }
-        if self.date.trim().is_empty() { return Err("date is empty".to_string()); }
         Ok(())
     }
+
+    /// Best-effort ASIN extraction: the `asin` tag if present, otherwise an ASIN-looking token in the comment
+    pub fn asin(&self) -> Option<String> {
+        if let Some(ref asin) = self.asin {
+            if !asin.trim().is_empty() {
+                return Some(asin.trim().to_string());
+            }
+        }
+        self.comment
+            .as_deref()?
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .find(|token| {
+                token.len() == 10
+                    && token.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+                    && token.chars().any(|c| c.is_ascii_digit())
+            })
+            .map(|token| token.to_string())
+    }
+
+    /// Book identity for logs, e.g. `"Title" by Artist (Album, Date)`, leaving out a missing date
+    pub fn to_display_string(&self) -> String {
+        match self.date.as_deref().map(str::trim).filter(|date| !date.is_empty()) {
+            Some(date) => format!("\"{}\" by {} ({}, {})", self.title, self.artist, self.album, date),
+            None => format!("\"{}\" by {} ({})", self.title, self.artist, self.album),
+        }
+    }
+
+    /// Value for an `--output-dir-template` placeholder. `None` for unknown placeholders,
+    /// `Some(None)` for known ones the file has no value for.
+    pub fn template_value(&self, name: &str) -> Option<Option<String>> {
+        let value = match name {
+            "title" => Some(self.title.clone()),
+            "artist" => Some(self.artist.clone()),
+            "album_artist" => Some(self.album_artist.clone()),
+            "album" => Some(self.album.clone()),
+            "genre" => self.genre.clone(),
+            "date" => self.date.clone(),
+            "year" => self.date.as_ref().map(|date| date.chars().take(4).collect()),
+            "asin" => self.asin(),
+            _ => return None,
+        };
+        Some(value.filter(|value| !value.trim().is_empty()))
+    }
+}
+
+/// Fields that can be overridden with --metadata-map
+pub const METADATA_MAP_FIELDS: [&str; 8] = ["title", "artist", "album", "genre", "date", "comment", "copyright", "narrator"];
+
+/// `Tags` with the --metadata-map overrides applied on top, the probed tags stay untouched
+pub struct MappedTags<'a> {
+    tags: &'a Tags,
+    overrides: &'a HashMap<String, String>,
 }
 
+impl<'a> MappedTags<'a> {
+    /// `tags` with `overrides` from --metadata-map
+    pub fn new(tags: &'a Tags, overrides: &'a HashMap<String, String>) -> Self {
+        Self { tags, overrides }
+    }
+
+    /// A --metadata-map field, overridden or as probed. `narrator` is only ever set by an override.
+    pub fn get(&self, field: &str) -> Option<String> {
+        if let Some(value) = self.overrides.get(field) {
+            return Some(value.clone());
+        }
+        match field {
+            "title" => Some(self.tags.title.clone()),
+            "artist" => Some(self.tags.artist.clone()),
+            "album" => Some(self.tags.album.clone()),
+            "genre" => self.tags.genre.clone(),
+            "date" => self.tags.date.clone(),
+            "comment" => self.tags.comment.clone(),
+            "copyright" => self.tags.copyright.clone(),
+            _ => None,
+        }
+    }
+
+    /// Title, overridden or as probed
+    pub fn title(&self) -> String {
+        self.get("title").unwrap_or_default()
+    }
+
+    /// Artist, overridden or as probed
+    pub fn artist(&self) -> String {
+        self.get("artist").unwrap_or_default()
+    }
+
+    /// Album, overridden or as probed
+    pub fn album(&self) -> String {
+        self.get("album").unwrap_or_default()
+    }
+
+    /// Genre, overridden or as probed
+    pub fn genre(&self) -> Option<String> {
+        self.get("genre")
+    }
+
+    /// Date, overridden or as probed
+    pub fn date(&self) -> Option<String> {
+        self.get("date")
+    }
+
+    /// Like [`Tags::template_value`], with overrides applied and `{narrator}` as an extra placeholder
+    pub fn template_value(&self, name: &str) -> Option<Option<String>> {
+        let value = match name {
+            "year" => self.get("date").map(|date| date.chars().take(4).collect()),
+            "narrator" => self.get(name),
+            name if self.overrides.contains_key(name) => self.get(name),
+            _ => return self.tags.template_value(name),
+        };
+        Some(value.filter(|value| !value.trim().is_empty()))
+    }
+}
+
+
+
+/// Deserializing the output of `ffprobe -show_chapters -print_format json`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FFProbeChapters {
+    /// Chapter markers in the order ffprobe lists them
+    #[serde(default)]
+    pub chapters: Vec<FFProbeChapter>,
+}
+
+
+/// A chapter marker embedded in the file
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FFProbeChapter {
+    /// Marker id
+    #[serde(default)]
+    pub id: i64,
+    /// Time base of `start` and `end`, such as `1/1000`
+    #[serde(rename = "time_base", default)]
+    pub time_base: String,
+    /// Start in time base ticks
+    #[serde(default)]
+    pub start: i64,
+    /// Start in seconds
+    #[serde(rename = "start_time")]
+    pub start_time: String,
+    /// End in time base ticks
+    #[serde(default)]
+    pub end: i64,
+    /// End in seconds
+    #[serde(rename = "end_time")]
+    pub end_time: String,
+    /// Tags of the marker
+    #[serde(default)]
+    pub tags: FFProbeChapterTags,
+}
+
+impl FFProbeChapter {
+    /// Start and end of the marker in ms, from ffprobe's seconds strings or, when those can't be
+    /// parsed, the ticks in the time base
+    pub fn span_ms(&self) -> Result<(i64, i64), String> {
+        let start_ms = marker_time_ms(&self.start_time, self.start, &self.time_base)
+            .ok_or_else(|| format!("invalid start_time '{}'", self.start_time))?;
+        let end_ms = marker_time_ms(&self.end_time, self.end, &self.time_base)
+            .ok_or_else(|| format!("invalid end_time '{}'", self.end_time))?;
+        Ok((start_ms, end_ms))
+    }
+
+    /// The marker's title, "Chapter N" for markers without one
+    pub fn title_or_numbered(&self, chapter_number: usize) -> String {
+        self.tags.title
+            .clone()
+            .filter(|title| !title.trim().is_empty())
+            .unwrap_or_else(|| format!("Chapter {}", chapter_number))
+    }
+}
+
+/// A marker time in ms from ffprobe's seconds string, falling back to ticks in the time base
+fn marker_time_ms(seconds: &str, ticks: i64, time_base: &str) -> Option<i64> {
+    if let Ok(seconds) = seconds.trim().parse::<f64>() {
+        return Some((seconds * 1000.0).round() as i64);
+    }
+    let (numerator, denominator) = time_base.split_once('/')?;
+    match (numerator.parse::<i64>(), denominator.parse::<i64>()) {
+        (Ok(numerator), Ok(denominator)) if denominator != 0 => Some(ticks * numerator * 1000 / denominator),
+        _ => None,
+    }
+}
+
+
+/// Tags of a chapter marker
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FFProbeChapterTags {
+    /// Marker title, None or empty for untitled markers
+    pub title: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(fields: &[(&'static str, &str)]) -> Tags {
+        Tags::from_map(fields.iter().map(|(name, value)| (*name, value.to_string())).collect())
+    }
+
+    #[test]
+    fn from_map_sets_only_the_given_fields() {
+        let tags = tags(&[("title", "Dune"), ("album_artist", "Frank Herbert"), ("date", "1965"), ("unknown", "x")]);
+        assert_eq!(tags.title, "Dune");
+        assert_eq!(tags.album_artist, "Frank Herbert");
+        assert_eq!(tags.date.as_deref(), Some("1965"));
+        assert_eq!(tags.artist, "");
+        assert_eq!(tags.genre, None);
+        assert_eq!(Tags::from_map(HashMap::new()), Tags::default());
+    }
+
+    #[test]
+    fn from_map_validation() {
+        assert_eq!(tags(&[("title", "Dune"), ("artist", "Frank Herbert")]).validate(), Err("major_brand is empty".to_string()));
+        let complete = tags(&[("major_brand", "aaxc"), ("title", "Dune"), ("artist", "Frank Herbert"), ("album", "Dune")]);
+        assert_eq!(complete.validate(), Ok(()));
+    }
+
+    #[test]
+    fn metadata_map_overrides_win() {
+        let tags = tags(&[("title", "Dune"), ("genre", "Fiction")]);
+        let overrides = HashMap::from([("genre".to_string(), "Science Fiction".to_string())]);
+        let mapped = MappedTags::new(&tags, &overrides);
+        assert_eq!(mapped.title(), "Dune");
+        assert_eq!(mapped.genre().as_deref(), Some("Science Fiction"));
+        assert_eq!(mapped.template_value("year"), Some(None));
+    }
+
+    #[test]
+    fn marker_time_falls_back_to_ticks() {
+        assert_eq!(marker_time_ms("N/A", 44100, "1/44100"), Some(1000));
+        assert_eq!(marker_time_ms("2.25", 0, ""), Some(2250));
+        assert_eq!(marker_time_ms("", 5, "bogus"), None);
+        assert_eq!(marker_time_ms("", 5, "1/0"), None);
+    }
+}
@@ -1,7 +1,15 @@
 mod chapters;
 mod ffprobe_format;
-mod voucher;
+mod schema;
+mod strict_chapters;
+mod validation;
+/// Voucher file types, kept in their own module where names clash with the chapters file
+pub mod voucher;
 
 pub use chapters::*;
 pub use ffprobe_format::*;
-pub use voucher::*;
+pub use schema::*;
+pub use strict_chapters::*;
+// ContentMetadata, ContentReference and LastPositionHeard are also chapters types, so the
+// voucher's are only reachable as voucher::*
+pub use voucher::{AudibleCliVoucher, ContentLicense, ContentUrl, LastPositionHeard2, LicenseResponse, Parameter, PlaybackInfo, Rule};
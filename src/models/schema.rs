@@ -0,0 +1,108 @@
+use super::chapters::AudibleChapters;
+use super::voucher::AudibleCliVoucher;
+
+/// Input files with a JSON Schema, derived from their models with schemars.
+/// Copies are kept in `schema/` for other tools, see the generate-schema subcommand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaName {
+    /// audible-cli chapters file (`AudibleChapters`)
+    Chapters,
+    /// audible-cli voucher file (`AudibleCliVoucher`)
+    Voucher,
+}
+
+impl SchemaName {
+    /// The JSON Schema of this file
+    pub fn schema(self) -> serde_json::Value {
+        let schema = match self {
+            SchemaName::Chapters => schemars::schema_for!(AudibleChapters),
+            SchemaName::Voucher => schemars::schema_for!(AudibleCliVoucher),
+        };
+        serde_json::to_value(schema).expect("schema serializes to JSON")
+    }
+}
+
+/// Validate a raw JSON document against `schema`.
+///
+/// Catches structural problems (wrong field types, missing required arrays) before serde
+/// gets to fill in defaults. Each violation names the JSON pointer of the offending value.
+pub fn validate_json_schema(schema: &serde_json::Value, raw_json: &str) -> Result<(), Vec<String>> {
+    let instance: serde_json::Value = serde_json::from_str(raw_json)
+        .map_err(|e| vec![format!("invalid JSON: {}", e)])?;
+    let validator = jsonschema::validator_for(schema)
+        .map_err(|e| vec![format!("invalid schema: {}", e)])?;
+    let violations: Vec<String> = validator
+        .iter_errors(&instance)
+        .map(|error| {
+            let path = error.instance_path.to_string();
+            let path = if path.is_empty() { "/".to_string() } else { path };
+            format!("{}: {}", path, error)
+        })
+        .collect();
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
+
+/// Validate a raw chapter file against the chapters schema
+pub fn validate_chapter_json_schema(raw_json: &str) -> Result<(), Vec<String>> {
+    validate_json_schema(&SchemaName::Chapters.schema(), raw_json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid_chapters() -> serde_json::Value {
+        serde_json::to_value(AudibleChapters::default()).unwrap()
+    }
+
+    fn valid_voucher() -> serde_json::Value {
+        serde_json::to_value(AudibleCliVoucher::default()).unwrap()
+    }
+
+    #[test]
+    fn accepts_serialized_chapters() {
+        assert_eq!(validate_chapter_json_schema(&valid_chapters().to_string()), Ok(()));
+    }
+
+    #[test]
+    fn reports_wrong_types_and_missing_arrays() {
+        let mut document = valid_chapters();
+        document["content_metadata"]["chapter_info"]["runtime_length_ms"] = serde_json::json!("100");
+        document.as_object_mut().unwrap().remove("response_groups");
+
+        let violations = validate_chapter_json_schema(&document.to_string()).unwrap_err();
+        assert_eq!(violations.len(), 2, "{:?}", violations);
+        assert!(violations.iter().any(|v| v.starts_with("/content_metadata/chapter_info/runtime_length_ms:")), "{:?}", violations);
+        assert!(violations.iter().any(|v| v.contains("response_groups")), "{:?}", violations);
+    }
+
+    #[test]
+    fn voucher_schema_accepts_valid_and_rejects_invalid() {
+        let schema = SchemaName::Voucher.schema();
+        assert_eq!(validate_json_schema(&schema, &valid_voucher().to_string()), Ok(()));
+
+        let mut document = valid_voucher();
+        document["content_license"]["license_response"]["key"] = serde_json::json!(42);
+        let violations = validate_json_schema(&schema, &document.to_string()).unwrap_err();
+        assert_eq!(violations.len(), 1, "{:?}", violations);
+        assert!(violations[0].starts_with("/content_license/license_response/key:"), "{:?}", violations);
+
+        // A chapters file is not a voucher
+        assert!(validate_json_schema(&schema, &valid_chapters().to_string()).is_err());
+    }
+
+    #[test]
+    fn exported_schemas_are_up_to_date() {
+        for (name, committed) in [
+            (SchemaName::Chapters, include_str!("../../schema/chapters.schema.json")),
+            (SchemaName::Voucher, include_str!("../../schema/voucher.schema.json")),
+        ] {
+            let exported: serde_json::Value = serde_json::from_str(committed).unwrap();
+            assert_eq!(exported, name.schema(), "regenerate the {:?} schema in schema/ with generate-schema", name);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use super::chapters::{AudibleChapters, ChapterInfo, ChapterNode, ContentMetadata, ContentReference, LastPositionHeard};
+
+/// Strict variant of `AudibleChapters` that rejects any field not known to this tool.
+/// `deny_unknown_fields` is a derive-time attribute, so every nested type has a strict mirror.
+#[derive(Debug, Clone, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StrictAudibleChapters {
+    /// See [`AudibleChapters::content_metadata`]
+    #[serde(rename = "content_metadata")]
+    pub content_metadata: StrictContentMetadata,
+    /// See [`AudibleChapters::response_groups`]
+    #[serde(rename = "response_groups")]
+    pub response_groups: Vec<String>,
+}
+
+/// Strict variant of [`ContentMetadata`]
+#[derive(Debug, Clone, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StrictContentMetadata {
+    /// See [`ContentMetadata::chapter_info`]
+    #[serde(rename = "chapter_info")]
+    pub chapter_info: StrictChapterInfo,
+    /// See [`ContentMetadata::content_reference`]
+    #[serde(rename = "content_reference")]
+    pub content_reference: StrictContentReference,
+    /// See [`ContentMetadata::last_position_heard`]
+    #[serde(rename = "last_position_heard")]
+    pub last_position_heard: StrictLastPositionHeard,
+}
+
+/// Strict variant of [`ChapterInfo`]
+#[derive(Debug, Clone, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StrictChapterInfo {
+    /// See [`ChapterInfo::brand_intro_duration_ms`]
+    #[serde(rename = "brandIntroDurationMs")]
+    pub brand_intro_duration_ms: i64,
+    /// See [`ChapterInfo::brand_outro_duration_ms`]
+    #[serde(rename = "brandOutroDurationMs")]
+    pub brand_outro_duration_ms: i64,
+    /// See [`ChapterInfo::chapters`]
+    pub chapters: Vec<StrictChapterNode>,
+    /// See [`ChapterInfo::is_accurate`]
+    #[serde(rename = "is_accurate")]
+    pub is_accurate: bool,
+    /// See [`ChapterInfo::runtime_length_ms`]
+    #[serde(rename = "runtime_length_ms")]
+    pub runtime_length_ms: i64,
+    /// See [`ChapterInfo::runtime_length_sec`]
+    #[serde(rename = "runtime_length_sec")]
+    pub runtime_length_sec: i64,
+}
+
+/// Strict variant of [`ChapterNode`]
+#[derive(Debug, Clone, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StrictChapterNode {
+    /// See [`ChapterNode::length_ms`]
+    #[serde(rename = "length_ms")]
+    pub length_ms: i64,
+    /// See [`ChapterNode::start_offset_ms`]
+    #[serde(rename = "start_offset_ms")]
+    pub start_offset_ms: i64,
+    /// See [`ChapterNode::start_offset_sec`]
+    #[serde(rename = "start_offset_sec")]
+    pub start_offset_sec: i64,
+    /// See [`ChapterNode::title`]
+    pub title: String,
+    /// See [`ChapterNode::chapters`]
+    #[serde(default)]
+    pub chapters: Vec<StrictChapterNode>,
+}
+
+/// Strict variant of [`ContentReference`]
+#[derive(Debug, Clone, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StrictContentReference {
+    /// See [`ContentReference::acr`]
+    pub acr: String,
+    /// See [`ContentReference::asin`]
+    pub asin: String,
+    /// See [`ContentReference::codec`]
+    pub codec: String,
+    /// See [`ContentReference::content_format`]
+    #[serde(rename = "content_format")]
+    pub content_format: String,
+    /// See [`ContentReference::content_size_in_bytes`]
+    #[serde(rename = "content_size_in_bytes")]
+    pub content_size_in_bytes: i64,
+    /// See [`ContentReference::file_version`]
+    #[serde(rename = "file_version")]
+    pub file_version: String,
+    /// See [`ContentReference::marketplace`]
+    pub marketplace: String,
+    /// See [`ContentReference::sku`]
+    pub sku: String,
+    /// See [`ContentReference::tempo`]
+    pub tempo: String,
+    /// See [`ContentReference::version`]
+    pub version: String,
+}
+
+/// Strict variant of [`LastPositionHeard`]
+#[derive(Debug, Clone, PartialEq, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct StrictLastPositionHeard {
+    /// See [`LastPositionHeard::last_updated`]
+    #[serde(rename = "last_updated")]
+    pub last_updated: Option<String>,
+    /// See [`LastPositionHeard::position_ms`]
+    #[serde(rename = "position_ms")]
+    pub position_ms: Option<i64>,
+    /// See [`LastPositionHeard::status`]
+    pub status: String,
+}
+
+impl From<StrictAudibleChapters> for AudibleChapters {
+    fn from(strict: StrictAudibleChapters) -> Self {
+        Self {
+            content_metadata: strict.content_metadata.into(),
+            response_groups: strict.response_groups,
+        }
+    }
+}
+
+impl From<StrictContentMetadata> for ContentMetadata {
+    fn from(strict: StrictContentMetadata) -> Self {
+        Self {
+            chapter_info: strict.chapter_info.into(),
+            content_reference: strict.content_reference.into(),
+            last_position_heard: strict.last_position_heard.into(),
+        }
+    }
+}
+
+impl From<StrictChapterInfo> for ChapterInfo {
+    fn from(strict: StrictChapterInfo) -> Self {
+        Self {
+            brand_intro_duration_ms: strict.brand_intro_duration_ms,
+            brand_outro_duration_ms: strict.brand_outro_duration_ms,
+            chapters: strict.chapters.into_iter().map(ChapterNode::from).collect(),
+            is_accurate: strict.is_accurate,
+            runtime_length_ms: strict.runtime_length_ms,
+            runtime_length_sec: strict.runtime_length_sec,
+        }
+    }
+}
+
+impl From<StrictChapterNode> for ChapterNode {
+    fn from(strict: StrictChapterNode) -> Self {
+        Self {
+            length_ms: strict.length_ms,
+            start_offset_ms: strict.start_offset_ms,
+            start_offset_sec: strict.start_offset_sec,
+            title: strict.title,
+            chapters: strict.chapters.into_iter().map(ChapterNode::from).collect(),
+        }
+    }
+}
+
+impl From<StrictContentReference> for ContentReference {
+    fn from(strict: StrictContentReference) -> Self {
+        Self {
+            acr: strict.acr,
+            asin: strict.asin,
+            codec: strict.codec,
+            content_format: strict.content_format,
+            content_size_in_bytes: strict.content_size_in_bytes,
+            file_version: strict.file_version,
+            marketplace: strict.marketplace,
+            sku: strict.sku,
+            tempo: strict.tempo,
+            version: strict.version,
+        }
+    }
+}
+
+impl From<StrictLastPositionHeard> for LastPositionHeard {
+    fn from(strict: StrictLastPositionHeard) -> Self {
+        Self {
+            last_updated: strict.last_updated,
+            position_ms: strict.position_ms,
+            status: strict.status,
+        }
+    }
+}
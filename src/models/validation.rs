@@ -0,0 +1,39 @@
+/// Outcome of a validation pass: `Ok` carries warnings, `Err` carries every fatal error found
+pub type ValidationResult = Result<Vec<String>, Vec<String>>;
+
+/// Accumulates warnings and errors so a validation pass can report every issue at once
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Validator {
+    warnings: Vec<String>,
+    errors: Vec<String>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.errors.push(message.into());
+    }
+
+    pub fn warning(&mut self, message: impl Into<String>) {
+        self.warnings.push(message.into());
+    }
+
+    /// Merge the result of a nested validation, prefixing each message with the field it came from
+    pub fn nested(&mut self, field: &str, result: ValidationResult) {
+        match result {
+            Ok(warnings) => self.warnings.extend(warnings.into_iter().map(|w| format!("{}: {}", field, w))),
+            Err(errors) => self.errors.extend(errors.into_iter().map(|e| format!("{}: {}", field, e))),
+        }
+    }
+
+    pub fn finish(self) -> ValidationResult {
+        if self.errors.is_empty() {
+            Ok(self.warnings)
+        } else {
+            Err(self.errors)
+        }
+    }
+}
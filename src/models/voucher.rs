@@ -1,263 +1,372 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use super::validation::{ValidationResult, Validator};
 
 /// Deserializing the voucher file generated by `audible-cli`
 /// All I need is two fields but since this is a generated struct I'll just leave it as is
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AudibleCliVoucher {
+    /// The license with the decryption key and IV
     #[serde(rename = "content_license")]
     pub content_license: ContentLicense,
+    /// API response groups audible-cli requested
     #[serde(rename = "response_groups")]
     pub response_groups: Vec<String>,
 }
 
 impl AudibleCliVoucher {
-    pub fn validate(&self) -> Result<(), String> {
-        self.content_license.validate().map_err(|e| format!("content_license: {}", e))?;
-        if self.response_groups.is_empty() {
-            return Err("response_groups is empty".to_string());
-        }
-        Ok(())
+    /// Every problem in the voucher, prefixed with the path of the field
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        validator.nested("content_license", self.content_license.validate());
+        if self.response_groups.is_empty() { validator.error("response_groups is empty"); }
+        validator.finish()
+    }
+
+    /// Expiry of the license, from the first parseable `expireDate` in the license rules
+    pub fn license_expiry(&self) -> Option<DateTime<Utc>> {
+        self.content_license
+            .license_response
+            .rules
+            .iter()
+            .flat_map(|rule| &rule.parameters)
+            .find_map(|parameter| parse_iso8601(&parameter.expire_date))
     }
 }
 
+/// Parse an ISO 8601 timestamp, treating values without an offset as UTC
+fn parse_iso8601(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Some(date_time.with_timezone(&Utc));
+    }
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Some(date_time.and_utc());
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|date_time| date_time.and_utc())
+}
+
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// License granted for the book
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ContentLicense {
+    /// Audible content reference of the file
     pub acr: String,
+    /// ASIN of the book
     pub asin: String,
+    /// Identity and download URL of the file
     #[serde(rename = "content_metadata")]
     pub content_metadata: ContentMetadata,
+    /// DRM scheme, `Adrm` or `Mpeg`
     #[serde(rename = "drm_type")]
     pub drm_type: String,
+    /// Right granted by the license, such as `Download`
     #[serde(rename = "granted_right")]
     pub granted_right: String,
+    /// License id
     #[serde(rename = "license_id")]
     pub license_id: String,
+    /// Decryption key, IV and license rules
     #[serde(rename = "license_response")]
     pub license_response: LicenseResponse,
+    /// Type of the license response
     #[serde(rename = "license_response_type")]
     pub license_response_type: String,
+    /// Message from the license request
     pub message: String,
+    /// Playback position synced with the license
     #[serde(rename = "playback_info")]
     pub playback_info: PlaybackInfo,
+    /// Whether this is a license for a preview
     pub preview: bool,
+    /// Id of the license request
     #[serde(rename = "request_id")]
     pub request_id: String,
+    /// Whether playback has to support ads
     #[serde(rename = "requires_ad_supported_playback")]
     pub requires_ad_supported_playback: bool,
+    /// Status of the license request, `Granted` on success
     #[serde(rename = "status_code")]
     pub status_code: String,
+    /// Voucher id
     #[serde(rename = "voucher_id")]
     pub voucher_id: String,
 }
 
 impl ContentLicense {
-    pub fn validate(&self) -> Result<(), String> {
-        if self.acr.trim().is_empty() { return Err("acr is empty".to_string()); }
-        if self.asin.trim().is_empty() { return Err("asin is empty".to_string()); }
-        self.content_metadata.validate().map_err(|e| format!("content_metadata: {}", e))?;
-        if self.drm_type.trim().is_empty() { return Err("drm_type is empty".to_string()); }
-        if self.granted_right.trim().is_empty() { return Err("granted_right is empty".to_string()); }
-        if self.license_id.trim().is_empty() { return Err("license_id is empty".to_string()); }
-        self.license_response.validate().map_err(|e| format!("license_response: {}", e))?;
-        if self.license_response_type.trim().is_empty() { return Err("license_response_type is empty".to_string()); }
-        if self.message.trim().is_empty() { return Err("message is empty".to_string()); }
-        self.playback_info.validate().map_err(|e| format!("playback_info: {}", e))?;
-        if self.request_id.trim().is_empty() { return Err("request_id is empty".to_string()); }
-        if self.status_code.trim().is_empty() { return Err("status_code is empty".to_string()); }
-        if self.voucher_id.trim().is_empty() { return Err("voucher_id is empty".to_string()); }
-        Ok(())
+    /// Problems in the license and its nested sections
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        if self.acr.trim().is_empty() { validator.error("acr is empty"); }
+        if self.asin.trim().is_empty() { validator.error("asin is empty"); }
+        validator.nested("content_metadata", self.content_metadata.validate());
+        if self.drm_type.trim().is_empty() { validator.error("drm_type is empty"); }
+        if self.granted_right.trim().is_empty() { validator.error("granted_right is empty"); }
+        if self.license_id.trim().is_empty() { validator.error("license_id is empty"); }
+        validator.nested("license_response", self.license_response.validate());
+        if self.license_response_type.trim().is_empty() { validator.error("license_response_type is empty"); }
+        if self.message.trim().is_empty() { validator.error("message is empty"); }
+        validator.nested("playback_info", self.playback_info.validate());
+        if self.preview { validator.warning("preview is true, the license may only cover a sample"); }
+        if self.request_id.trim().is_empty() { validator.error("request_id is empty"); }
+        if self.status_code.trim().is_empty() { validator.error("status_code is empty"); }
+        if self.voucher_id.trim().is_empty() { validator.error("voucher_id is empty"); }
+        validator.finish()
     }
 }
 
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Metadata of the licensed file
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ContentMetadata {
+    /// Identity of the file
     #[serde(rename = "content_reference")]
     pub content_reference: ContentReference,
+    /// Where the file is downloaded from
     #[serde(rename = "content_url")]
     pub content_url: ContentUrl,
+    /// Where listening last stopped
     #[serde(rename = "last_position_heard")]
     pub last_position_heard: LastPositionHeard,
 }
 
 impl ContentMetadata {
-    pub fn validate(&self) -> Result<(), String> {
-        self.content_reference.validate().map_err(|e| format!("content_reference: {}", e))?;
-        self.content_url.validate().map_err(|e| format!("content_url: {}", e))?;
-        self.last_position_heard.validate().map_err(|e| format!("last_position_heard: {}", e))?;
-        Ok(())
+    /// Problems in the content reference, URL and listening position
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        validator.nested("content_reference", self.content_reference.validate());
+        validator.nested("content_url", self.content_url.validate());
+        validator.nested("last_position_heard", self.last_position_heard.validate());
+        validator.finish()
     }
 }
 
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Identity of the licensed file
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ContentReference {
+    /// Audible content reference of the file
     pub acr: String,
+    /// ASIN of the book
     pub asin: String,
+    /// Audible codec of the file
     pub codec: String,
+    /// Audible format of the file
     #[serde(rename = "content_format")]
     pub content_format: String,
+    /// Size of the file in bytes
     #[serde(rename = "content_size_in_bytes")]
     pub content_size_in_bytes: i64,
+    /// Version of the file
     #[serde(rename = "file_version")]
     pub file_version: String,
+    /// Marketplace the book was bought in
     pub marketplace: String,
+    /// Audible SKU of the book
     pub sku: String,
+    /// Playback tempo the file was made for
     pub tempo: String,
+    /// Version of the content reference
     pub version: String,
 }
 
 impl ContentReference {
-    pub fn validate(&self) -> Result<(), String> {
-        if self.acr.trim().is_empty() { return Err("acr is empty".to_string()); }
-        if self.asin.trim().is_empty() { return Err("asin is empty".to_string()); }
-        if self.codec.trim().is_empty() { return Err("codec is empty".to_string()); }
-        if self.content_format.trim().is_empty() { return Err("content_format is empty".to_string()); }
-        if self.content_size_in_bytes <= 0 { return Err("content_size_in_bytes is not positive".to_string()); }
-        if self.file_version.trim().is_empty() { return Err("file_version is empty".to_string()); }
-        if self.marketplace.trim().is_empty() { return Err("marketplace is empty".to_string()); }
-        if self.sku.trim().is_empty() { return Err("sku is empty".to_string()); }
-        if self.tempo.trim().is_empty() { return Err("tempo is empty".to_string()); }
-        if self.version.trim().is_empty() { return Err("version is empty".to_string()); }
-        Ok(())
+    /// Book identity for logs, e.g. `ASIN: B001234567 [codec: aax_adp, marketplace: us]`
+    pub fn to_display_string(&self) -> String {
+        format!("ASIN: {} [codec: {}, marketplace: {}]", self.asin, self.codec, self.marketplace)
+    }
+
+    /// Empty fields and a non-positive size
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        if self.acr.trim().is_empty() { validator.error("acr is empty"); }
+        if self.asin.trim().is_empty() { validator.error("asin is empty"); }
+        if self.codec.trim().is_empty() { validator.error("codec is empty"); }
+        if self.content_format.trim().is_empty() { validator.error("content_format is empty"); }
+        if self.content_size_in_bytes <= 0 { validator.error("content_size_in_bytes is not positive"); }
+        if self.file_version.trim().is_empty() { validator.error("file_version is empty"); }
+        if self.marketplace.trim().is_empty() { validator.error("marketplace is empty"); }
+        if self.sku.trim().is_empty() { validator.error("sku is empty"); }
+        if self.tempo.trim().is_empty() { validator.error("tempo is empty"); }
+        if self.version.trim().is_empty() { validator.error("version is empty"); }
+        validator.finish()
     }
 }
 
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Download location of the file
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ContentUrl {
+    /// URL of the file for offline listening
     #[serde(rename = "offline_url")]
     pub offline_url: String,
 }
 
 impl ContentUrl {
-    pub fn validate(&self) -> Result<(), String> {
-        if self.offline_url.trim().is_empty() { return Err("offline_url is empty".to_string()); }
-        Ok(())
+    /// An empty URL
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        if self.offline_url.trim().is_empty() { validator.error("offline_url is empty"); }
+        validator.finish()
     }
 }
 
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Where listening last stopped, as stored in the content metadata
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct LastPositionHeard {
+    /// When the position was last synced
     #[serde(rename = "last_updated")]
     pub last_updated: Option<String>,
+    /// Position in ms
     #[serde(rename = "position_ms")]
     pub position_ms: Option<i64>,
+    /// Sync status as reported by Audible
     pub status: String,
 }
 
 impl LastPositionHeard {
-    pub fn validate(&self) -> Result<(), String> {
+    /// Empty fields and a negative position
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
         if let Some(ref last_updated) = self.last_updated {
-            if last_updated.trim().is_empty() { return Err("last_updated is empty".to_string()); }
+            if last_updated.trim().is_empty() { validator.error("last_updated is empty"); }
         }
         if let Some(position_ms) = self.position_ms {
-            if position_ms < 0 { return Err("position_ms is negative".to_string()); }
+            if position_ms < 0 { validator.error("position_ms is negative"); }
         }
-        if self.status.trim().is_empty() { return Err("status is empty".to_string()); }
-        Ok(())
+        if self.status.trim().is_empty() { validator.error("status is empty"); }
+        validator.finish()
     }
 }
 
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// The decryption key and license rules
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct LicenseResponse {
+    /// AES key, hex encoded
     pub key: String,
+    /// AES initialization vector, hex encoded
     pub iv: String,
+    /// Rules restricting the license
     pub rules: Vec<Rule>,
 }
 
 impl LicenseResponse {
-    pub fn validate(&self) -> Result<(), String> {
-        if self.key.trim().is_empty() { return Err("key is empty".to_string()); }
-        if self.iv.trim().is_empty() { return Err("iv is empty".to_string()); }
+    /// An empty key or IV and invalid rules
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        if self.key.trim().is_empty() { validator.error("key is empty"); }
+        if self.iv.trim().is_empty() { validator.error("iv is empty"); }
         for (i, rule) in self.rules.iter().enumerate() {
-            rule.validate().map_err(|e| format!("rules[{}]: {}", i, e))?;
+            validator.nested(&format!("rules[{}]", i), rule.validate());
         }
-        Ok(())
+        validator.finish()
     }
 }
 
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A rule restricting the license
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Rule {
+    /// Parameters of the rule
     pub parameters: Vec<Parameter>,
+    /// Rule name, such as `DefaultExpiresRule`
     pub name: String,
 }
 
 impl Rule {
-    pub fn validate(&self) -> Result<(), String> {
-        if self.name.trim().is_empty() { return Err("name is empty".to_string()); }
+    /// An empty name and invalid parameters
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        if self.name.trim().is_empty() { validator.error("name is empty"); }
         for (i, param) in self.parameters.iter().enumerate() {
-            param.validate().map_err(|e| format!("parameters[{}]: {}", i, e))?;
+            validator.nested(&format!("parameters[{}]", i), param.validate());
         }
-        Ok(())
+        validator.finish()
     }
 }
 
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A parameter of a license rule
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Parameter {
+    /// When the license expires
     pub expire_date: String,
+    /// Parameter type, `type` in the file
     #[serde(rename = "type")]
     pub type_field: String,
 }
 
 impl Parameter {
-    pub fn validate(&self) -> Result<(), String> {
-        if self.expire_date.trim().is_empty() { return Err("expire_date is empty".to_string()); }
-        if self.type_field.trim().is_empty() { return Err("type is empty".to_string()); }
-        Ok(())
+    /// Empty fields
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        if self.expire_date.trim().is_empty() { validator.error("expire_date is empty"); }
+        if self.type_field.trim().is_empty() { validator.error("type is empty"); }
+        validator.finish()
     }
 }
 
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Playback state synced with the license
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct PlaybackInfo {
+    /// Where listening last stopped
     #[serde(rename = "last_position_heard")]
     pub last_position_heard: LastPositionHeard2,
 }
 
 impl PlaybackInfo {
-    pub fn validate(&self) -> Result<(), String> {
-        self.last_position_heard.validate().map_err(|e| format!("last_position_heard: {}", e))?;
-        Ok(())
+    /// Problems in the listening position
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
+        validator.nested("last_position_heard", self.last_position_heard.validate());
+        validator.finish()
     }
 }
 
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Where listening last stopped, as stored in the playback info
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct LastPositionHeard2 {
+    /// When the position was last synced
     #[serde(rename = "last_updated")]
     pub last_updated: Option<String>,
+    /// Position in ms
     #[serde(rename = "position_ms")]
     pub position_ms: Option<i64>,
+    /// Sync status as reported by Audible
     pub status: String,
 }
 
 impl LastPositionHeard2 {
-    pub fn validate(&self) -> Result<(), String> {
+    /// Empty fields and a negative position
+    pub fn validate(&self) -> ValidationResult {
+        let mut validator = Validator::new();
         if let Some(ref last_updated) = self.last_updated {
-            if last_updated.trim().is_empty() { return Err("last_updated is empty".to_string()); }
+            if last_updated.trim().is_empty() { validator.error("last_updated is empty"); }
         }
         if let Some(position_ms) = self.position_ms {
-            if position_ms < 0 { return Err("position_ms is negative".to_string()); }
+            if position_ms < 0 { validator.error("position_ms is negative"); }
         }
-        if self.status.trim().is_empty() { return Err("status is empty".to_string()); }
-        Ok(())
+        if self.status.trim().is_empty() { validator.error("status is empty"); }
+        validator.finish()
     }
 }
 
@@ -0,0 +1,755 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use is_terminal::IsTerminal;
+use serde::Serialize;
+
+use log::info;
+
+use crate::convert::{relative_path, BenchmarkResult, BookInfo, ValidationReport};
+
+/// Machine-readable progress events for JSON output
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type")]
+pub(crate) enum ProgressEvent {
+    #[serde(rename = "conversion_started")]
+    ConversionStarted {
+        total_chapters: usize,
+        output_format: String,
+        output_path: String,
+        asin: String,
+        book_title: String,
+        artist: String,
+        /// Audio stream of the input, unknown in pipeline mode
+        audio_codec: Option<String>,
+        sample_rate_hz: Option<u32>,
+        channels: Option<u32>,
+        source_bitrate_bps: Option<u64>,
+        /// Chapters declared by the book, 0 without chapter data
+        chapter_count: u64,
+        total_audio_duration_ms: u64,
+        max_chapter_depth: u64,
+        shortest_chapter_ms: u64,
+        longest_chapter_ms: u64,
+    },
+    #[serde(rename = "chapter_started")]
+    ChapterStarted {
+        chapter_number: usize,
+        total_chapters: usize,
+        chapter_title: String,
+        duration_seconds: f64,
+    },
+    #[serde(rename = "chapter_progress")]
+    ChapterProgress {
+        chapter_number: usize,
+        total_chapters: usize,
+        chapter_title: String,
+        progress_percentage: f64,
+        current_time: f64,
+        total_duration: f64,
+        speed: f64,
+        bitrate: f64,
+        file_size: u64,
+        fps: f64,
+        eta_seconds: Option<f64>,
+    },
+    #[serde(rename = "chapter_completed")]
+    ChapterCompleted {
+        chapter_number: usize,
+        total_chapters: usize,
+        chapter_title: String,
+        output_file: String,
+        duration_seconds: f64,
+        /// Estimated time left for the remaining chapters, once a conversion speed is known
+        overall_eta_seconds: Option<f64>,
+    },
+    #[serde(rename = "conversion_completed")]
+    ConversionCompleted {
+        total_chapters: usize,
+        total_duration_seconds: f64,
+        success: bool,
+        peak_speed: f64,
+        average_speed: f64,
+        /// Sum of the output file sizes; 0 in single-file mode
+        total_output_bytes: u64,
+    },
+    #[serde(rename = "benchmark_result")]
+    BenchmarkResult(BenchmarkResult),
+    #[serde(rename = "validation_report")]
+    ValidationReport(ValidationReport),
+    #[serde(rename = "error")]
+    Error {
+        message: String,
+        chapter_number: Option<usize>,
+    },
+    #[serde(rename = "warning")]
+    Warning {
+        code: String,
+        message: String,
+        chapter_number: Option<usize>,
+        retry_count: Option<u32>,
+    },
+}
+
+impl ProgressEvent {
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+/// Estimates the time left for a split conversion from the speed of the chapters converted so far
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OverallEtaEstimator {
+    /// Audio not converted yet
+    pub(crate) remaining_audio_ms: i64,
+    /// Running average of the completed chapters' conversion speeds
+    pub(crate) average_speed: f64,
+    pub(crate) speed_samples: u64,
+}
+
+impl OverallEtaEstimator {
+    pub(crate) fn new(total_audio_ms: i64) -> Self {
+        Self {
+            remaining_audio_ms: total_audio_ms.max(0),
+            ..Default::default()
+        }
+    }
+
+    /// Record a completed chapter of `audio_ms`, converted at `speed` (0 when ffmpeg reported none)
+    pub(crate) fn complete_chapter(&mut self, audio_ms: i64, speed: f64) {
+        self.remaining_audio_ms = (self.remaining_audio_ms - audio_ms.max(0)).max(0);
+        if speed > 0.0 {
+            self.average_speed = (self.average_speed * self.speed_samples as f64 + speed) / (self.speed_samples + 1) as f64;
+            self.speed_samples += 1;
+        }
+    }
+
+    /// `remaining_audio_ms / average_speed`, or None until a speed has been recorded
+    pub(crate) fn eta(&self) -> Option<Duration> {
+        (self.average_speed > 0.0)
+            .then(|| Duration::from_secs_f64(self.remaining_audio_ms as f64 / 1000.0 / self.average_speed))
+    }
+}
+
+/// Progress tracking information for a single conversion
+#[derive(Debug, Clone)]
+pub(crate) struct ConversionProgress {
+    pub(crate) current_time: f64,
+    pub(crate) total_duration: f64,
+    pub(crate) speed: f64,
+    pub(crate) bitrate: f64,
+    pub(crate) size: u64,
+    pub(crate) fps: f64,
+    pub(crate) peak_speed: f64,
+    pub(crate) average_speed: f64,
+    pub(crate) speed_samples: u64,
+}
+
+impl ConversionProgress {
+    pub(crate) fn new(total_duration: f64) -> Self {
+        Self {
+            current_time: 0.0,
+            total_duration,
+            speed: 0.0,
+            bitrate: 0.0,
+            size: 0,
+            fps: 0.0,
+            peak_speed: 0.0,
+            average_speed: 0.0,
+            speed_samples: 0,
+        }
+    }
+
+    /// Set the current speed and fold it into the running peak and average
+    pub(crate) fn record_speed(&mut self, speed: f64) {
+        self.speed = speed;
+        self.peak_speed = speed.max(self.peak_speed);
+        self.average_speed = (self.average_speed * self.speed_samples as f64 + speed) / (self.speed_samples + 1) as f64;
+        self.speed_samples += 1;
+    }
+
+    /// Combine the speed statistics of another conversion into these, weighting averages by sample count
+    fn merge_speed(&mut self, other: &ConversionProgress) {
+        let samples = self.speed_samples + other.speed_samples;
+        if samples > 0 {
+            self.average_speed = (self.average_speed * self.speed_samples as f64
+                + other.average_speed * other.speed_samples as f64)
+                / samples as f64;
+        }
+        self.peak_speed = self.peak_speed.max(other.peak_speed);
+        self.speed_samples = samples;
+    }
+
+    pub(crate) fn percentage(&self) -> f64 {
+        if self.total_duration > 0.0 {
+            (self.current_time / self.total_duration * 100.0).min(100.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub(crate) fn eta(&self) -> Option<Duration> {
+        if self.speed > 0.0 && self.current_time < self.total_duration {
+            let remaining_time = (self.total_duration - self.current_time) / self.speed;
+            Some(Duration::from_secs(remaining_time as u64))
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn format_time(seconds: f64) -> String {
+        let hours = (seconds / 3600.0) as u64;
+        let minutes = ((seconds % 3600.0) / 60.0) as u64;
+        let secs = (seconds % 60.0) as u64;
+        format!("{:02}:{:02}:{:02}", hours, minutes, secs)
+    }
+
+    pub(crate) fn format_size(bytes: u64) -> String {
+        const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
+        let mut size = bytes as f64;
+        let mut unit_index = 0;
+        
+        while size >= 1024.0 && unit_index < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit_index += 1;
+        }
+        
+        format!("{:.1} {}", size, UNITS[unit_index])
+    }
+}
+
+/// Where progress bars are drawn: stderr, or nowhere when they are turned off
+pub(crate) fn progress_draw_target(progress_bars: bool) -> ProgressDrawTarget {
+    if !progress_bars {
+        ProgressDrawTarget::hidden()
+    } else if std::io::stderr().is_terminal() {
+        ProgressDrawTarget::stderr()
+    } else {
+        // --force-progress: indicatif's own stderr target skips drawing when it isn't a terminal
+        ProgressDrawTarget::term_like(Box::new(console::Term::stderr()))
+    }
+}
+
+/// Progress manager for tracking overall conversion progress
+///
+/// The manager is `Send + Sync`: state that changes while chapters are converted lives
+/// behind a mutex, so all methods used during conversion take `&self`.
+pub(crate) struct ProgressManager {
+    multi: MultiProgress,
+    overall_pb: ProgressBar,
+    start_time: Instant,
+    total_chapters: usize,
+    verbose: bool,
+    machine_readable: bool,
+    /// Draw the bars; when false they are hidden and never ticked
+    progress_bars: bool,
+    progress_batch: usize,
+    /// Base directory that paths in events are made relative to, `None` to report them as-is
+    relative_base: Option<PathBuf>,
+    inner: Arc<Mutex<ProgressManagerInner>>,
+}
+
+/// Mutable state of a `ProgressManager`
+struct ProgressManagerInner {
+    current_pb: Option<ProgressBar>,
+    current_chapter: usize,
+    progress_lines: usize,
+    pending_progress: Option<ConversionProgress>,
+    /// Latest progress of the chapter being converted
+    chapter_progress: Option<ConversionProgress>,
+    /// Speed statistics accumulated over all completed chapters
+    speed_summary: ConversionProgress,
+    /// Size of all completed chapter output files
+    total_output_bytes: u64,
+    eta_estimator: OverallEtaEstimator,
+    /// When the chapter being converted was started, for its wall time
+    chapter_started: Instant,
+}
+
+impl ProgressManager {
+    pub(crate) fn new_with_verbose(total_chapters: usize, verbose: bool, progress_bars: bool) -> Self {
+        Self::new_with_options(total_chapters, verbose, false, progress_bars, 1)
+    }
+
+    pub(crate) fn new_machine_readable(total_chapters: usize, progress_batch: usize) -> Self {
+        Self::new_with_options(total_chapters, false, true, false, progress_batch)
+    }
+
+    fn new_with_options(
+        total_chapters: usize,
+        verbose: bool,
+        machine_readable: bool,
+        progress_bars: bool,
+        progress_batch: usize,
+    ) -> Self {
+        let multi = MultiProgress::with_draw_target(progress_draw_target(progress_bars));
+        let overall_pb = multi.add(ProgressBar::new(total_chapters as u64));
+        
+        if !machine_readable {
+            overall_pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("{bar:40.cyan/blue} {pos:>3}/{len:3} chapters [{elapsed_precise}] {msg}")
+                    .unwrap()
+                    .progress_chars("█▉▊▋▌▍▎▏  "),
+            );
+            overall_pb.set_message("Starting conversion...");
+        } else {
+            // Hide progress bars in machine-readable mode
+            overall_pb.set_style(ProgressStyle::default_bar().template("").unwrap());
+        }
+
+        Self {
+            multi,
+            overall_pb,
+            start_time: Instant::now(),
+            total_chapters,
+            verbose,
+            machine_readable,
+            progress_bars,
+            progress_batch: progress_batch.max(1),
+            relative_base: None,
+            inner: Arc::new(Mutex::new(ProgressManagerInner {
+                current_pb: None,
+                current_chapter: 0,
+                progress_lines: 0,
+                pending_progress: None,
+                chapter_progress: None,
+                speed_summary: ConversionProgress::new(0.0),
+                total_output_bytes: 0,
+                eta_estimator: OverallEtaEstimator::default(),
+                chapter_started: Instant::now(),
+            })),
+        }
+    }
+
+    fn lock_inner(&self) -> MutexGuard<'_, ProgressManagerInner> {
+        // Progress state stays usable even if another thread panicked while holding the lock
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Report paths in events relative to `base`
+    pub(crate) fn set_relative_base(&mut self, base: &Path) {
+        self.relative_base = Some(base.to_path_buf());
+    }
+
+    /// Total length of the audio to convert, for the overall ETA
+    pub(crate) fn set_total_audio_ms(&mut self, total_audio_ms: i64) {
+        self.lock_inner().eta_estimator = OverallEtaEstimator::new(total_audio_ms);
+    }
+
+    fn event_path(&self, path: &str) -> String {
+        match &self.relative_base {
+            Some(base) => relative_path(Path::new(path), base).to_string_lossy().to_string(),
+            None => path.to_string(),
+        }
+    }
+
+    pub(crate) fn start_chapter(&self, chapter_title: &str, duration: f64) -> ProgressBar {
+        let mut inner = self.lock_inner();
+        self.flush_progress(&mut inner);
+        inner.current_chapter += 1;
+        inner.chapter_started = Instant::now();
+        
+        if self.machine_readable {
+            let event = ProgressEvent::ChapterStarted {
+                chapter_number: inner.current_chapter,
+                total_chapters: self.total_chapters,
+                chapter_title: chapter_title.to_string(),
+                duration_seconds: duration,
+            };
+            println!("{}", event.to_json());
+        } else {
+            let mut message = format!("Chapter {}/{}: {}", inner.current_chapter, self.total_chapters, chapter_title);
+            if let Some(eta) = inner.eta_estimator.eta() {
+                message.push_str(&format!(" | Overall ETA: {}", ConversionProgress::format_time(eta.as_secs_f64())));
+            }
+            self.overall_pb.set_message(message);
+        }
+
+        let current_pb = self.multi.add(ProgressBar::new(duration as u64));
+        
+        if !self.machine_readable {
+            current_pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("{prefix:.bold} {bar:40.green/yellow} {percent:>3}% [{elapsed_precise}] {msg}")
+                    .unwrap()
+                    .progress_chars("█▉▊▋▌▍▎▏  "),
+            );
+            // The prefix survives the progress updates replacing the message
+            current_pb.set_prefix(Self::chapter_label(inner.current_chapter, self.total_chapters));
+            current_pb.set_message(format!("Converting: {}", chapter_title));
+            if self.progress_bars {
+                current_pb.enable_steady_tick(Duration::from_millis(100));
+            }
+        } else {
+            // Hide progress bars in machine-readable mode
+            current_pb.set_style(ProgressStyle::default_bar().template("").unwrap());
+        }
+
+        inner.current_pb = Some(current_pb.clone());
+        current_pb
+    }
+
+    /// `[Ch 07/47]`, zero-padded to the width of the chapter count (at least 2 digits)
+    fn chapter_label(chapter_number: usize, total_chapters: usize) -> String {
+        let width = total_chapters.to_string().len().max(2);
+        format!("[Ch {:0width$}/{:0width$}]", chapter_number, total_chapters, width = width)
+    }
+
+    pub(crate) fn update_chapter_progress(&self, progress: &ConversionProgress) {
+        let mut inner = self.lock_inner();
+        inner.chapter_progress = Some(progress.clone());
+        if self.machine_readable {
+            // Only emit every `progress_batch` lines, keeping the latest values for the next flush
+            inner.progress_lines += 1;
+            if inner.progress_lines >= self.progress_batch {
+                inner.progress_lines = 0;
+                inner.pending_progress = None;
+                self.emit_chapter_progress(inner.current_chapter, progress);
+            } else {
+                inner.pending_progress = Some(progress.clone());
+            }
+        } else {
+            if let Some(ref pb) = inner.current_pb {
+                pb.set_position(progress.current_time as u64);
+                
+                let eta_str = progress.eta()
+                    .map(|eta| format!("ETA: {}", Self::format_duration(eta)))
+                    .unwrap_or_else(|| "ETA: --:--:--".to_string());
+                
+                let speed_str = if progress.speed > 0.0 {
+                    format!("Speed: {:.1}x", progress.speed)
+                } else {
+                    "Speed: --".to_string()
+                };
+                
+                let bitrate_str = if progress.bitrate > 0.0 {
+                    format!("Bitrate: {:.0} kbps", progress.bitrate / 1000.0)
+                } else {
+                    "Bitrate: --".to_string()
+                };
+                
+                let size_str = if progress.size > 0 {
+                    format!("Size: {}", ConversionProgress::format_size(progress.size))
+                } else {
+                    "Size: --".to_string()
+                };
+
+                let fps_str = if self.verbose && progress.fps > 0.0 {
+                    format!("FPS: {:.1}", progress.fps)
+                } else {
+                    String::new()
+                };
+
+                let time_str = if self.verbose {
+                    format!("Time: {}/{}", 
+                        ConversionProgress::format_time(progress.current_time),
+                        ConversionProgress::format_time(progress.total_duration))
+                } else {
+                    String::new()
+                };
+
+                let mut message_parts = vec![eta_str, speed_str, bitrate_str, size_str];
+                if self.verbose {
+                    if !fps_str.is_empty() {
+                        message_parts.push(fps_str);
+                    }
+                    if !time_str.is_empty() {
+                        message_parts.push(time_str);
+                    }
+                }
+
+                pb.set_message(message_parts.join(" | "));
+            }
+
+            // Log detailed progress in verbose mode
+            if self.verbose {
+                info!("Progress: {:.1}% | Time: {}/{} | Speed: {:.1}x (avg {:.1}x, peak {:.1}x) | Bitrate: {:.0} kbps | Size: {}", 
+                    progress.percentage(),
+                    ConversionProgress::format_time(progress.current_time),
+                    ConversionProgress::format_time(progress.total_duration),
+                    progress.speed,
+                    progress.average_speed,
+                    progress.peak_speed,
+                    progress.bitrate / 1000.0,
+                    ConversionProgress::format_size(progress.size)
+                );
+            }
+        }
+    }
+
+    /// Emit any progress held back by `--progress-batch`
+    pub(crate) fn flush_chapter_progress(&self) {
+        let mut inner = self.lock_inner();
+        self.flush_progress(&mut inner);
+    }
+
+    fn flush_progress(&self, inner: &mut ProgressManagerInner) {
+        inner.progress_lines = 0;
+        if let Some(progress) = inner.pending_progress.take() {
+            self.emit_chapter_progress(inner.current_chapter, &progress);
+        }
+    }
+
+    fn emit_chapter_progress(&self, chapter_number: usize, progress: &ConversionProgress) {
+        let event = ProgressEvent::ChapterProgress {
+            chapter_number,
+            total_chapters: self.total_chapters,
+            chapter_title: "".to_string(), // Will be filled by caller
+            progress_percentage: progress.percentage(),
+            current_time: progress.current_time,
+            total_duration: progress.total_duration,
+            speed: progress.speed,
+            bitrate: progress.bitrate,
+            file_size: progress.size,
+            fps: progress.fps,
+            eta_seconds: progress.eta().map(|eta| eta.as_secs() as f64),
+        };
+        println!("{}", event.to_json());
+    }
+
+    pub(crate) fn complete_chapter(&self, chapter_title: &str, output_file: &str, duration: f64, output_bytes: u64) {
+        let mut inner = self.lock_inner();
+        self.flush_progress(&mut inner);
+        inner.total_output_bytes += output_bytes;
+        let chapter_progress = inner.chapter_progress.take().unwrap_or_else(|| ConversionProgress::new(duration));
+        inner.speed_summary.merge_speed(&chapter_progress);
+        let chapter_speed = chapter_progress.average_speed;
+        let summary = self.verbose.then(|| {
+            Self::chapter_summary(inner.current_chapter, duration, inner.chapter_started.elapsed(), &chapter_progress, output_bytes)
+        });
+        if let Some(summary) = &summary {
+            info!("{}", summary);
+        }
+        inner.eta_estimator.complete_chapter((duration * 1000.0) as i64, chapter_speed);
+        let overall_eta = inner.eta_estimator.eta();
+        if self.machine_readable {
+            let event = ProgressEvent::ChapterCompleted {
+                chapter_number: inner.current_chapter,
+                total_chapters: self.total_chapters,
+                chapter_title: chapter_title.to_string(),
+                output_file: self.event_path(output_file),
+                duration_seconds: duration,
+                overall_eta_seconds: overall_eta.map(|eta| eta.as_secs_f64()),
+            };
+            println!("{}", event.to_json());
+        } else {
+            if let Some(pb) = inner.current_pb.take() {
+                pb.finish_with_message(summary.unwrap_or_else(|| "Chapter completed".to_string()));
+            }
+            if let Some(eta) = overall_eta {
+                self.overall_pb.set_message(format!("Overall ETA: {}", ConversionProgress::format_time(eta.as_secs_f64())));
+            }
+        }
+        self.overall_pb.inc(1);
+    }
+
+    /// e.g. `Chapter 3 completed: audio_duration=00:42:10, wall_time=01:55, speed=22.0x (avg), peak_speed=31.5x, output_size=38.6 MB`
+    fn chapter_summary(chapter_number: usize, duration: f64, wall_time: Duration, progress: &ConversionProgress, output_bytes: u64) -> String {
+        let wall_seconds = wall_time.as_secs();
+        format!(
+            "Chapter {} completed: audio_duration={}, wall_time={:02}:{:02}, speed={:.1}x (avg), peak_speed={:.1}x, output_size={}",
+            chapter_number,
+            ConversionProgress::format_time(duration),
+            wall_seconds / 60,
+            wall_seconds % 60,
+            progress.average_speed,
+            progress.peak_speed,
+            ConversionProgress::format_size(output_bytes),
+        )
+    }
+
+    pub(crate) fn complete_all(&self, success: bool) {
+        let (speed_summary, total_output_bytes) = {
+            let inner = self.lock_inner();
+            (inner.speed_summary.clone(), inner.total_output_bytes)
+        };
+        info!(
+            "Conversion speed: average {:.1}x, peak {:.1}x",
+            speed_summary.average_speed, speed_summary.peak_speed
+        );
+        info!("Total output size: {}", ConversionProgress::format_size(total_output_bytes));
+        if self.machine_readable {
+            let event = ProgressEvent::ConversionCompleted {
+                total_chapters: self.total_chapters,
+                total_duration_seconds: self.start_time.elapsed().as_secs() as f64,
+                success,
+                peak_speed: speed_summary.peak_speed,
+                average_speed: speed_summary.average_speed,
+                total_output_bytes,
+            };
+            println!("{}", event.to_json());
+        } else {
+            self.overall_pb.finish_with_message(format!(
+                "All {} chapters completed in {}",
+                self.total_chapters,
+                Self::format_duration(self.start_time.elapsed())
+            ));
+        }
+    }
+
+    /// Speeds of the completed chapters and the time since the manager was created
+    pub(crate) fn benchmark_result(&self, total_audio_ms: i64) -> BenchmarkResult {
+        let speed_summary = self.lock_inner().speed_summary.clone();
+        BenchmarkResult::new(self.total_chapters, total_audio_ms, &speed_summary, self.start_time.elapsed())
+    }
+
+    pub(crate) fn emit_error(&self, message: &str, chapter_number: Option<usize>) {
+        if self.machine_readable {
+            let event = ProgressEvent::Error {
+                message: message.to_string(),
+                chapter_number,
+            };
+            println!("{}", event.to_json());
+        }
+    }
+
+    pub(crate) fn emit_warning(&self, code: &str, message: &str, chapter_number: Option<usize>, retry_count: Option<u32>) {
+        if self.machine_readable {
+            let event = ProgressEvent::Warning {
+                code: code.to_string(),
+                message: message.to_string(),
+                chapter_number,
+                retry_count,
+            };
+            println!("{}", event.to_json());
+        }
+    }
+
+    pub(crate) fn emit_conversion_started(&self, output_format: &str, output_path: &str, book: &BookInfo) {
+        if self.machine_readable {
+            let event = ProgressEvent::ConversionStarted {
+                total_chapters: self.total_chapters,
+                output_format: output_format.to_string(),
+                output_path: self.event_path(output_path),
+                asin: book.asin.clone(),
+                book_title: book.title.clone(),
+                artist: book.artist.clone(),
+                audio_codec: book.audio_codec.clone(),
+                sample_rate_hz: book.sample_rate_hz,
+                channels: book.channels,
+                source_bitrate_bps: book.source_bitrate_bps,
+                chapter_count: book.chapter_stats.chapter_count,
+                total_audio_duration_ms: book.chapter_stats.total_duration_ms,
+                max_chapter_depth: book.chapter_stats.max_depth,
+                shortest_chapter_ms: book.chapter_stats.shortest_ms(),
+                longest_chapter_ms: book.chapter_stats.longest_ms(),
+            };
+            println!("{}", event.to_json());
+        }
+    }
+
+    pub(crate) fn format_duration(duration: Duration) -> String {
+        let total_seconds = duration.as_secs();
+        let hours = total_seconds / 3600;
+        let minutes = (total_seconds % 3600) / 60;
+        let seconds = total_seconds % 60;
+        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::convert::parse_ffmpeg_progress_line;
+
+    #[test]
+    fn speed_average_of_single_sample() {
+        let mut progress = ConversionProgress::new(60.0);
+        progress.record_speed(4.5);
+        assert_eq!(progress.peak_speed, 4.5);
+        assert_eq!(progress.average_speed, 4.5);
+    }
+
+    #[test]
+    fn speed_merge_weights_by_samples() {
+        let mut first = ConversionProgress::new(60.0);
+        first.record_speed(10.0);
+        let mut second = ConversionProgress::new(60.0);
+        for speed in [40.0, 40.0, 40.0] {
+            second.record_speed(speed);
+        }
+
+        let mut summary = ConversionProgress::new(0.0);
+        summary.merge_speed(&first);
+        summary.merge_speed(&second);
+        assert_eq!(summary.speed_samples, 4);
+        assert_eq!(summary.peak_speed, 40.0);
+        assert!((summary.average_speed - 32.5).abs() < 1e-9);
+
+        // Merging a conversion without speed samples changes nothing
+        summary.merge_speed(&ConversionProgress::new(60.0));
+        assert!((summary.average_speed - 32.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn progress_manager_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ProgressManager>();
+    }
+
+    #[test]
+    fn progress_manager_concurrent_complete_chapter() {
+        let total_chapters = 16;
+        let manager = ProgressManager::new_with_verbose(total_chapters, false, false);
+        std::thread::scope(|scope| {
+            for chapter in 0..total_chapters {
+                let manager = &manager;
+                scope.spawn(move || {
+                    let title = format!("Chapter {}", chapter);
+                    manager.start_chapter(&title, 10.0);
+                    let mut progress = ConversionProgress::new(10.0);
+                    parse_ffmpeg_progress_line("speed=2.0x", &mut progress);
+                    manager.update_chapter_progress(&progress);
+                    manager.complete_chapter(&title, "out.mp3", 10.0, 100);
+                });
+            }
+        });
+
+        assert_eq!(manager.overall_pb.position(), total_chapters as u64);
+        let inner = manager.lock_inner();
+        assert_eq!(inner.current_chapter, total_chapters);
+        assert!(inner.speed_summary.speed_samples >= 1);
+        assert_eq!(inner.speed_summary.peak_speed, 2.0);
+        assert_eq!(inner.total_output_bytes, 100 * total_chapters as u64);
+    }
+
+    #[test]
+    fn chapter_label_pads_to_chapter_count() {
+        assert_eq!(ProgressManager::chapter_label(7, 47), "[Ch 07/47]");
+        assert_eq!(ProgressManager::chapter_label(1, 5), "[Ch 01/05]");
+        assert_eq!(ProgressManager::chapter_label(7, 210), "[Ch 007/210]");
+    }
+
+    #[test]
+    fn chapter_summary_line() {
+        let mut progress = ConversionProgress::new(2530.0);
+        for speed in [20.0, 31.5, 14.5] {
+            progress.record_speed(speed);
+        }
+        assert_eq!(
+            ProgressManager::chapter_summary(3, 2530.0, Duration::from_secs(115), &progress, 40_474_624),
+            "Chapter 3 completed: audio_duration=00:42:10, wall_time=01:55, speed=22.0x (avg), peak_speed=31.5x, output_size=38.6 MB",
+        );
+    }
+
+    #[test]
+    fn overall_eta_uses_average_chapter_speed() {
+        let mut estimator = OverallEtaEstimator::new(100_000);
+        assert_eq!(estimator.eta(), None);
+
+        // No speed reported yet, only the remaining audio shrinks
+        estimator.complete_chapter(20_000, 0.0);
+        assert_eq!(estimator.eta(), None);
+
+        estimator.complete_chapter(20_000, 10.0);
+        estimator.complete_chapter(20_000, 30.0);
+        // 40s of audio left at an average of 20x
+        assert_eq!(estimator.eta(), Some(Duration::from_secs(2)));
+
+        estimator.complete_chapter(50_000, 20.0);
+        assert_eq!(estimator.eta(), Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use log::info;
+
+/// A book extracted from a ZIP archive (--zip-path)
+///
+/// Extracted into a temporary directory unless --zip-extract-dir is given; the temporary
+/// directory is removed when this is dropped.
+pub struct ExtractedBook {
+    /// The .aaxc audio file
+    pub aaxc_path: PathBuf,
+    /// The voucher file
+    pub voucher_path: PathBuf,
+    /// The chapters file, when the archive has one
+    pub chapters_path: Option<PathBuf>,
+    _temp_dir: Option<tempfile::TempDir>,
+}
+
+/// Which of the book's files an archive entry is, by its file name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BookFile {
+    Aaxc,
+    Voucher,
+    Chapters,
+}
+
+impl BookFile {
+    fn from_file_name(file_name: &str) -> Option<Self> {
+        let lower = file_name.to_ascii_lowercase();
+        if lower.ends_with("-chapters.json") {
+            Some(BookFile::Chapters)
+        } else if lower.ends_with(".aaxc") {
+            Some(BookFile::Aaxc)
+        } else if lower.ends_with(".voucher") {
+            Some(BookFile::Voucher)
+        } else {
+            None
+        }
+    }
+}
+
+/// Extract the .aaxc, .voucher and optional -chapters.json from `zip_path` into `extract_dir`,
+/// or a temporary directory. Other entries are ignored, directories inside the archive are not kept.
+pub fn extract_book(zip_path: &Path, extract_dir: Option<&Path>) -> Result<ExtractedBook> {
+    let file = File::open(zip_path).with_context(|| format!("Failed to open ZIP file: {}", zip_path.display()))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .with_context(|| format!("Failed to read ZIP file: {}", zip_path.display()))?;
+
+    let mut entries: Vec<(BookFile, usize, String)> = Vec::new();
+    for index in 0..archive.len() {
+        let entry = archive.by_index(index)?;
+        if entry.is_dir() {
+            continue;
+        }
+        // Only the file name is used, so entries can't be written outside the extraction directory
+        let Some(file_name) = entry.enclosed_name().and_then(|path| path.file_name().map(|name| name.to_string_lossy().to_string())) else {
+            continue;
+        };
+        if let Some(kind) = BookFile::from_file_name(&file_name) {
+            entries.push((kind, index, file_name));
+        }
+    }
+    check_book_files(&entries.iter().map(|(kind, _, name)| (*kind, name.as_str())).collect::<Vec<_>>())
+        .map_err(|message| anyhow::anyhow!("{} {}", zip_path.display(), message))?;
+
+    let (temp_dir, target_dir) = match extract_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create extraction directory: {}", dir.display()))?;
+            (None, dir.to_path_buf())
+        }
+        None => {
+            let temp_dir = tempfile::Builder::new()
+                .prefix("audible-util-")
+                .tempdir()
+                .context("Failed to create a temporary directory for the ZIP file")?;
+            let path = temp_dir.path().to_path_buf();
+            (Some(temp_dir), path)
+        }
+    };
+
+    let mut aaxc_path = None;
+    let mut voucher_path = None;
+    let mut chapters_path = None;
+    for (kind, index, file_name) in entries {
+        let destination = target_dir.join(&file_name);
+        let mut entry = archive.by_index(index)?;
+        let mut output = File::create(&destination)
+            .with_context(|| format!("Failed to create extracted file: {}", destination.display()))?;
+        std::io::copy(&mut entry, &mut output)
+            .with_context(|| format!("Failed to extract {} from {}", file_name, zip_path.display()))?;
+        info!("Extracted {} to {}", file_name, destination.display());
+        match kind {
+            BookFile::Aaxc => aaxc_path = Some(destination),
+            BookFile::Voucher => voucher_path = Some(destination),
+            BookFile::Chapters => chapters_path = Some(destination),
+        }
+    }
+
+    Ok(ExtractedBook {
+        aaxc_path: aaxc_path.context("No .aaxc file was extracted")?,
+        voucher_path: voucher_path.context("No .voucher file was extracted")?,
+        chapters_path,
+        _temp_dir: temp_dir,
+    })
+}
+
+/// Exactly one .aaxc and one .voucher, and at most one -chapters.json
+fn check_book_files(files: &[(BookFile, &str)]) -> Result<(), String> {
+    for (kind, description, required) in [
+        (BookFile::Aaxc, ".aaxc file", true),
+        (BookFile::Voucher, ".voucher file", true),
+        (BookFile::Chapters, "-chapters.json file", false),
+    ] {
+        let names: Vec<&str> = files.iter().filter(|(file, _)| *file == kind).map(|(_, name)| *name).collect();
+        match names.len() {
+            0 if required => return Err(format!("contains no {}", description)),
+            0 | 1 => {}
+            _ => return Err(format!("contains more than one {}: {}", description, names.join(", "))),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_book_files() {
+        assert_eq!(BookFile::from_file_name("Book-AAX_44_128.aaxc"), Some(BookFile::Aaxc));
+        assert_eq!(BookFile::from_file_name("Book-AAX_44_128.voucher"), Some(BookFile::Voucher));
+        assert_eq!(BookFile::from_file_name("Book-chapters.json"), Some(BookFile::Chapters));
+        assert_eq!(BookFile::from_file_name("cover.jpg"), None);
+    }
+
+    #[test]
+    fn requires_one_book_and_voucher() {
+        use BookFile::*;
+        assert_eq!(check_book_files(&[(Aaxc, "a.aaxc"), (Voucher, "a.voucher")]), Ok(()));
+        assert_eq!(check_book_files(&[(Voucher, "a.voucher")]), Err("contains no .aaxc file".to_string()));
+        assert_eq!(check_book_files(&[(Aaxc, "a.aaxc")]), Err("contains no .voucher file".to_string()));
+        assert_eq!(
+            check_book_files(&[(Aaxc, "a.aaxc"), (Voucher, "a.voucher"), (Chapters, "a-chapters.json"), (Chapters, "b-chapters.json")]),
+            Err("contains more than one -chapters.json file: a-chapters.json, b-chapters.json".to_string())
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use log::info;
+
+/// A file downloaded for an http(s) --chapter-path
+///
+/// Downloaded into a temporary directory that is removed when this is dropped, unless the
+/// file is kept with --keep-temp-files.
+pub struct DownloadedFile {
+    /// Where the file was downloaded to
+    pub path: PathBuf,
+    _temp_dir: Option<tempfile::TempDir>,
+}
+
+/// The URL when `value` is an http or https URL; anything else is a local path
+pub fn http_url(value: &str) -> Option<url::Url> {
+    url::Url::parse(value).ok().filter(|url| matches!(url.scheme(), "http" | "https"))
+}
+
+/// GET `url` with the given headers and write the response body to a temporary file,
+/// named after the last segment of the URL's path.
+pub fn download_file(url: &url::Url, headers: &[(String, String)], timeout: Duration, keep: bool) -> Result<DownloadedFile> {
+    let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+    let mut request = agent.request_url("GET", url);
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    info!("Downloading {}", url);
+    let response = request.call().with_context(|| format!("Failed to download {}", url))?;
+
+    let temp_dir = tempfile::Builder::new()
+        .prefix("audible-util-")
+        .tempdir()
+        .context("Failed to create a temporary directory for the download")?;
+    let path = temp_dir.path().join(download_file_name(url));
+    let mut output = File::create(&path)
+        .with_context(|| format!("Failed to create downloaded file: {}", path.display()))?;
+    std::io::copy(&mut response.into_reader(), &mut output)
+        .with_context(|| format!("Failed to download {}", url))?;
+    info!("Downloaded {} to {}", url, path.display());
+
+    let temp_dir = if keep {
+        let _kept_dir = temp_dir.keep();
+        info!("Keeping downloaded file: {}", path.display());
+        None
+    } else {
+        Some(temp_dir)
+    };
+    Ok(DownloadedFile { path, _temp_dir: temp_dir })
+}
+
+/// The last segment of the URL's path, `chapters.json` when the path ends in a slash
+fn download_file_name(url: &url::Url) -> String {
+    url.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .and_then(|segment| Path::new(segment).file_name())
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "chapters.json".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_http_urls_are_downloaded() {
+        assert!(http_url("https://example.com/book-chapters.json").is_some());
+        assert!(http_url("http://localhost:8080/book-chapters.json").is_some());
+        assert!(http_url("book-chapters.json").is_none());
+        assert!(http_url("/srv/books/book-chapters.json").is_none());
+        assert!(http_url("file:///srv/books/book-chapters.json").is_none());
+        assert!(http_url("C:\\books\\book-chapters.json").is_none());
+    }
+
+    #[test]
+    fn names_download_after_url_path() {
+        let name = |url: &str| download_file_name(&url::Url::parse(url).unwrap());
+        assert_eq!(name("https://example.com/books/Book-chapters.json?token=abc"), "Book-chapters.json");
+        assert_eq!(name("https://example.com/books/"), "chapters.json");
+        assert_eq!(name("https://example.com"), "chapters.json");
+        assert_eq!(name("https://example.com/books/.."), "chapters.json");
+    }
+}
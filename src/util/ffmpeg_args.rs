@@ -0,0 +1,250 @@
+use std::ffi::OsString;
+use std::path::Path;
+
+/// Builder for an ffmpeg command line.
+///
+/// Options are collected in any order and [`FFmpegArgs::build`] puts them where ffmpeg expects
+/// them, e.g. the decryption options before `-i` and the output file last.
+#[derive(Debug, Clone, Default)]
+pub struct FFmpegArgs {
+    log_level: Option<String>,
+    no_stats: bool,
+    input_options: Vec<String>,
+    input_time_range: Option<(String, String)>,
+    input: Option<OsString>,
+    metadata_input: Option<OsString>,
+    threads: Option<String>,
+    time_range: Option<(String, String)>,
+    progress_pipe: Option<String>,
+    overwrite: bool,
+    extra_args: Vec<OsString>,
+    codec: Option<String>,
+    output_args: Vec<OsString>,
+    output: Option<OsString>,
+}
+
+impl FFmpegArgs {
+    /// Empty argument list
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ffmpeg's `-loglevel`
+    pub fn log_level(mut self, level: &str) -> Self {
+        self.log_level = Some(level.to_string());
+        self
+    }
+
+    /// Don't print the encoding statistics line
+    pub fn no_stats(mut self) -> Self {
+        self.no_stats = true;
+        self
+    }
+
+    /// Input file
+    pub fn input(mut self, path: &Path) -> Self {
+        self.input = Some(path.as_os_str().to_os_string());
+        self
+    }
+
+    /// Second input with metadata only, such as an ffmetadata file of chapters
+    pub fn metadata_input(mut self, path: &Path) -> Self {
+        self.metadata_input = Some(path.as_os_str().to_os_string());
+        self
+    }
+
+    /// Decrypt an .aaxc input
+    pub fn decryption_key(mut self, key: &str, iv: &str) -> Self {
+        self.input_options = vec!["-audible_key".to_string(), key.to_string(), "-audible_iv".to_string(), iv.to_string()];
+        self
+    }
+
+    /// Decrypt an .aax input
+    pub fn activation_bytes(mut self, activation_bytes: &str) -> Self {
+        self.input_options = vec!["-activation_bytes".to_string(), activation_bytes.to_string()];
+        self
+    }
+
+    /// Number of threads, passed as `-threads` after the inputs
+    pub fn threads(mut self, threads: &str) -> Self {
+        self.threads = Some(threads.to_string());
+        self
+    }
+
+    /// Read only `duration` from `start` of the input. Seeking on the input skips decoding
+    /// everything before `start`, unlike [`FFmpegArgs::time_range`].
+    pub fn input_time_range(mut self, start: &str, duration: &str) -> Self {
+        self.input_time_range = Some((start.to_string(), duration.to_string()));
+        self
+    }
+
+    /// Convert only `duration` from `start`, both in ffmpeg's time format
+    pub fn time_range(mut self, start: &str, duration: &str) -> Self {
+        self.time_range = Some((start.to_string(), duration.to_string()));
+        self
+    }
+
+    /// Write `-progress` key=value lines to `path`
+    pub fn progress_pipe(mut self, path: &str) -> Self {
+        self.progress_pipe = Some(path.to_string());
+        self
+    }
+
+    /// Overwrite the output file without asking
+    pub fn overwrite(mut self) -> Self {
+        self.overwrite = true;
+        self
+    }
+
+    /// Output options such as `-metadata` or `-af`, kept in the order they are added
+    pub fn extra_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.extra_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Audio codec of the output; video streams such as cover art are dropped
+    pub fn codec(mut self, codec: &str) -> Self {
+        self.codec = Some(codec.to_string());
+        self
+    }
+
+    /// Options that follow the codec, such as `-b:a` or `-f`, kept in the order they are added
+    pub fn output_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.output_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Output file, or a protocol such as `pipe:1`
+    pub fn output(mut self, output: impl Into<OsString>) -> Self {
+        self.output = Some(output.into());
+        self
+    }
+
+    /// The arguments in the order ffmpeg expects them
+    pub fn build(self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = Vec::new();
+        if let Some(level) = self.log_level {
+            args.extend(["-loglevel".into(), level.into()]);
+        }
+        if self.no_stats {
+            args.push("-nostats".into());
+        }
+        // Input options only apply to the input that follows them
+        args.extend(self.input_options.into_iter().map(OsString::from));
+        if let Some((start, duration)) = self.input_time_range {
+            args.extend(["-ss".into(), start.into(), "-t".into(), duration.into()]);
+        }
+        if let Some(input) = self.input {
+            args.extend(["-i".into(), input]);
+        }
+        if let Some(metadata_input) = self.metadata_input {
+            args.extend(["-i".into(), metadata_input]);
+        }
+        if let Some(threads) = self.threads {
+            args.extend(["-threads".into(), threads.into()]);
+        }
+        if let Some((start, duration)) = self.time_range {
+            args.extend(["-ss".into(), start.into(), "-t".into(), duration.into()]);
+        }
+        if let Some(progress_pipe) = self.progress_pipe {
+            args.extend(["-progress".into(), progress_pipe.into()]);
+        }
+        if self.overwrite {
+            args.push("-y".into());
+        }
+        args.extend(self.extra_args);
+        if let Some(codec) = self.codec {
+            args.extend(["-vn".into(), "-codec:a".into(), codec.into()]);
+        }
+        args.extend(self.output_args);
+        if let Some(output) = self.output {
+            args.push(output);
+        }
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: Vec<OsString>) -> Vec<String> {
+        args.into_iter().map(|arg| arg.to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn chapter_arguments_in_ffmpeg_order() {
+        // Added out of order on purpose
+        let args = FFmpegArgs::new()
+            .output("out/Chapter01.tmp.mp3")
+            .output_args(["-b:a", "128000"])
+            .codec("mp3")
+            .extra_args(["-metadata", "track=1/2"])
+            .time_range("00:00:00.000", "00:00:40.000")
+            .progress_pipe("/dev/stdout")
+            .overwrite()
+            .threads("0")
+            .input(Path::new("book.aaxc"))
+            .decryption_key("00ff", "ff00")
+            .log_level("error")
+            .build();
+        assert_eq!(strings(args), [
+            "-loglevel", "error",
+            "-audible_key", "00ff", "-audible_iv", "ff00",
+            "-i", "book.aaxc",
+            "-threads", "0",
+            "-ss", "00:00:00.000", "-t", "00:00:40.000",
+            "-progress", "/dev/stdout",
+            "-y",
+            "-metadata", "track=1/2",
+            "-vn", "-codec:a", "mp3",
+            "-b:a", "128000",
+            "out/Chapter01.tmp.mp3",
+        ]);
+    }
+
+    #[test]
+    fn decryption_comes_before_the_input() {
+        let args = strings(FFmpegArgs::new().input(Path::new("book.aax")).activation_bytes("1a2b3c4d").build());
+        assert_eq!(args, ["-activation_bytes", "1a2b3c4d", "-i", "book.aax"]);
+    }
+
+    #[test]
+    fn input_time_range_comes_before_the_input() {
+        let args = FFmpegArgs::new()
+            .input(Path::new("book.aaxc"))
+            .input_time_range("00:01:00.000", "00:00:04.000")
+            .decryption_key("00ff", "ff00")
+            .build();
+        assert_eq!(strings(args), [
+            "-audible_key", "00ff", "-audible_iv", "ff00",
+            "-ss", "00:01:00.000", "-t", "00:00:04.000",
+            "-i", "book.aaxc",
+        ]);
+    }
+
+    #[test]
+    fn metadata_input_follows_the_decrypted_input() {
+        let args = FFmpegArgs::new()
+            .metadata_input(Path::new("book.ffmetadata"))
+            .input(Path::new("book.aax"))
+            .activation_bytes("1a2b3c4d")
+            .build();
+        assert_eq!(strings(args), ["-activation_bytes", "1a2b3c4d", "-i", "book.aax", "-i", "book.ffmetadata"]);
+    }
+
+    #[test]
+    fn unset_options_are_left_out() {
+        assert!(FFmpegArgs::new().build().is_empty());
+        let args = strings(FFmpegArgs::new().no_stats().output_args(["-f", "mp3"]).output("pipe:1").build());
+        assert_eq!(args, ["-nostats", "-f", "mp3", "pipe:1"]);
+    }
+}
@@ -0,0 +1,239 @@
+use std::path::PathBuf;
+
+/// How characters outside ASCII are treated when sanitizing titles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EncodingMode {
+    /// Keep only ASCII letters and digits, the safest choice across filesystems and players
+    #[default]
+    Ascii,
+    /// Keep any Unicode letters and digits
+    Unicode,
+}
+
+/// Options for turning a chapter title into a filename component
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SanitizeConfig {
+    /// Character used in place of spaces and path separators
+    pub replacement: char,
+    /// Characters kept in addition to letters, digits and the replacement character
+    pub allowed_extra: Vec<char>,
+    /// Maximum length of the result in characters, `None` for no limit
+    pub max_length: Option<usize>,
+    /// How characters outside ASCII are treated
+    pub encoding: EncodingMode,
+}
+
+impl Default for SanitizeConfig {
+    fn default() -> Self {
+        Self {
+            replacement: '_',
+            allowed_extra: vec!['-'],
+            max_length: None,
+            encoding: EncodingMode::Ascii,
+        }
+    }
+}
+
+/// Names Windows refuses to use as a file name, with or without an extension
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Sanitize a title for use in a filename.
+///
+/// Spaces and path separators become the replacement character, every other character that isn't
+/// a letter, digit or explicitly allowed is dropped. Windows reserved names get the replacement
+/// character appended. The result may be empty when nothing in the title survives.
+pub fn sanitize_title(title: &str, config: &SanitizeConfig) -> String {
+    let mut sanitized: String = title
+        .chars()
+        .map(|c| match c {
+            ' ' | '/' | '\\' => config.replacement,
+            c => c,
+        })
+        .filter(|c| {
+            let is_alphanumeric = match config.encoding {
+                EncodingMode::Ascii => c.is_ascii_alphanumeric(),
+                EncodingMode::Unicode => c.is_alphanumeric(),
+            };
+            is_alphanumeric || *c == config.replacement || config.allowed_extra.contains(c)
+        })
+        .collect();
+
+    if let Some(max_length) = config.max_length {
+        if let Some((index, _)) = sanitized.char_indices().nth(max_length) {
+            sanitized.truncate(index);
+        }
+    }
+
+    if is_windows_reserved_name(&sanitized) {
+        if config.max_length.is_some_and(|max_length| sanitized.chars().count() >= max_length) {
+            sanitized.pop();
+        }
+        sanitized.push(config.replacement);
+    }
+
+    sanitized
+}
+
+fn is_windows_reserved_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name);
+    WINDOWS_RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+/// Expand a directory template like `{artist}/{album}` into a relative path.
+///
+/// Placeholders are resolved with `lookup`, and each path component is sanitized like a chapter
+/// title, so values can't introduce extra directories. Values that are missing or sanitize to
+/// nothing become `Unknown`. Fails on unknown placeholders and unterminated braces.
+pub fn expand_dir_template(
+    template: &str,
+    lookup: impl Fn(&str) -> Option<Option<String>>,
+) -> Result<PathBuf, String> {
+    let config = SanitizeConfig::default();
+    let mut path = PathBuf::new();
+    for segment in template.split(['/', '\\']).filter(|segment| !segment.is_empty()) {
+        let mut component = String::new();
+        let mut rest = segment;
+        while let Some(start) = rest.find('{') {
+            component.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unterminated placeholder in '{}'", segment))?;
+            let name = &rest[start + 1..start + end];
+            let value = lookup(name).ok_or_else(|| format!("unknown placeholder {{{}}}", name))?;
+            component.push_str(value.as_deref().unwrap_or_default());
+            rest = &rest[start + end + 1..];
+        }
+        component.push_str(rest);
+
+        let component = sanitize_title(&component, &config);
+        path.push(if component.is_empty() { "Unknown" } else { &component });
+    }
+    if path.as_os_str().is_empty() {
+        return Err("template is empty".to_string());
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sanitize(title: &str) -> String {
+        sanitize_title(title, &SanitizeConfig::default())
+    }
+
+    #[test]
+    fn empty_title_stays_empty() {
+        assert_eq!(sanitize(""), "");
+    }
+
+    #[test]
+    fn only_special_chars_are_filtered_to_empty() {
+        assert_eq!(sanitize(":?*\"<>|"), "");
+        assert_eq!(sanitize("!@#$%^&()"), "");
+    }
+
+    #[test]
+    fn spaces_and_separators_are_replaced() {
+        assert_eq!(sanitize("Part One: Empire / Chapter 1?"), "Part_One_Empire___Chapter_1");
+        assert_eq!(sanitize("a\\b"), "a_b");
+        assert_eq!(sanitize("   "), "___");
+    }
+
+    #[test]
+    fn allowed_chars_are_kept() {
+        assert_eq!(sanitize("Chapter_1-Prologue"), "Chapter_1-Prologue");
+    }
+
+    #[test]
+    fn unicode_is_dropped_in_ascii_mode() {
+        assert_eq!(sanitize("Café Über"), "Caf_ber");
+        assert_eq!(sanitize("第一章"), "");
+    }
+
+    #[test]
+    fn unicode_is_kept_in_unicode_mode() {
+        let config = SanitizeConfig { encoding: EncodingMode::Unicode, ..Default::default() };
+        assert_eq!(sanitize_title("Café Über", &config), "Café_Über");
+        assert_eq!(sanitize_title("第一章: 始まり", &config), "第一章_始まり");
+    }
+
+    #[test]
+    fn windows_reserved_names_are_suffixed() {
+        assert_eq!(sanitize("CON"), "CON_");
+        assert_eq!(sanitize("prn"), "prn_");
+        assert_eq!(sanitize("Aux"), "Aux_");
+        assert_eq!(sanitize("LPT1"), "LPT1_");
+        assert_eq!(sanitize("Console"), "Console");
+        assert_eq!(sanitize("CON 2"), "CON_2");
+    }
+
+    #[test]
+    fn very_long_titles_are_truncated() {
+        let title = "a".repeat(10_000);
+        assert_eq!(sanitize(&title).len(), 10_000);
+
+        let config = SanitizeConfig { max_length: Some(255), ..Default::default() };
+        assert_eq!(sanitize_title(&title, &config).len(), 255);
+    }
+
+    #[test]
+    fn truncation_respects_char_boundaries() {
+        let config = SanitizeConfig {
+            max_length: Some(3),
+            encoding: EncodingMode::Unicode,
+            ..Default::default()
+        };
+        assert_eq!(sanitize_title("Éééé", &config), "Ééé");
+    }
+
+    #[test]
+    fn truncation_to_reserved_name_stays_within_limit() {
+        let config = SanitizeConfig { max_length: Some(3), ..Default::default() };
+        assert_eq!(sanitize_title("Console", &config), "Co_");
+    }
+
+    #[test]
+    fn custom_replacement_and_extra_chars() {
+        let config = SanitizeConfig {
+            replacement: '.',
+            allowed_extra: vec!['-', '(', ')'],
+            ..Default::default()
+        };
+        assert_eq!(sanitize_title("Book 1 (Abridged)", &config), "Book.1.(Abridged)");
+    }
+
+    fn lookup(name: &str) -> Option<Option<String>> {
+        match name {
+            "artist" => Some(Some("Brandon Sanderson".to_string())),
+            "album" => Some(Some("Mistborn: The Final Empire".to_string())),
+            "series" => Some(Some("../..".to_string())),
+            "genre" => Some(None),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expands_placeholders_per_component() {
+        let path = expand_dir_template("{artist}/{album}", lookup).unwrap();
+        assert_eq!(path, PathBuf::from("Brandon_Sanderson").join("Mistborn_The_Final_Empire"));
+    }
+
+    #[test]
+    fn template_values_cannot_escape_the_directory() {
+        let path = expand_dir_template("{series}/{genre} books", lookup).unwrap();
+        assert_eq!(path, PathBuf::from("_").join("_books"));
+        assert_eq!(expand_dir_template("{genre}", lookup).unwrap(), PathBuf::from("Unknown"));
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(expand_dir_template("{narrator}", lookup).is_err());
+        assert!(expand_dir_template("{artist", lookup).is_err());
+        assert!(expand_dir_template("/", lookup).is_err());
+    }
+}
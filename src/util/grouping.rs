@@ -0,0 +1,176 @@
+use crate::cli::MergeDirection;
+use crate::models::{FlattenedChapter, MergedChapter};
+
+/// Group chapters by the top-level part they belong to, in book order, as `(part title, chapters)`.
+///
+/// Parts are the `disc_number`s assigned when flattening, the same grouping used for disc tags and
+/// the by-disc split structure. A book without parts is a single group with an empty title.
+pub fn group_chapters_by_part(chapters: &[FlattenedChapter]) -> Vec<(String, Vec<&FlattenedChapter>)> {
+    let mut groups: Vec<(usize, String, Vec<&FlattenedChapter>)> = Vec::new();
+    for chapter in chapters {
+        match groups.last_mut() {
+            Some((disc_number, _, group)) if *disc_number == chapter.disc_number => group.push(chapter),
+            _ => groups.push((chapter.disc_number, chapter.disc_title.clone(), vec![chapter])),
+        }
+    }
+    groups.into_iter().map(|(_, title, group)| (title, group)).collect()
+}
+
+/// Merge each run of consecutive short chapters into a single neighbouring chapter.
+///
+/// The whole run goes into the first long chapter after it (`Next`) or the last long chapter
+/// before it (`Previous`), falling back to the other side when there is none. A run with no
+/// long chapter on either side is kept as one chapter.
+pub fn merge_short_chapters(chapters: &[FlattenedChapter], min_duration_ms: i64, direction: MergeDirection) -> Vec<MergedChapter> {
+    let mut merged_chapters: Vec<MergedChapter> = Vec::new();
+    let mut i = 0;
+    
+    while i < chapters.len() {
+        let current_chapter = &chapters[i];
+        
+        if current_chapter.should_include(min_duration_ms) {
+            // This chapter is long enough, add it as-is
+            merged_chapters.push(MergedChapter::from_flattened(current_chapter));
+            i += 1;
+            continue;
+        }
+        
+        // Collect the whole run of short chapters, dropping empty ones (length_ms <= 0)
+        let mut group = Vec::new();
+        while i < chapters.len() && !chapters[i].should_include(min_duration_ms) {
+            if chapters[i].should_merge_with_next(min_duration_ms) {
+                group.push(&chapters[i]);
+            }
+            i += 1;
+        }
+        if group.is_empty() {
+            continue;
+        }
+        
+        let has_next = i < chapters.len();
+        let has_previous = !merged_chapters.is_empty();
+        let into_next = match direction {
+            MergeDirection::Next => has_next || !has_previous,
+            MergeDirection::Previous => !has_previous && has_next,
+        };
+        
+        let mut target = if into_next && has_next {
+            // Chapter i is the first long chapter after the run
+            let target = MergedChapter::from_flattened(&chapters[i]);
+            i += 1;
+            target
+        } else if let Some(previous) = merged_chapters.pop() {
+            previous
+        } else {
+            // No long chapter on either side, keep the run as one chapter
+            MergedChapter::from_flattened(group.remove(0))
+        };
+        for short_chapter in group {
+            target.merge_with(short_chapter);
+        }
+        merged_chapters.push(target);
+    }
+    
+    merged_chapters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ChapterInfo, ChapterNode};
+
+    fn node(title: &str, start_offset_ms: i64, length_ms: i64, chapters: Vec<ChapterNode>) -> ChapterNode {
+        ChapterNode { title: title.to_string(), start_offset_ms, length_ms, start_offset_sec: start_offset_ms / 1000, chapters }
+    }
+
+    fn flattened(title: &str, start_offset_ms: i64, length_ms: i64, chapter_number: usize) -> FlattenedChapter {
+        FlattenedChapter {
+            title: title.to_string(),
+            full_path: title.to_string(),
+            start_offset_ms,
+            length_ms,
+            start_offset_sec: start_offset_ms / 1000,
+            level: 0,
+            chapter_number,
+            global_chapter_number: chapter_number,
+            disc_number: 1,
+            disc_title: String::new(),
+        }
+    }
+
+    fn titles<'a>(groups: &[(String, Vec<&'a FlattenedChapter>)]) -> Vec<(String, Vec<&'a str>)> {
+        groups
+            .iter()
+            .map(|(part, chapters)| (part.clone(), chapters.iter().map(|chapter| chapter.title.as_str()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn groups_chapters_by_top_level_part() {
+        let info = ChapterInfo {
+            chapters: vec![
+                node("Part 1", 0, 0, vec![node("Chapter 1", 0, 1000, vec![]), node("Chapter 2", 1000, 1000, vec![])]),
+                node("Part 2", 2000, 0, vec![node("Chapter 3", 2000, 1000, vec![node("Scene", 2500, 500, vec![])])]),
+            ],
+            ..Default::default()
+        };
+        let chapters = info.flatten(false);
+        assert_eq!(titles(&group_chapters_by_part(&chapters)), [
+            ("Part 1".to_string(), vec!["Part_1_Chapter_1", "Part_1_Chapter_2"]),
+            ("Part 2".to_string(), vec!["Part_2_Chapter_3", "Part_2_Chapter_3_Scene"]),
+        ]);
+    }
+
+    #[test]
+    fn book_without_parts_is_one_group() {
+        let info = ChapterInfo {
+            chapters: vec![node("Chapter 1", 0, 1000, vec![]), node("Chapter 2", 1000, 1000, vec![])],
+            ..Default::default()
+        };
+        let chapters = info.flatten(false);
+        assert_eq!(titles(&group_chapters_by_part(&chapters)), [(String::new(), vec!["Chapter 1", "Chapter 2"])]);
+        assert!(group_chapters_by_part(&[]).is_empty());
+    }
+
+    #[test]
+    fn merges_consecutive_short_chapters_into_one_target() {
+        let chapters = vec![
+            flattened("Opening", 0, 60_000, 1),
+            flattened("Short A", 60_000, 1_000, 2),
+            flattened("Short B", 61_000, 2_000, 3),
+            flattened("Short C", 63_000, 3_000, 4),
+            flattened("Long", 66_000, 60_000, 5),
+        ];
+
+        let next = merge_short_chapters(&chapters, 10_000, MergeDirection::Next);
+        assert_eq!(next.len(), 2);
+        assert_eq!(next[0].merged_chapters, vec!["Opening"]);
+        assert_eq!(next[1].chapter_number, 5);
+        assert_eq!(next[1].merged_chapters, vec!["Long", "Short A", "Short B", "Short C"]);
+        assert_eq!((next[1].start_offset_ms, next[1].length_ms), (60_000, 66_000));
+
+        let previous = merge_short_chapters(&chapters, 10_000, MergeDirection::Previous);
+        assert_eq!(previous.len(), 2);
+        assert_eq!(previous[0].merged_chapters, vec!["Opening", "Short A", "Short B", "Short C"]);
+        assert_eq!((previous[0].start_offset_ms, previous[0].length_ms), (0, 66_000));
+        assert_eq!(previous[1].merged_chapters, vec!["Long"]);
+    }
+
+    #[test]
+    fn merge_falls_back_to_the_other_side() {
+        let chapters = vec![
+            flattened("Short A", 0, 1_000, 1),
+            flattened("Short B", 1_000, 1_000, 2),
+            flattened("Long", 2_000, 60_000, 3),
+            flattened("Short C", 62_000, 1_000, 4),
+        ];
+
+        let next = merge_short_chapters(&chapters, 10_000, MergeDirection::Next);
+        assert_eq!(next.len(), 1);
+        assert_eq!((next[0].start_offset_ms, next[0].length_ms), (0, 63_000));
+
+        let previous = merge_short_chapters(&chapters, 10_000, MergeDirection::Previous);
+        assert_eq!(previous.len(), 1);
+        assert_eq!(previous[0].merged_chapters, vec!["Long", "Short A", "Short B", "Short C"]);
+    }
+}
@@ -0,0 +1,14 @@
+/// Extracting a book from a ZIP archive
+pub mod archive;
+/// Downloading chapter files over http(s)
+pub mod download;
+/// Building ffmpeg command lines
+pub mod ffmpeg_args;
+/// Turning chapter titles into file names
+pub mod filename;
+/// Grouping chapters into output files
+pub mod grouping;
+/// Running ffprobe on the input
+pub mod probe;
+/// Cleaning up temporary files
+pub mod temp_file;
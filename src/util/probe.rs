@@ -0,0 +1,86 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+use crate::models::FFProbeFormat;
+
+/// Probe the input file's format, streams and tags with ffprobe, giving up after `timeout`
+pub fn ffprobe(aaxc_file_path: &Path, timeout: Duration) -> Result<FFProbeFormat> {
+    let mut ffprobe_cmd = Command::new("ffprobe");
+    ffprobe_cmd
+        .args([
+            "-i",
+            aaxc_file_path
+                .to_str()
+                .context("Failed to convert input file path to string.")?,
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+            "-sexagesimal",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let ffprobe_cmd = match output_with_timeout(&mut ffprobe_cmd, timeout) {
+        Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, format!(
+                "ffprobe did not finish within {} seconds and was stopped. The input file may be corrupted; use --ffprobe-timeout to allow more time.",
+                timeout.as_secs()
+            )).into());
+        },
+        result => result.with_context(|| "Failed to execute ffprobe. Is ffprobe installed and available in your PATH?")?,
+    };
+
+    if !ffprobe_cmd.status.success() {
+        let stderr = String::from_utf8_lossy(&ffprobe_cmd.stderr);
+        anyhow::bail!(
+            "ffprobe failed with error:\n{}\nPlease ensure the input file is a valid Audible AAXC file.",
+            stderr
+        );
+    }
+
+    let ffprobe_output = std::str::from_utf8(&ffprobe_cmd.stdout)
+        .context("Failed to parse ffprobe output as UTF-8.")?;
+    let ffprobe_json: FFProbeFormat = serde_json::from_str(ffprobe_output)
+        .context("Failed to parse ffprobe output as JSON. The file may not be a valid Audible AAXC file.")?;
+    Ok(ffprobe_json)
+}
+
+/// Run `cmd` to completion and collect its output, killing it if it runs longer than `timeout`
+pub fn output_with_timeout(cmd: &mut Command, timeout: Duration) -> std::io::Result<std::process::Output> {
+    fn read_all(pipe: Option<impl std::io::Read + Send + 'static>) -> std::thread::JoinHandle<Vec<u8>> {
+        std::thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buffer);
+            }
+            buffer
+        })
+    }
+
+    let mut child = cmd.spawn()?;
+    // Drain the pipes while waiting so a chatty process can't block on a full pipe
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "process timed out"));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    Ok(std::process::Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
@@ -0,0 +1,60 @@
+use std::path::{Path, PathBuf};
+
+use log::{error, info, warn};
+
+/// Temporary path ffmpeg writes to before the output is moved into place, e.g.
+/// `Chapter01_Intro.tmp.mp3` for `Chapter01_Intro.mp3`. The extension is kept last so
+/// ffmpeg still picks the output format from it.
+pub fn temp_output_path(output_path: &Path) -> PathBuf {
+    let stem = output_path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match output_path.extension() {
+        Some(extension) => format!("{}.tmp.{}", stem, extension.to_string_lossy()),
+        None => format!("{}.tmp", stem),
+    };
+    output_path.with_file_name(file_name)
+}
+
+/// Removes a temporary file when dropped, unless it was persisted.
+///
+/// With `keep` set (--keep-temp-files) the file is left behind and its path logged instead,
+/// so a failed conversion can be inspected.
+pub struct TempFileGuard {
+    path: PathBuf,
+    keep: bool,
+    persisted: bool,
+}
+
+impl TempFileGuard {
+    /// Guard for `path`, kept on disk after drop when `keep` is set
+    pub fn new(path: PathBuf, keep: bool) -> Self {
+        Self { path, keep, persisted: false }
+    }
+
+    /// Path of the temporary file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Move the temporary file to `destination`, replacing any existing file
+    pub fn persist(mut self, destination: &Path) -> std::io::Result<()> {
+        std::fs::rename(&self.path, destination)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFileGuard {
+    fn drop(&mut self) {
+        if self.persisted || !self.path.exists() {
+            return;
+        }
+        if self.keep {
+            // Logged as an error so it shows up with the default log level next to the failure
+            error!("Keeping temporary file for inspection: {}", self.path.display());
+        } else if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("Failed to remove temporary file {}: {}", self.path.display(), e);
+        } else {
+            info!("Removed temporary file: {}", self.path.display());
+        }
+    }
+}
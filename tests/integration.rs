@@ -1,12 +1,12 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use tempfile::NamedTempFile;
-use std::fs::File;
 use std::io::Write;
 use std::env;
 use std::os::unix::fs::PermissionsExt;
 
-// Sample valid voucher JSON
+// Sample valid voucher JSON. Rule parameters use the voucher's camelCase keys (`expireDate`),
+// otherwise the voucher doesn't deserialize and every BookFixture run fails before converting.
 const VALID_VOUCHER: &str = r#"{
   "content_license": {
     "acr": "acr",
@@ -39,7 +39,7 @@ const VALID_VOUCHER: &str = r#"{
       "iv": "iv",
       "rules": [{
         "parameters": [{
-          "expire_date": "date",
+          "expireDate": "date",
           "type": "type"
         }],
         "name": "rule"
@@ -91,9 +91,123 @@ const VALID_FFPROBE: &str = r#"{
       "copyright": "copyright",
       "date": "2020"
     }
-  }
+  },
+  "streams": [
+    {
+      "index": 0,
+      "codec_name": "aac",
+      "codec_type": "audio",
+      "sample_rate": "44100",
+      "channels": 2,
+      "bit_rate": "125588"
+    }
+  ]
 }"#;
 
+// Sample valid chapters JSON, as written by audible-cli
+const VALID_CHAPTERS: &str = r#"{
+  "content_metadata": {
+    "chapter_info": {
+      "brandIntroDurationMs": 2000,
+      "brandOutroDurationMs": 5000,
+      "chapters": [
+        { "length_ms": 40000, "start_offset_ms": 0, "start_offset_sec": 0, "title": "Opening Credits" },
+        { "length_ms": 60000, "start_offset_ms": 40000, "start_offset_sec": 40, "title": "Chapter 1" }
+      ],
+      "is_accurate": true,
+      "runtime_length_ms": 100000,
+      "runtime_length_sec": 100
+    },
+    "content_reference": {
+      "acr": "acr",
+      "asin": "asin",
+      "codec": "codec",
+      "content_format": "format",
+      "content_size_in_bytes": 123,
+      "file_version": "1",
+      "marketplace": "market",
+      "sku": "sku",
+      "tempo": "tempo",
+      "version": "v1"
+    },
+    "last_position_heard": {
+      "last_updated": "now",
+      "position_ms": 0,
+      "status": "ok"
+    }
+  },
+  "response_groups": ["chapter_info"]
+}"#;
+
+/// A book laid out the way audible-cli downloads it (`book.aaxc`, `book.voucher`,
+/// `book-chapters.json`), plus stub `ffprobe`/`ffmpeg` scripts so runs don't need real binaries.
+/// The stub ffprobe prints `VALID_FFPROBE`; the stub ffmpeg reports its version, or logs its arguments,
+/// creates its output file and exits.
+struct BookFixture {
+    dir: tempfile::TempDir,
+}
+
+impl BookFixture {
+    fn new(chapters_json: &str) -> Self {
+        Self::with_ffmpeg_version(chapters_json, "6.1.1")
+    }
+
+    fn with_ffmpeg_version(chapters_json: &str, ffmpeg_version: &str) -> Self {
+        let dir = tempfile::tempdir().expect("temp dir");
+        std::fs::write(dir.path().join("book.aaxc"), "").unwrap();
+        std::fs::write(dir.path().join("book.voucher"), VALID_VOUCHER).unwrap();
+        std::fs::write(dir.path().join("book-chapters.json"), chapters_json).unwrap();
+
+        let bin = dir.path().join("bin");
+        std::fs::create_dir(&bin).unwrap();
+        std::fs::write(bin.join("ffprobe.json"), VALID_FFPROBE).unwrap();
+        let ffprobe = format!("#!/bin/sh\n/bin/cat '{}'\n", bin.join("ffprobe.json").display());
+        let ffmpeg = format!(
+            "#!/bin/sh\nif [ \"$1\" = -version ]; then echo 'ffmpeg version {}'; exit 0; fi\n\
+             if [ \"$1\" = -hide_banner ]; then exit 0; fi\n\
+             echo \"$@\" >> '{}'\nfor last; do :; done\n: > \"$last\"\necho progress=end\n",
+            ffmpeg_version,
+            bin.join("ffmpeg.log").display()
+        );
+        for (name, script) in [("ffprobe", ffprobe.as_str()), ("ffmpeg", ffmpeg.as_str())] {
+            std::fs::write(bin.join(name), script).unwrap();
+            std::fs::set_permissions(bin.join(name), std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        Self { dir }
+    }
+
+    fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+
+    /// The argument lines of every stub ffmpeg invocation so far
+    fn ffmpeg_calls(&self) -> Vec<String> {
+        std::fs::read_to_string(self.path().join("bin").join("ffmpeg.log"))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// A command for this book with the stubs first on PATH and output going to `out/`
+    fn command(&self) -> Command {
+        let out = self.path().join("out");
+        std::fs::create_dir_all(&out).unwrap();
+        let mut cmd = Command::cargo_bin("audible-util").unwrap();
+        cmd.env("PATH", format!("{}:/usr/bin:/bin", self.path().join("bin").display()));
+        cmd.arg("--aaxc_path").arg(self.path().join("book.aaxc"));
+        cmd.arg("--output-path").arg(&out);
+        cmd
+    }
+}
+
+/// `VALID_CHAPTERS` with its top-level `"chapters": [...]` entry replaced
+fn chapters_json_with(chapters: &str) -> String {
+    let start = VALID_CHAPTERS.find("\"chapters\": [").unwrap();
+    let end = start + VALID_CHAPTERS[start..].find("],").unwrap() + 1;
+    format!("{}{}{}", &VALID_CHAPTERS[..start], chapters, &VALID_CHAPTERS[end..])
+}
+
 fn write_temp_file(contents: &str, suffix: &str) -> NamedTempFile {
     use tempfile::Builder;
     let mut file = Builder::new()
@@ -325,7 +439,7 @@ fn test_voucher_data_validation() {
           "iv": "iv",
           "rules": [{
             "parameters": [{
-              "expire_date": "date",
+              "expireDate": "date",
               "type": "type"
             }],
             "name": "rule"
@@ -398,4 +512,1483 @@ fn test_real_conversion_aborted() {
         "Expected abort/killed message in stderr, got: {}",
         stderr
     );
-}
\ No newline at end of file
+}
+#[test]
+fn test_progress_batch_rejects_zero() {
+    let aaxc = write_temp_file("", ".aaxc");
+    let mut cmd = Command::cargo_bin("audible-util").unwrap();
+    cmd.arg("--aaxc_path").arg(aaxc.path());
+    cmd.arg("--machine-readable");
+    cmd.arg("--progress-batch").arg("0");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--progress-batch"));
+}
+
+#[test]
+fn test_verify_requires_metadata_only() {
+    let aaxc = write_temp_file("", ".aaxc");
+    let mut cmd = Command::cargo_bin("audible-util").unwrap();
+    cmd.arg("--aaxc_path").arg(aaxc.path());
+    cmd.arg("--split");
+    cmd.arg("--verify");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--metadata-only"));
+}
+
+#[test]
+fn test_split_metadata_only_with_stub_tools() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Chapter01_Opening_Credits.mp3"))
+        .stdout(predicate::str::contains("Chapter02_Chapter_1.mp3"));
+}
+
+#[test]
+fn test_metadata_only_verify() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let out = book.path().join("out");
+    book.command()
+        .args(["--split", "--metadata-only", "--verify"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Chapter01_Opening_Credits.mp3 [FAILED: file is missing]"))
+        .stderr(predicate::str::contains("2 of 2 chapter files failed verification"));
+
+    // The stub ffprobe reports 60 s for every chapter file, the length of the second chapter only
+    book.command().arg("--split").assert().success();
+    let bin = book.path().join("bin");
+    let ffprobe = format!(
+        "#!/bin/sh\ncase \"$*\" in *format=duration*) echo 60.000000; exit 0;; esac\nexec /bin/cat '{}'\n",
+        bin.join("ffprobe.json").display()
+    );
+    std::fs::write(bin.join("ffprobe"), ffprobe).unwrap();
+    book.command()
+        .args(["--split", "--metadata-only", "--verify"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Chapter01_Opening_Credits.mp3 [FAILED: duration is 60.0s, expected 40.0s]"))
+        .stdout(predicate::str::contains("Chapter02_Chapter_1.mp3 [OK]"))
+        .stderr(predicate::str::contains("1 of 2 chapter files failed verification"));
+    assert!(out.join("Chapter02_Chapter_1.mp3").is_file());
+}
+
+#[test]
+fn test_metadata_only_timestamps() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only").arg("--timestamps");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("[00:00:00 - 00:00:40] (00:40) "))
+        .stdout(predicate::str::contains("[00:00:40 - 00:01:40] (01:00) "));
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--timestamps");
+    cmd.assert().failure().stderr(predicate::str::contains("--metadata-only"));
+}
+
+#[test]
+fn test_strict_json_rejects_unknown_fields() {
+    let chapters = VALID_CHAPTERS.replace("\"is_accurate\": true,", "\"is_accurate\": true,\n      \"new_field\": 1,");
+    let book = BookFixture::new(&chapters);
+
+    let mut lenient = book.command();
+    lenient.arg("--split").arg("--metadata-only");
+    lenient.assert().success();
+
+    let mut strict = book.command();
+    strict.arg("--split").arg("--metadata-only").arg("--strict-json");
+    strict.assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown field `new_field`"));
+}
+
+#[test]
+fn test_disc_tags_follow_parts() {
+    let parts = r#""chapters": [
+        { "length_ms": 40000, "start_offset_ms": 0, "start_offset_sec": 0, "title": "Part One", "chapters": [
+          { "length_ms": 40000, "start_offset_ms": 0, "start_offset_sec": 0, "title": "Chapter 1" }
+        ] },
+        { "length_ms": 60000, "start_offset_ms": 40000, "start_offset_sec": 40, "title": "Part Two", "chapters": [
+          { "length_ms": 30000, "start_offset_ms": 40000, "start_offset_sec": 40, "title": "Chapter 2" },
+          { "length_ms": 30000, "start_offset_ms": 70000, "start_offset_sec": 70, "title": "Chapter 3" }
+        ] }
+      ]"#;
+    let book = BookFixture::new(&chapters_json_with(parts));
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--disc-tags");
+    cmd.assert().success();
+
+    // Parts with their own length are converted as chapters too
+    let calls = book.ffmpeg_calls();
+    let expected = ["disc=1/2", "disc=1/2", "disc=2/2", "disc=2/2", "disc=2/2"];
+    assert_eq!(calls.len(), expected.len(), "{:?}", calls);
+    for (call, disc) in calls.iter().zip(expected) {
+        assert!(call.contains(&format!("-metadata {}", disc)), "expected {} in: {}", disc, call);
+    }
+}
+
+#[test]
+fn test_split_structure_by_disc() {
+    let parts = r#""chapters": [
+        { "length_ms": 0, "start_offset_ms": 0, "start_offset_sec": 0, "title": "Part One", "chapters": [
+          { "length_ms": 40000, "start_offset_ms": 0, "start_offset_sec": 0, "title": "Chapter 1" }
+        ] },
+        { "length_ms": 0, "start_offset_ms": 40000, "start_offset_sec": 40, "title": "Part Two: Return", "chapters": [
+          { "length_ms": 30000, "start_offset_ms": 40000, "start_offset_sec": 40, "title": "Chapter 2" },
+          { "length_ms": 30000, "start_offset_ms": 70000, "start_offset_sec": 70, "title": "Chapter 3" }
+        ] }
+      ]"#;
+    let book = BookFixture::new(&chapters_json_with(parts));
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--split-structure").arg("by-disc").arg("--metadata-only");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Disc01_Part_One/"))
+        .stdout(predicate::str::contains("Disc02_Part_Two_Return/"))
+        .stdout(predicate::str::contains("Disc03").not());
+}
+
+#[test]
+fn test_zero_length_parent_keeps_children() {
+    // Shaped like audible-cli output for books whose parts only group chapters
+    let chapters = r#""chapters": [
+        { "length_ms": 40000, "start_offset_ms": 0, "start_offset_sec": 0, "title": "Opening Credits" },
+        { "length_ms": 0, "start_offset_ms": 40000, "start_offset_sec": 40, "title": "Part One", "chapters": [
+          { "length_ms": 30000, "start_offset_ms": 40000, "start_offset_sec": 40, "title": "Chapter 1" },
+          { "length_ms": 30000, "start_offset_ms": 70000, "start_offset_sec": 70, "title": "Chapter 2" }
+        ] }
+      ]"#;
+    let book = BookFixture::new(&chapters_json_with(chapters));
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Chapter01_Opening_Credits.mp3"))
+        .stdout(predicate::str::contains("Chapter02_Part_One_Chapter_1.mp3"))
+        .stdout(predicate::str::contains("Chapter03_Part_One_Chapter_2.mp3"))
+        .stdout(predicate::str::contains("Chapter0").count(3));
+}
+
+#[test]
+fn test_fractional_min_chapter_duration() {
+    let chapters = r#""chapters": [
+        { "length_ms": 40000, "start_offset_ms": 0, "start_offset_sec": 0, "title": "Opening Credits" },
+        { "length_ms": 300, "start_offset_ms": 40000, "start_offset_sec": 40, "title": "Marker" },
+        { "length_ms": 59700, "start_offset_ms": 40300, "start_offset_sec": 40, "title": "Chapter 1" }
+      ]"#;
+    let book = BookFixture::new(&chapters_json_with(chapters));
+
+    // 0.5s filters the 300 ms marker chapter
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only").arg("--min-chapter-duration").arg("0.5");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Marker.mp3").not())
+        .stdout(predicate::str::contains("Chapter0").count(2));
+
+    // 0.1s keeps it
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only").arg("--min-chapter-duration").arg("0.1");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Chapter02_Marker.mp3"))
+        .stdout(predicate::str::contains("Chapter0").count(3));
+}
+
+#[test]
+fn test_chapter_encoding_utf16() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut utf16 = vec![0xFF, 0xFE];
+    for unit in VALID_CHAPTERS.encode_utf16() {
+        utf16.extend_from_slice(&unit.to_le_bytes());
+    }
+    std::fs::write(book.path().join("book-chapters.json"), utf16).unwrap();
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--chapter-encoding"));
+
+    for encoding in ["utf-16-le", "auto"] {
+        let mut cmd = book.command();
+        cmd.arg("--split").arg("--metadata-only").arg("--chapter-encoding").arg(encoding);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Chapter02_Chapter_1.mp3"));
+    }
+}
+
+#[test]
+fn test_title_case() {
+    let book = BookFixture::new(&VALID_CHAPTERS.replace("Opening Credits", "OPENING CREDITS"));
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only").arg("--title-case").arg("title");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Chapter01_Opening_Credits.mp3"));
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only").arg("--title-case").arg("sentence");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Chapter01_Opening_credits.mp3"));
+}
+
+#[test]
+fn test_test_chapter() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--test-chapter").arg("2");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("TEST RUN"));
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 1, "{:?}", calls);
+    assert!(calls[0].ends_with("Chapter02_Chapter_1.tmp.mp3"), "{}", calls[0]);
+    assert!(book.path().join("out").join("Chapter02_Chapter_1.mp3").exists());
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--test-chapter").arg("3");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("out of range"));
+}
+
+#[test]
+fn test_aiff_output_codec() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--output-type").arg("aiff");
+    cmd.assert().success();
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 1, "{:?}", calls);
+    assert!(calls[0].contains("-codec:a pcm_s16be"), "{}", calls[0]);
+    assert!(calls[0].ends_with(".aiff"), "{}", calls[0]);
+}
+
+#[test]
+fn test_default_bitrate_per_format() {
+    for (output_type, bitrate) in [("mp3", Some("128000")), ("m4a", Some("128000")), ("ogg", Some("96000")), ("flac", None), ("wav", None)] {
+        let book = BookFixture::new(VALID_CHAPTERS);
+        let mut cmd = book.command();
+        cmd.arg("--output-type").arg(output_type);
+        cmd.assert().success();
+        let calls = book.ffmpeg_calls();
+        assert_eq!(calls.len(), 1, "{:?}", calls);
+        match bitrate {
+            Some(bitrate) => assert!(calls[0].contains(&format!("-b:a {} ", bitrate)), "{}", calls[0]),
+            None => assert!(!calls[0].contains("-b:a"), "{}", calls[0]),
+        }
+    }
+}
+
+#[test]
+fn test_mp3_quality() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    book.command().args(["--mp3-quality", "2"]).assert().success();
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 1, "{:?}", calls);
+    assert!(calls[0].contains("-codec:a mp3 -q:a 2 "), "{}", calls[0]);
+    assert!(!calls[0].contains("-b:a"), "{}", calls[0]);
+
+    book.command()
+        .args(["--mp3-quality", "2", "-T", "ogg"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--mp3-quality requires --output-type mp3, got ogg."));
+    book.command().args(["--mp3-quality", "10"]).assert().failure();
+}
+
+#[test]
+fn test_exclude_output_type() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.args(["-T", "wav", "--exclude-output-type", "aiff", "--exclude-output-type", "wav"]);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Output type wav is excluded by --exclude-output-type"));
+    assert!(book.ffmpeg_calls().is_empty());
+
+    let mut cmd = book.command();
+    cmd.args(["-T", "flac", "--exclude-output-type", "wav"]);
+    cmd.assert().success();
+}
+
+#[test]
+fn test_ffprobe_optional_tags() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let ffprobe = VALID_FFPROBE
+        .replace("\"genre\": \"genre\",", "\"genre\": \"\",")
+        .replace("\"comment\": \"comment\",", "")
+        .replace("\"copyright\": \"copyright\",", "")
+        .replace("\"album\": \"album\",", "\"album\": \"album\"")
+        .replace("\"date\": \"2020\"", "");
+    std::fs::write(book.path().join("bin").join("ffprobe.json"), ffprobe).unwrap();
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only");
+    cmd.assert().success();
+}
+
+#[test]
+fn test_log_file_json() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let log_path = book.path().join("run.log");
+    for _ in 0..2 {
+        let mut cmd = book.command();
+        cmd.env("RUST_LOG", "info");
+        cmd.arg("--split").arg("--metadata-only");
+        cmd.arg("--log-file").arg(&log_path).arg("--log-format").arg("json");
+        cmd.assert().success();
+    }
+
+    let log = std::fs::read_to_string(&log_path).unwrap();
+    let events: Vec<serde_json::Value> = log
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("log line is JSON"))
+        .collect();
+    let starts = events.iter().filter(|event| event["message"] == "Starting audible-util").count();
+    assert_eq!(starts, 2, "log file should be appended to");
+    assert!(events.iter().all(|event| event["level"].is_string() && event["timestamp"].is_string()));
+}
+
+#[test]
+fn test_ffmpeg_too_old() {
+    let book = BookFixture::with_ffmpeg_version(VALID_CHAPTERS, "4.2.7-0+deb10u1");
+    let mut cmd = book.command();
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("ffmpeg 4.2.7 is too old"));
+    assert!(book.ffmpeg_calls().is_empty());
+}
+
+#[test]
+fn test_generate_config() {
+    let mut cmd = Command::cargo_bin("audible-util").unwrap();
+    cmd.arg("generate-config");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("output_type = \"mp3\""))
+        .stdout(predicate::str::contains("progress_batch = 1"))
+        .stdout(predicate::str::contains("# aaxc_path = <AAXC_FILE>"));
+
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.toml");
+    let mut cmd = Command::cargo_bin("audible-util").unwrap();
+    cmd.arg("generate-config").arg("--output").arg(&config_path);
+    cmd.assert().success().stdout(predicate::str::is_empty());
+    let config = std::fs::read_to_string(&config_path).unwrap();
+    assert!(config.contains("split_structure = \"flat\""));
+}
+
+#[test]
+fn test_generate_schema() {
+    let schema_of = |name: &str| -> serde_json::Value {
+        let mut cmd = Command::cargo_bin("audible-util").unwrap();
+        let output = cmd.arg("generate-schema").arg(name).output().unwrap();
+        assert!(output.status.success());
+        serde_json::from_slice(&output.stdout).unwrap()
+    };
+    let chapters = jsonschema::validator_for(&schema_of("chapters")).unwrap();
+    let voucher = jsonschema::validator_for(&schema_of("voucher")).unwrap();
+
+    let valid_chapters: serde_json::Value = serde_json::from_str(VALID_CHAPTERS).unwrap();
+    let valid_voucher: serde_json::Value = serde_json::from_str(VALID_VOUCHER).unwrap();
+    assert!(chapters.is_valid(&valid_chapters));
+    assert!(voucher.is_valid(&valid_voucher));
+    assert!(!chapters.is_valid(&valid_voucher));
+    assert!(!voucher.is_valid(&valid_chapters));
+    let wrong_type: serde_json::Value = serde_json::from_str(&VALID_CHAPTERS.replace("\"runtime_length_ms\": 100000", "\"runtime_length_ms\": \"100000\"")).unwrap();
+    assert!(!chapters.is_valid(&wrong_type));
+
+    let dir = tempfile::tempdir().unwrap();
+    let schema_path = dir.path().join("voucher.schema.json");
+    let mut cmd = Command::cargo_bin("audible-util").unwrap();
+    cmd.arg("generate-schema").arg("voucher").arg("--output").arg(&schema_path);
+    cmd.assert().success().stdout(predicate::str::is_empty());
+    let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&schema_path).unwrap()).unwrap();
+    assert_eq!(written, schema_of("voucher"));
+
+    let mut cmd = Command::cargo_bin("audible-util").unwrap();
+    cmd.arg("generate-schema").arg("manifest");
+    cmd.assert().failure().stderr(predicate::str::contains("invalid value 'manifest'"));
+}
+
+#[test]
+fn test_expired_voucher_warns() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let voucher = VALID_VOUCHER.replace("\"expireDate\": \"date\"", "\"expireDate\": \"2020-01-01T00:00:00Z\"");
+    std::fs::write(book.path().join("book.voucher"), voucher).unwrap();
+
+    let mut cmd = book.command();
+    cmd.env("RUST_LOG", "warn");
+    cmd.arg("--split").arg("--metadata-only");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("voucher license expired on 2020-01-01"));
+}
+
+#[test]
+fn test_chapter_gap_fill() {
+    let chapters = r#""chapters": [
+        { "length_ms": 40000, "start_offset_ms": 0, "start_offset_sec": 0, "title": "Opening Credits" },
+        { "length_ms": 50000, "start_offset_ms": 40500, "start_offset_sec": 40, "title": "Chapter 1" }
+      ]"#;
+    let book = BookFixture::new(&chapters_json_with(chapters));
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--chapter-gap-fill");
+    cmd.assert().success();
+
+    // The audio is 100 s long with a 5 s brand outro
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 2, "{:?}", calls);
+    assert!(calls[0].contains("-ss 00:00:00.000 -t 00:00:40.500"), "{}", calls[0]);
+    assert!(calls[1].contains("-ss 00:00:40.500 -t 00:00:54.500"), "{}", calls[1]);
+}
+
+#[test]
+fn test_relative_paths() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("-M").arg("--relative-paths");
+    let output = cmd.assert().success().get_output().stdout.clone();
+
+    let events: Vec<serde_json::Value> = String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let started = events.iter().find(|event| event["type"] == "conversion_started").unwrap();
+    assert_eq!(started["output_path"], ".");
+    let completed: Vec<_> = events
+        .iter()
+        .filter(|event| event["type"] == "chapter_completed")
+        .map(|event| event["output_file"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(completed, vec!["Chapter01_Opening_Credits.mp3", "Chapter02_Chapter_1.mp3"]);
+}
+
+#[test]
+fn test_overlap_strategy() {
+    let chapters = r#""chapters": [
+        { "length_ms": 42000, "start_offset_ms": 0, "start_offset_sec": 0, "title": "Opening Credits" },
+        { "length_ms": 60000, "start_offset_ms": 40000, "start_offset_sec": 40, "title": "Chapter 1" }
+      ]"#;
+    let book = BookFixture::new(&chapters_json_with(chapters));
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--overlap-strategy").arg("error");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("overlaps the next chapter 'Chapter 1' by 2000 ms"));
+    assert!(book.ffmpeg_calls().is_empty());
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--overlap-strategy").arg("truncate");
+    cmd.assert().success();
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 2, "{:?}", calls);
+    assert!(calls[0].contains("-ss 00:00:00.000 -t 00:00:40.000"), "{}", calls[0]);
+}
+
+#[test]
+fn test_output_dir_template() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--output-dir-template").arg("{artist}/{album} ({year})");
+    cmd.assert().success();
+
+    let book_dir = book.path().join("out").join("artist").join("album_2020");
+    assert!(book_dir.is_dir());
+    assert!(book_dir.join("Chapter01_Opening_Credits.mp3").exists());
+
+    let mut cmd = book.command();
+    cmd.arg("--output-dir-template").arg("{publisher}");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown placeholder {publisher}"));
+}
+
+#[test]
+fn test_asin_dirs() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--asin-dirs");
+    cmd.assert().success();
+    assert!(book.path().join("out").join("asin").join("Chapter01_Opening_Credits.mp3").exists());
+
+    // Without --split the ASIN comes from the voucher
+    let mut cmd = book.command();
+    cmd.arg("--asin-dirs");
+    cmd.assert().success();
+    assert!(book.path().join("out").join("asin").join("album.mp3").exists());
+}
+
+#[test]
+fn test_album_dir() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--album-dir").arg("--playlist").arg("book.m3u");
+    cmd.current_dir(book.path());
+    cmd.assert().success();
+
+    let album_dir = book.path().join("out").join("album");
+    assert!(album_dir.join("Chapter01_Opening_Credits.mp3").exists());
+    let playlist = std::fs::read_to_string(album_dir.join("book.m3u")).unwrap();
+    assert!(playlist.contains("Chapter01_Opening_Credits.mp3"));
+    assert!(!book.path().join("book.m3u").exists());
+}
+
+#[test]
+fn test_no_ffmpeg_metadata() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split");
+    cmd.assert().success();
+    assert!(book.ffmpeg_calls().iter().all(|call| call.contains("-map_metadata 0")));
+
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--no-ffmpeg-metadata");
+    cmd.assert().success();
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 2);
+    assert!(calls.iter().all(|call| call.contains("-map_metadata -1")));
+}
+
+#[test]
+fn test_audio_filter() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--audio-filter").arg("atempo=1.25");
+    cmd.assert().success();
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 2);
+    assert!(calls.iter().all(|call| call.contains("-af atempo=1.25 -vn")), "{:?}", calls);
+}
+
+#[test]
+fn test_metadata_map() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split")
+        .arg("--metadata-map").arg("genre=Fantasy")
+        .arg("--metadata-map").arg("artist=Jane Doe")
+        .arg("--output-dir-template").arg("{artist}/{genre}");
+    cmd.assert().success();
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 2);
+    assert!(calls.iter().all(|call| call.contains("-metadata artist=Jane Doe -metadata genre=Fantasy")), "{:?}", calls);
+    assert!(book.path().join("out").join("Jane_Doe").join("Fantasy").join("Chapter01_Opening_Credits.mp3").exists());
+
+    book.command()
+        .arg("--metadata-map").arg("publisher=Nobody")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown field 'publisher'"));
+}
+
+#[test]
+fn test_conditional_skips_up_to_date_output() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    // Backdate the input so the chapter files are clearly newer, regardless of timestamp granularity
+    let aaxc = std::fs::File::options().write(true).open(book.path().join("book.aaxc")).unwrap();
+    aaxc.set_modified(std::time::SystemTime::now() - std::time::Duration::from_secs(60)).unwrap();
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--conditional");
+    cmd.assert().success();
+    assert_eq!(book.ffmpeg_calls().len(), 2);
+
+    // The chapter files are now newer than the input
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--if-newer");
+    cmd.assert().success();
+    assert_eq!(book.ffmpeg_calls().len(), 2);
+
+    // --benchmark writes nothing, so it runs regardless
+    book.command().args(["--split", "--conditional", "--benchmark"]).assert().success();
+    assert_eq!(book.ffmpeg_calls().len(), 4);
+}
+
+#[test]
+fn test_ffprobe_requires_audio_stream() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let video_only = VALID_FFPROBE
+        .replace("\"codec_name\": \"aac\"", "\"codec_name\": \"mjpeg\"")
+        .replace("\"codec_type\": \"audio\"", "\"codec_type\": \"video\"");
+    std::fs::write(book.path().join("bin").join("ffprobe.json"), video_only).unwrap();
+    book.command()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no audio stream found (only video)"));
+
+    let unexpected_codec = VALID_FFPROBE.replace("\"codec_name\": \"aac\"", "\"codec_name\": \"mp3\"");
+    std::fs::write(book.path().join("bin").join("ffprobe.json"), unexpected_codec).unwrap();
+    book.command()
+        .env("RUST_LOG", "warn")
+        .arg("--split")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Unexpected audio codec 'mp3'"));
+}
+
+#[test]
+fn test_error_exit_codes() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    std::fs::remove_file(book.path().join("book.aaxc")).unwrap();
+    book.command()
+        .assert()
+        .code(3)
+        .stderr(predicate::str::contains("Input file does not exist"));
+
+    let book = BookFixture::new(VALID_CHAPTERS);
+    std::fs::write(book.path().join("book.voucher"), "not json").unwrap();
+    book.command()
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("Failed to parse voucher file"));
+
+    let book = BookFixture::new(VALID_CHAPTERS);
+    std::fs::remove_file(book.path().join("book-chapters.json")).unwrap();
+    book.command()
+        .arg("--split")
+        .assert()
+        .code(5)
+        .stderr(predicate::str::contains("Chapter file does not exist"));
+
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let bin = book.path().join("bin");
+    std::fs::rename(bin.join("ffmpeg"), bin.join("ffmpeg-stub")).unwrap();
+    let failing = format!(
+        "#!/bin/sh\ncase \"$1\" in -version|-hide_banner) exec '{}' \"$@\";; esac\nexit 7\n",
+        bin.join("ffmpeg-stub").display()
+    );
+    std::fs::write(bin.join("ffmpeg"), failing).unwrap();
+    std::fs::set_permissions(bin.join("ffmpeg"), std::fs::Permissions::from_mode(0o755)).unwrap();
+    book.command()
+        .arg("--split")
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains("ffmpeg failed with exit code 7"));
+}
+
+#[test]
+fn test_ffprobe_timeout() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let bin = book.path().join("bin");
+    std::fs::write(bin.join("ffprobe"), "#!/bin/sh\nexec sleep 30\n").unwrap();
+    std::fs::set_permissions(bin.join("ffprobe"), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let started = std::time::Instant::now();
+    book.command()
+        .arg("--ffprobe-timeout")
+        .arg("1")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("ffprobe did not finish within 1 seconds"));
+    assert!(started.elapsed() < std::time::Duration::from_secs(20));
+}
+
+#[test]
+fn test_keep_temp_files() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    // ffmpeg writes part of the output, then fails
+    let bin = book.path().join("bin");
+    std::fs::rename(bin.join("ffmpeg"), bin.join("ffmpeg-stub")).unwrap();
+    let failing = format!(
+        "#!/bin/sh\ncase \"$1\" in -version|-hide_banner) exec '{}' \"$@\";; esac\n\
+         for last; do :; done\necho partial > \"$last\"\nexit 1\n",
+        bin.join("ffmpeg-stub").display()
+    );
+    std::fs::write(bin.join("ffmpeg"), failing).unwrap();
+    std::fs::set_permissions(bin.join("ffmpeg"), std::fs::Permissions::from_mode(0o755)).unwrap();
+    let temp_file = book.path().join("out").join("Chapter01_Opening_Credits.tmp.mp3");
+
+    book.command().arg("--split").assert().failure();
+    assert!(!temp_file.exists());
+
+    book.command()
+        .arg("--split")
+        .arg("--keep-temp-files")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Keeping temporary file for inspection"));
+    assert_eq!(std::fs::read_to_string(&temp_file).unwrap(), "partial\n");
+    assert!(!book.path().join("out").join("Chapter01_Opening_Credits.mp3").exists());
+}
+
+#[test]
+fn test_retry_failed_chapter() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    // Wrap the stub so the first conversion fails
+    let bin = book.path().join("bin");
+    std::fs::rename(bin.join("ffmpeg"), bin.join("ffmpeg-stub")).unwrap();
+    let flaky = format!(
+        "#!/bin/sh\ncase \"$1\" in -version|-hide_banner) exec '{1}' \"$@\";; esac\n\
+         if [ ! -e '{0}' ]; then : > '{0}'; exit 1; fi\nexec '{1}' \"$@\"\n",
+        bin.join("failed-once").display(),
+        bin.join("ffmpeg-stub").display()
+    );
+    std::fs::write(bin.join("ffmpeg"), flaky).unwrap();
+    std::fs::set_permissions(bin.join("ffmpeg"), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("-M").arg("--retry").arg("1");
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let warning: serde_json::Value = String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .find(|event| event["type"] == "warning")
+        .expect("retry warning event");
+    assert_eq!(warning["code"], "retry");
+    assert_eq!(warning["retry_count"], 1);
+    assert_eq!(warning["chapter_number"], 1);
+    assert_eq!(book.ffmpeg_calls().len(), 2);
+
+    book.command()
+        .arg("--split")
+        .arg("--retry")
+        .arg("6")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_chapter_prefix() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--chapter-prefix").arg("Kapitel");
+    cmd.assert().success();
+    assert!(book.path().join("out").join("Kapitel01_Opening_Credits.mp3").exists());
+    assert!(book.path().join("out").join("Kapitel02_Chapter_1.mp3").exists());
+}
+
+#[test]
+fn test_m3u_playlist() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let playlist = book.path().join("book.m3u8");
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--playlist").arg(&playlist);
+    cmd.assert().success();
+    assert_eq!(
+        std::fs::read_to_string(&playlist).unwrap(),
+        "#EXTM3U\n\
+         #EXTINF:40,artist - Opening Credits\n#EXTALB:album\n#EXTART:artist\n#EXTGENRE:genre\n#EXTCHAP:1\n\
+         out/Chapter01_Opening_Credits.mp3\n\
+         #EXTINF:60,artist - Chapter 1\n#EXTALB:album\n#EXTART:artist\n#EXTGENRE:genre\n#EXTCHAP:2\n\
+         out/Chapter02_Chapter_1.mp3\n"
+    );
+
+    let playlist = book.path().join("out").join("book.m3u");
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--playlist").arg(&playlist);
+    cmd.assert().success();
+    assert_eq!(
+        std::fs::read_to_string(&playlist).unwrap(),
+        "Chapter01_Opening_Credits.mp3\nChapter02_Chapter_1.mp3\n"
+    );
+
+    // Regenerated without converting the audio again
+    std::fs::remove_file(&playlist).unwrap();
+    book.command().arg("--split").arg("--metadata-only").arg("--playlist").arg(&playlist).assert().success();
+    assert_eq!(
+        std::fs::read_to_string(&playlist).unwrap(),
+        "Chapter01_Opening_Credits.mp3\nChapter02_Chapter_1.mp3\n"
+    );
+    assert_eq!(book.ffmpeg_calls().len(), 4);
+}
+
+#[test]
+fn test_pipeline_stdin_to_stdout() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let pipeline = || {
+        let mut cmd = Command::cargo_bin("audible-util").unwrap();
+        cmd.current_dir(book.path());
+        cmd.env("PATH", format!("{}:/usr/bin:/bin", book.path().join("bin").display()));
+        cmd.args(["--aaxc_path", "-", "--output-path", "-", "--key", "00ff", "--iv", "ff00"]);
+        cmd
+    };
+
+    pipeline()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--duration"));
+
+    let output = pipeline()
+        .args(["--duration", "100", "-M"])
+        .write_stdin("")
+        .assert()
+        .success()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("\"type\":\"conversion_started\""), "{}", stderr);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("\"type\""));
+
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 1, "{:?}", calls);
+    assert!(calls[0].contains("-audible_key 00ff -audible_iv ff00 -i pipe:0"), "{}", calls[0]);
+    assert!(calls[0].ends_with("-f mp3 pipe:1"), "{}", calls[0]);
+}
+
+#[test]
+fn test_export_chapters() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let chapters_path = book.path().join("book-chapters.json");
+    std::fs::remove_file(&chapters_path).unwrap();
+    let ffprobe = VALID_FFPROBE.replacen(
+        "{",
+        r#"{
+  "chapters": [
+    { "id": 0, "time_base": "1/1000", "start": 0, "start_time": "0.000000", "end": 40000, "end_time": "40.000000", "tags": { "title": "Opening Credits" } },
+    { "id": 1, "time_base": "1/1000", "start": 40000, "start_time": "40.000000", "end": 100000, "end_time": "100.000000", "tags": {} }
+  ],"#,
+        1,
+    );
+    std::fs::write(book.path().join("bin").join("ffprobe.json"), ffprobe).unwrap();
+
+    let export = || {
+        let mut cmd = Command::cargo_bin("audible-util").unwrap();
+        cmd.env("PATH", format!("{}:/usr/bin:/bin", book.path().join("bin").display()));
+        cmd.arg("export-chapters").arg(book.path().join("book.aaxc"));
+        cmd
+    };
+    export().assert().success();
+
+    let chapters: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&chapters_path).unwrap()).unwrap();
+    let info = &chapters["content_metadata"]["chapter_info"];
+    assert_eq!(info["brandIntroDurationMs"], 0);
+    assert_eq!(info["runtime_length_ms"], 100000);
+    assert_eq!(info["chapters"][0]["title"], "Opening Credits");
+    assert_eq!(info["chapters"][1]["title"], "Chapter 2");
+    assert_eq!(info["chapters"][1]["length_ms"], 60000);
+    assert_eq!(chapters["content_metadata"]["content_reference"]["asin"], "asin");
+
+    // The exported file is usable for splitting
+    book.command().arg("--split").assert().success();
+    assert_eq!(book.ffmpeg_calls().len(), 2);
+
+    export()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Use --force to overwrite it"));
+    export().arg("--force").assert().success();
+}
+
+#[test]
+fn test_conversion_started_book_identity() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let output = book.command()
+        .arg("-M")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let started: serde_json::Value = String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .find(|event| event["type"] == "conversion_started")
+        .expect("conversion_started event");
+    assert_eq!(started["asin"], "asin");
+    assert_eq!(started["book_title"], "title");
+    assert_eq!(started["artist"], "artist");
+    assert_eq!(started["audio_codec"], "aac");
+    assert_eq!(started["sample_rate_hz"], 44100);
+    assert_eq!(started["channels"], 2);
+    assert_eq!(started["source_bitrate_bps"], 125588);
+}
+
+#[test]
+fn test_chapter_summary() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let output = book.command()
+        .arg("--split")
+        .arg("-M")
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let started: serde_json::Value = String::from_utf8_lossy(&output)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .find(|event| event["type"] == "conversion_started")
+        .expect("conversion_started event");
+    assert_eq!(started["chapter_count"], 2);
+    assert_eq!(started["total_audio_duration_ms"], 100000);
+    assert_eq!(started["max_chapter_depth"], 1);
+    assert_eq!(started["shortest_chapter_ms"], 40000);
+    assert_eq!(started["longest_chapter_ms"], 60000);
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "2 chapters, 00:01:40 total, 1 levels, average 00:00:50, shortest \"Opening Credits\" (00:00:40), longest \"Chapter 1\" (00:01:00)",
+        ));
+}
+
+#[test]
+fn test_ffmpeg_without_audible_support() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let ffmpeg = "#!/bin/sh\nif [ \"$1\" = -version ]; then echo 'ffmpeg version 6.1.1'; exit 0; fi\n\
+                  echo \"Unrecognized option 'audible_key'.\" >&2\n\
+                  echo 'Error splitting the argument list: Option not found' >&2\nexit 1\n";
+    std::fs::write(book.path().join("bin").join("ffmpeg"), ffmpeg).unwrap();
+    book.command()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("does not support the -audible_key option"));
+}
+
+#[test]
+fn test_aax_activation_bytes() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    std::fs::rename(book.path().join("book.aaxc"), book.path().join("book.aax")).unwrap();
+    std::fs::remove_file(book.path().join("book.voucher")).unwrap();
+    let aax_command = || {
+        let mut cmd = Command::cargo_bin("audible-util").unwrap();
+        cmd.env("PATH", format!("{}:/usr/bin:/bin", book.path().join("bin").display()));
+        cmd.env_remove("AUDIBLE_ACTIVATION_BYTES");
+        cmd.arg("--aaxc_path").arg(book.path().join("book.aax"));
+        cmd.arg("--output-path").arg(book.path().join("out"));
+        cmd
+    };
+
+    aax_command()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--activation-bytes"));
+
+    // The voucher isn't needed, ffmpeg decrypts with the activation bytes
+    let mut cmd = aax_command();
+    cmd.env("AUDIBLE_ACTIVATION_BYTES", "1A2B3C4D").arg("--split");
+    cmd.assert().success();
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 2);
+    assert!(calls.iter().all(|call| call.contains("-activation_bytes 1a2b3c4d -i")), "{:?}", calls);
+    assert!(calls.iter().all(|call| !call.contains("-audible_key")), "{:?}", calls);
+
+    let mut cmd = aax_command();
+    cmd.arg("--activation-bytes").arg("xyz");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("expected 8 hex digits"));
+}
+
+#[test]
+fn test_key_and_iv_from_command() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    std::fs::remove_file(book.path().join("book.voucher")).unwrap();
+
+    let mut cmd = book.command();
+    cmd.arg("--key-from-cmd").arg("echo 00ff11ee").arg("--iv-from-cmd").arg("printf '  aa55\\n'");
+    cmd.assert().success();
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 1);
+    assert!(calls[0].contains("-audible_key 00ff11ee -audible_iv aa55 -i"), "{}", calls[0]);
+
+    let mut cmd = book.command();
+    cmd.arg("--key-from-cmd").arg("exit 3").arg("--iv").arg("aa55");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("The command for the decryption key failed with exit code 3"));
+
+    let mut cmd = book.command();
+    cmd.arg("--key").arg("00ff").arg("--iv-from-cmd").arg("echo not-a-secret");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("did not print a hex string"))
+        .stderr(predicate::str::contains("not-a-secret").not());
+
+    // The key and IV still come in pairs
+    let mut cmd = book.command();
+    cmd.arg("--key-from-cmd").arg("echo 00ff");
+    cmd.assert().failure().stderr(predicate::str::contains("--iv"));
+}
+
+#[test]
+fn test_min_max_chapter() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only").arg("--min-chapter").arg("2");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Chapter_1.mp3"))
+        .stdout(predicate::str::contains("Opening_Credits").not());
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--min-chapter").arg("2").arg("--max-chapter").arg("1");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("--min-chapter 2 is greater than --max-chapter 1"));
+
+    // The range only applies to split conversions
+    let mut cmd = book.command();
+    cmd.arg("--max-chapter").arg("1");
+    cmd.assert().failure().stderr(predicate::str::contains("--split"));
+}
+
+#[test]
+fn test_chapter_list_file() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let list = book.path().join("chapters.txt");
+    std::fs::write(&list, "# reviewed\n\n2\n").unwrap();
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only").arg("--chapter-list-file").arg(&list);
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Chapter_1.mp3"))
+        .stdout(predicate::str::contains("Opening_Credits.mp3").not());
+
+    // Combined with a range, only chapters selected by both are converted
+    std::fs::write(&list, "1-2\n").unwrap();
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only").arg("--chapter-list-file").arg(&list).arg("--max-chapter").arg("1");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Opening_Credits.mp3"))
+        .stdout(predicate::str::contains("Chapter_1.mp3").not());
+
+    std::fs::write(&list, "1\n2-x\n").unwrap();
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--chapter-list-file").arg(&list);
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("chapters.txt:2: 'x' is not a chapter number"));
+}
+
+#[test]
+fn test_title_disambiguation() {
+    let chapters = r#""chapters": [
+        { "length_ms": 40000, "start_offset_ms": 0, "start_offset_sec": 0, "title": "Interlude" },
+        { "length_ms": 30000, "start_offset_ms": 40000, "start_offset_sec": 40, "title": "Chapter 1" },
+        { "length_ms": 30000, "start_offset_ms": 70000, "start_offset_sec": 70, "title": "Interlude" }
+      ]"#;
+    let book = BookFixture::new(&chapters_json_with(chapters));
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only").arg("-f").arg("title-only").arg("--title-disambiguation").arg("number");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Interlude_01.mp3"))
+        .stdout(predicate::str::contains("Interlude_03.mp3"))
+        .stdout(predicate::str::contains("Chapter_1.mp3"));
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("--metadata-only").arg("-f").arg("title-only").arg("--title-disambiguation").arg("time");
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::contains("Interlude_00-00-00.mp3"))
+        .stdout(predicate::str::contains("Interlude_00-01-10.mp3"));
+}
+
+#[test]
+fn test_duplicate_filenames_are_rejected() {
+    let chapters = r#""chapters": [
+        { "length_ms": 40000, "start_offset_ms": 0, "start_offset_sec": 0, "title": "Interlude" },
+        { "length_ms": 30000, "start_offset_ms": 40000, "start_offset_sec": 40, "title": "Chapter 1" },
+        { "length_ms": 30000, "start_offset_ms": 70000, "start_offset_sec": 70, "title": "Interlude" }
+      ]"#;
+    let book = BookFixture::new(&chapters_json_with(chapters));
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("-f").arg("title-only");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Several chapters would be written to the same file"))
+        .stderr(predicate::str::contains("Interlude.mp3 (chapters 1, 3)"));
+    assert!(book.ffmpeg_calls().is_empty());
+
+    let mut cmd = book.command();
+    cmd.arg("--split").arg("-f").arg("title-only").arg("--allow-duplicate-filenames");
+    cmd.env("RUST_LOG", "warn");
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("Chapters would overwrite each other"));
+    assert_eq!(book.ffmpeg_calls().len(), 3);
+}
+
+#[test]
+fn test_progress_bars_only_on_a_terminal() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    // stderr is a pipe here, so progress bars are off unless forced
+    let output = book.command().arg("--split").output().unwrap();
+    assert!(output.status.success());
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("chapters ["));
+
+    let output = book.command().args(["--split", "--force-progress"]).output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("chapters ["), "{}", String::from_utf8_lossy(&output.stderr));
+
+    book.command()
+        .args(["--no-progress", "--force-progress"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_max_speed() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    book.command().args(["--split", "--max-speed", "1000x"]).assert().success();
+
+    book.command()
+        .args(["--max-speed", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected a positive speed such as 1.5x, got '0'"));
+}
+
+#[test]
+fn test_chapter_search_parents() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    // The book two levels below the chapters file at the library root
+    let nested = book.path().join("author").join("series");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::rename(book.path().join("book.aaxc"), nested.join("book.aaxc")).unwrap();
+    std::fs::rename(book.path().join("book.voucher"), nested.join("book.voucher")).unwrap();
+    let command = |search_parents: &str| {
+        let mut cmd = Command::cargo_bin("audible-util").unwrap();
+        cmd.env("PATH", format!("{}:/usr/bin:/bin", book.path().join("bin").display()));
+        cmd.arg("--aaxc_path").arg(nested.join("book.aaxc"));
+        cmd.arg("--output-path").arg(book.path().join("out"));
+        cmd.args(["--split", "--metadata-only", "--chapter-search-parents", search_parents]);
+        cmd
+    };
+    std::fs::create_dir_all(book.path().join("out")).unwrap();
+
+    command("1").assert().failure().stderr(predicate::str::contains("book-chapters.json"));
+    command("2").assert().success().stdout(predicate::str::contains("Opening Credits"));
+
+    // A closer chapters file wins
+    std::fs::write(
+        book.path().join("author").join("book-chapters.json"),
+        VALID_CHAPTERS.replace("Opening Credits", "Closer Credits"),
+    )
+    .unwrap();
+    command("5").assert().success().stdout(predicate::str::contains("Closer Credits"));
+}
+
+#[test]
+fn test_embed_chapters() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    book.command()
+        .args(["--embed-chapters", "-T", "mp3"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--embed-chapters requires --output-type m4a, got mp3."));
+    assert!(book.ffmpeg_calls().is_empty());
+
+    book.command().args(["--embed-chapters", "-T", "m4a"]).assert().success();
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 1, "{:?}", calls);
+    let metadata_file = book.path().join("out").join("album.chapters.ffmetadata");
+    assert!(calls[0].contains(&format!("book.aaxc -i {} ", metadata_file.display())), "{}", calls[0]);
+    assert!(calls[0].contains("-map_metadata 0 -map_chapters 1 "), "{}", calls[0]);
+    // Removed again after the conversion
+    assert!(!metadata_file.exists());
+}
+
+/// A ZIP archive at `zip_path` with the given entries
+fn write_zip(zip_path: &std::path::Path, entries: &[(&str, &str)]) {
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(zip_path).unwrap());
+    for (name, content) in entries {
+        zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn test_zip_path() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let zip_path = book.path().join("book.zip");
+    write_zip(&zip_path, &[
+        ("export/Book-AAX_44_128.aaxc", ""),
+        ("export/Book-AAX_44_128.voucher", VALID_VOUCHER),
+        ("export/Book-chapters.json", VALID_CHAPTERS),
+        ("export/cover.jpg", ""),
+    ]);
+    let command = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("audible-util").unwrap();
+        cmd.env("PATH", format!("{}:/usr/bin:/bin", book.path().join("bin").display()));
+        cmd.arg("--zip-path").arg(&zip_path);
+        cmd.arg("--output-path").arg(book.path().join("out"));
+        cmd.args(extra);
+        cmd
+    };
+    std::fs::create_dir_all(book.path().join("out")).unwrap();
+
+    command(&["--split", "--metadata-only"]).assert().success().stdout(predicate::str::contains("Opening Credits"));
+
+    let extract_dir = book.path().join("extracted");
+    command(&["--zip-extract-dir", extract_dir.to_str().unwrap()]).assert().success();
+    let calls = book.ffmpeg_calls();
+    assert!(calls.last().unwrap().contains(&format!("-i {} ", extract_dir.join("Book-AAX_44_128.aaxc").display())), "{:?}", calls);
+    assert!(extract_dir.join("Book-chapters.json").exists());
+    assert!(!extract_dir.join("cover.jpg").exists());
+
+    write_zip(&zip_path, &[("Book.aaxc", ""), ("Book.voucher", VALID_VOUCHER), ("Other.voucher", VALID_VOUCHER)]);
+    command(&[])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("contains more than one .voucher file: Book.voucher, Other.voucher"));
+}
+
+#[test]
+fn test_strip_title_prefix() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    std::fs::create_dir_all(book.path().join("out")).unwrap();
+    book.command()
+        .args(["--split", "--metadata-only", "--strip-title-prefix", r"^(Opening|Chapter \d+)\s*"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Chapter01_Credits.mp3"))
+        // Nothing would be left of "Chapter 1", so it's kept
+        .stdout(predicate::str::contains("Chapter02_Chapter_1.mp3"));
+    book.command()
+        .args(["--strip-title-prefix", "(unclosed"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value '(unclosed' for '--strip-title-prefix <REGEX>'"));
+}
+
+#[test]
+fn test_chapter_time_offset() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    std::fs::create_dir_all(book.path().join("out")).unwrap();
+    book.command()
+        .args(["--split", "--metadata-only", "--timestamps", "--chapter-time-offset", "2000"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[00:00:02 - 00:00:42]").and(predicate::str::contains("[00:00:42 - 00:01:42]")));
+    // The opening credits can't start before the audio, they keep their end instead
+    book.command()
+        .args(["--split", "--metadata-only", "--timestamps", "--chapter-time-offset", "-1000"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[00:00:00 - 00:00:39]").and(predicate::str::contains("[00:00:39 - 00:01:39]")));
+}
+
+#[test]
+fn test_validate_chapters() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let chapters_path = book.path().join("book-chapters.json");
+    let validate = |extra: &[&str]| {
+        let mut cmd = Command::cargo_bin("audible-util").unwrap();
+        cmd.arg("validate-chapters").arg(&chapters_path).args(extra);
+        cmd
+    };
+
+    validate(&[]).assert().success().stdout(predicate::str::contains("2 chapters").and(predicate::str::contains("Valid (0 warnings)")));
+
+    // Chapter 1 starting 5 s early overlaps the opening credits
+    std::fs::write(&chapters_path, VALID_CHAPTERS.replace(r#""start_offset_ms": 40000, "start_offset_sec": 40"#, r#""start_offset_ms": 35000, "start_offset_sec": 35"#)).unwrap();
+    validate(&[])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("Error: \"Opening Credits\" overlaps \"Chapter 1\" by 5000 ms"))
+        .stderr(predicate::str::contains("Chapter file is not valid"));
+    let output = validate(&["-M"]).assert().code(1).get_output().stdout.clone();
+    let report: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(report["type"], "validation_report");
+    assert_eq!(report["parsed"], true);
+    assert_eq!(report["valid"], false);
+    assert_eq!(report["chapter_count"], 2);
+
+    std::fs::write(&chapters_path, "{ not json").unwrap();
+    validate(&[]).assert().code(2).stderr(predicate::str::contains("Chapter file could not be parsed"));
+}
+
+/// Answer one HTTP request with `body`, returning the request as received
+fn serve_once(body: String) -> (String, std::thread::JoinHandle<String>) {
+    use std::io::{BufRead, BufReader};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/books/Served-chapters.json", listener.local_addr().unwrap());
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = String::new();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                break;
+            }
+            request.push_str(&line);
+        }
+        write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body).unwrap();
+        request
+    });
+    (url, server)
+}
+
+#[test]
+fn test_chapter_path() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    std::fs::create_dir_all(book.path().join("out")).unwrap();
+    let local = book.path().join("other-chapters.json");
+    std::fs::write(&local, VALID_CHAPTERS.replace("Opening Credits", "Local Credits")).unwrap();
+    book.command()
+        .args(["--split", "--metadata-only", "--chapter-path", local.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Local Credits"));
+
+    let (url, server) = serve_once(VALID_CHAPTERS.replace("Opening Credits", "Served Credits"));
+    book.command()
+        .args(["--split", "--metadata-only", "--chapter-path", &url])
+        .args(["--chapter-path-header", "Authorization: Bearer secret"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Served Credits"));
+    let request = server.join().unwrap();
+    assert!(request.starts_with("GET /books/Served-chapters.json "), "{}", request);
+    assert!(request.contains("Authorization: Bearer secret\r\n"), "{}", request);
+
+    book.command()
+        .args(["--chapter-path-header", "no-colon"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected KEY:VALUE, got 'no-colon'"));
+}
+
+#[test]
+fn test_benchmark_writes_nothing() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    let out = book.path().join("out");
+    book.command()
+        .args(["--split", "--benchmark"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Chapters:       2"));
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 2, "{:?}", calls);
+    assert!(calls.iter().all(|call| call.ends_with("-f mp3 /dev/null")), "{:?}", calls);
+    assert_eq!(std::fs::read_dir(&out).unwrap().count(), 0);
+
+    let output = book.command().args(["--benchmark", "-M"]).output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains(r#"{"type":"benchmark_result","total_chapters":1,"#));
+    assert_eq!(std::fs::read_dir(&out).unwrap().count(), 0);
+}
+
+#[test]
+fn test_merge_on_silence() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    // The stub ffmpeg reports no silence, so the only boundary is merged away
+    book.command()
+        .args(["--split", "--metadata-only", "--merge-on-silence"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1/1 ").and(predicate::str::contains("Chapter01_Opening_Credits_includes_Chapter_1.mp3")));
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 1, "{:?}", calls);
+    assert!(calls[0].contains("-ss 00:00:38.000 -t 00:00:04.000 -i "), "{}", calls[0]);
+    assert!(calls[0].contains("-af silencedetect=noise=-30dB:d=0.3 -vn -f null /dev/null"), "{}", calls[0]);
+}
+
+#[test]
+fn test_split_at_silence() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    // Report one silence in the middle of the book for the whole-book silencedetect pass
+    let bin = book.path().join("bin");
+    std::fs::rename(bin.join("ffmpeg"), bin.join("ffmpeg-stub")).unwrap();
+    let silent = format!(
+        "#!/bin/sh
+case \"$*\" in *silencedetect*) echo 'silence_start: 49' >&2; \
+         echo 'silence_end: 52 | silence_duration: 3' >&2;; esac\nexec '{}' \"$@\"\n",
+        bin.join("ffmpeg-stub").display()
+    );
+    std::fs::write(bin.join("ffmpeg"), silent).unwrap();
+    std::fs::set_permissions(bin.join("ffmpeg"), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    book.command()
+        .args(["--split-at-silence", "--metadata-only"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Chapter01_Part_1.mp3").and(predicate::str::contains("Chapter02_Part_2.mp3")));
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 1, "{:?}", calls);
+    assert!(calls[0].contains("-af silencedetect=noise=-30dB:d=2.0 -vn -f null /dev/null"), "{}", calls[0]);
+    assert!(!calls[0].contains("-ss "), "{}", calls[0]);
+
+    book.command()
+        .args(["--split", "--split-at-silence"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_warn_chapter_count_without_terminal() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    // stdin isn't a terminal, so the split goes ahead without asking
+    book.command()
+        .args(["--split", "--warn-chapter-count", "1"])
+        .env("RUST_LOG", "info")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Writing 2 chapter files, more than --warn-chapter-count 1").and(predicate::str::contains("Continue?").not()));
+    assert_eq!(book.ffmpeg_calls().len(), 2);
+
+    // --benchmark writes no files
+    book.command()
+        .args(["--split", "--benchmark", "--warn-chapter-count", "1"])
+        .env("RUST_LOG", "info")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("--warn-chapter-count").not());
+
+    book.command().args(["--warn-chapter-count", "1"]).assert().failure();
+}
+
+#[test]
+fn test_chapters_from_ffprobe() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    std::fs::remove_file(book.path().join("book-chapters.json")).unwrap();
+    // The stub ffprobe prints embedded chapter markers when asked for them
+    let bin = book.path().join("bin");
+    std::fs::write(
+        bin.join("chapters.json"),
+        r#"{"chapters": [
+            {"id": 0, "time_base": "1/1000", "start": 0, "start_time": "0.000000", "end": 30000, "end_time": "30.000000", "tags": {"title": "Prologue"}},
+            {"id": 1, "time_base": "1/1000", "start": 30000, "start_time": "30.000000", "end": 100000, "end_time": "100.000000"}
+        ]}"#,
+    )
+    .unwrap();
+    let ffprobe = format!(
+        "#!/bin/sh
+case \"$*\" in *-show_chapters*) exec /bin/cat '{}';; esac\nexec /bin/cat '{}'\n",
+        bin.join("chapters.json").display(),
+        bin.join("ffprobe.json").display()
+    );
+    std::fs::write(bin.join("ffprobe"), ffprobe).unwrap();
+
+    book.command().arg("--split").assert().failure();
+    book.command()
+        .args(["--split", "--metadata-only", "--chapters-from-ffprobe"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Chapter01_Prologue.mp3").and(predicate::str::contains("Chapter02_Chapter_2.mp3")));
+    assert!(book.ffmpeg_calls().is_empty());
+}
+
+#[test]
+fn test_chapter_metadata_tags() {
+    let book = BookFixture::new(VALID_CHAPTERS);
+    book.command().arg("--split").assert().success();
+    let calls = book.ffmpeg_calls();
+    assert_eq!(calls.len(), 2);
+    assert!(calls[0].contains("-metadata title=Opening Credits -metadata track=1/2 -metadata album=album -metadata artist=artist -metadata genre=genre "), "{}", calls[0]);
+    assert!(calls[1].contains("-metadata title=Chapter 1 -metadata track=2/2 "), "{}", calls[1]);
+}